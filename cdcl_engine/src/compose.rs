@@ -0,0 +1,545 @@
+use crate::{
+    engine::EngineTrait,
+    outer_engine::{PropagationPolicy, PropagationStatistics},
+};
+
+/// 合成したエンジンから理論を型で取り出す
+///
+/// compose_engine! が合成した各理論について実装する．
+pub trait EngineTheoryTrait<TheoryT> {
+    fn theory_ref(&self) -> &TheoryT;
+
+    fn theory_ref_mut(&mut self) -> &mut TheoryT;
+
+    /// 理論を加えた層の伝播の方針
+    fn propagation_policy_ref_mut(&mut self) -> &mut PropagationPolicy;
+
+    /// 理論を加えた層の伝播の統計情報
+    fn propagation_statistics_ref(&self) -> &PropagationStatistics;
+}
+
+/// 探索の進捗の表に理論ごとの列として出力する統計情報
+///
+/// compose_engine! が合成した統計情報は，理論を並べた順に各理論の列を続け，最後に CoreEngine の列を置く．
+pub trait ProgressColumnsTrait {
+    /// 列の見出し
+    const HEADERS: &'static [&'static str];
+
+    /// HEADERS と同じ順の列の値
+    fn values(&self) -> impl Iterator<Item = u32>;
+}
+
+/// engine.get_theory::<TheoryT>() の形で理論を取り出すためのトレイト
+pub trait EngineGetTheoryTrait {
+    #[inline(always)]
+    fn get_theory<TheoryT>(&self) -> &TheoryT
+    where
+        Self: EngineTheoryTrait<TheoryT>,
+    {
+        return self.theory_ref();
+    }
+
+    #[inline(always)]
+    fn get_theory_mut<TheoryT>(&mut self) -> &mut TheoryT
+    where
+        Self: EngineTheoryTrait<TheoryT>,
+    {
+        return self.theory_ref_mut();
+    }
+
+    #[inline(always)]
+    fn get_propagation_policy_mut<TheoryT>(&mut self) -> &mut PropagationPolicy
+    where
+        Self: EngineTheoryTrait<TheoryT>,
+    {
+        return self.propagation_policy_ref_mut();
+    }
+
+    #[inline(always)]
+    fn get_propagation_statistics<TheoryT>(&self) -> &PropagationStatistics
+    where
+        Self: EngineTheoryTrait<TheoryT>,
+    {
+        return self.propagation_statistics_ref();
+    }
+}
+
+impl<EngineT> EngineGetTheoryTrait for EngineT where EngineT: EngineTrait {}
+
+/// 理論を外側から順に並べてエンジンを合成する
+///
+/// ```ignore
+/// compose_engine! {
+///     pub type SATEngine;
+///     pub fn new_sat_engine;
+///     pub fn contains_theory_variable;
+///     pub enum ExplainKey;
+///     pub struct SATEngineSummary;
+///     pub type EngineConstraint<ClauseT>;
+///     explanation Constraint { Clause, BinaryClause, MonadicClause };
+///     pub enum TheoryKind;
+///     xor: XorTheory(XorConstraint) = Xor("xor"),
+///     clause: ClauseTheory[ClauseT] = Clause("clause"),
+///     binary_clause: BinaryClauseTheory(BinaryClause),
+/// }
+/// ```
+///
+/// は次のものを生成する．
+/// - `SATEngine = OuterEngine<XorTheory, OuterEngine<ClauseTheory, OuterEngine<BinaryClauseTheory, CoreEngine<ExplainKey>>>>`
+/// - 理論を並べた順に受け取って SATEngine を作る `new_sat_engine(xor: XorTheory, clause: ClauseTheory, binary_clause: BinaryClauseTheory)`
+/// - 各理論の ExplainKey を理論の型名の variant に持つ ExplainKey と，その From の実装
+/// - ExplainKey から SATEngine::ExplainKey (Either の入れ子)への変換
+/// - SATEngine::Summary (タプルの入れ子)を理論ごとの名前のフィールドにした SATEngineSummary と，その From の実装，
+///   各理論の ProgressColumnsTrait の列を並べた進捗の表の見出し progress_headers と値 progress_values
+/// - SATEngine に加える制約 `EngineConstraint<ClauseT> = Either<XorConstraint, Either<ClauseT, Either<BinaryClause, Literal>>>`
+///   と，丸括弧で与えた各理論の制約からの From の実装
+/// - SATEngine の説明 `Either<XorT::ExplanationConstraint, Either<ClauseT::ExplanationConstraint, Either<BinaryClause, Literal>>>`
+///   から，呼び出し側が定義した `Constraint<Either<XorT::ExplanationConstraint, ClauseT::ExplanationConstraint>>` への From の実装．
+///   最も内側の理論の説明は BinaryClause に，CoreEngine の説明のリテラルは MonadicClause に，
+///   それ以外の理論の説明は Either の入れ子にして Clause に入れる
+/// - `Constraint<ClauseT>` から EngineConstraint<ClauseT> への From の実装．Clause は角括弧で与えた理論の制約に，
+///   BinaryClause は最も内側の理論の制約に，MonadicClause は CoreEngine に加える
+/// - 各理論についての EngineTheoryTrait の実装(理論と，理論を加えた層の伝播の方針・統計情報を取り出す)
+/// - `= Variant("name")` を付けた理論を variant に持つ TheoryKind と，コマンドラインで指定する名前の表 TheoryKind::NAMES，
+///   名前から引く TheoryKind::from_name，理論を加えた層の伝播の方針・統計情報を取り出す
+///   TheoryKind::propagation_policy_mut・ TheoryKind::propagation_statistics
+/// - いずれかの理論で変数が節として扱えない制約に現れるかを TheoryTrait::contains_variable で調べる
+///   `contains_theory_variable(engine: &SATEngine, index: u32) -> bool`
+///
+/// 理論の型は型名(識別子)で与え，同じ型を 2 回並べてはならない．理論は 2 つ以上並べ，角括弧で与える制約はちょうど 1 つとする．制約の型は丸括弧で，
+/// 制約の型引数で与える場合は角括弧で囲む．型引数の制約からの From は他の制約の From と重なり，
+/// CoreEngine に加える Literal からの From は孤児規則に反するので実装しない．
+#[macro_export]
+macro_rules! compose_engine {
+    (
+        $(#[$engine_attribute:meta])*
+        $visibility:vis type $engine:ident;
+        $constructor_visibility:vis fn $constructor:ident;
+        $(#[$contains_attribute:meta])*
+        $contains_visibility:vis fn $contains:ident;
+        $(#[$explain_key_attribute:meta])*
+        $explain_key_visibility:vis enum $explain_key:ident;
+        $(#[$summary_attribute:meta])*
+        $summary_visibility:vis struct $summary:ident;
+        $(#[$constraint_attribute:meta])*
+        $constraint_visibility:vis type $constraint:ident<$($parameter:ident),* $(,)?>;
+        $(#[$explanation_attribute:meta])*
+        explanation $explanation:ident { $clause:ident, $last:ident, $core:ident $(,)? };
+        $(#[$kind_attribute:meta])*
+        $kind_visibility:vis enum $kind:ident;
+        $($field:ident: $theory:ident $theory_constraint:tt $(= $kind_variant:ident($kind_name:literal))?),+ $(,)?
+    ) => {
+        $(#[$explain_key_attribute])*
+        #[derive(Clone, Copy)]
+        $explain_key_visibility enum $explain_key {
+            CoreEngine($crate::CoreEngineExplainKey),
+            $($theory(<$theory as $crate::TheoryTrait>::ExplainKey),)+
+        }
+
+        impl From<$crate::CoreEngineExplainKey> for $explain_key {
+            fn from(explain_key: $crate::CoreEngineExplainKey) -> Self {
+                return Self::CoreEngine(explain_key);
+            }
+        }
+
+        $(
+            impl From<<$theory as $crate::TheoryTrait>::ExplainKey> for $explain_key {
+                fn from(explain_key: <$theory as $crate::TheoryTrait>::ExplainKey) -> Self {
+                    return Self::$theory(explain_key);
+                }
+            }
+        )+
+
+        impl From<$explain_key> for $crate::__composite_explain_key_type!($($theory),+) {
+            fn from(explain_key: $explain_key) -> Self {
+                return $crate::__composite_explain_key_into_either!($explain_key, explain_key; $($theory),+);
+            }
+        }
+
+        $(#[$engine_attribute])*
+        $visibility type $engine = $crate::__composite_engine_type!($explain_key; $($theory),+);
+
+        // 理論の数だけ引数をとる
+        #[allow(clippy::too_many_arguments)]
+        $constructor_visibility fn $constructor($($field: $theory),+) -> $engine {
+            return $crate::__composite_engine_new!($($field),+);
+        }
+
+        $(#[$summary_attribute])*
+        #[derive(Default, Clone)]
+        $summary_visibility struct $summary {
+            $(pub $field: <$theory as $crate::TheoryTrait>::Summary,)+
+            pub core_engine: $crate::CoreEngineSummary,
+        }
+
+        impl From<$crate::__composite_summary_type!($($theory),+)> for $summary {
+            fn from(summary: $crate::__composite_summary_type!($($theory),+)) -> Self {
+                let $crate::__composite_summary_pattern!(core_engine; $($field),+) = summary;
+                return Self { $($field: $field,)+ core_engine: core_engine };
+            }
+        }
+
+        impl $summary {
+            /// 進捗の表の理論ごとの列の見出し
+            $summary_visibility fn progress_headers() -> impl Iterator<Item = &'static str> {
+                return ::std::iter::empty()
+                    $(.chain(
+                        <<$theory as $crate::TheoryTrait>::Summary as $crate::ProgressColumnsTrait>::HEADERS
+                            .iter()
+                            .copied(),
+                    ))+
+                    .chain(<$crate::CoreEngineSummary as $crate::ProgressColumnsTrait>::HEADERS.iter().copied());
+            }
+
+            /// progress_headers と同じ順の列の値
+            $summary_visibility fn progress_values(&self) -> impl Iterator<Item = u32> + '_ {
+                return ::std::iter::empty()
+                    $(.chain($crate::ProgressColumnsTrait::values(&self.$field)))+
+                    .chain($crate::ProgressColumnsTrait::values(&self.core_engine));
+            }
+        }
+
+        $(#[$constraint_attribute])*
+        $constraint_visibility type $constraint<$($parameter),*> =
+            $crate::__composite_constraint_type!($($theory_constraint),+);
+
+        $crate::__composite_constraint_from!($constraint<$($parameter),*>; []; $($theory_constraint),+);
+
+        $(#[$explanation_attribute])*
+        impl<'a> From<$crate::__composite_explanation_type!('a; $($theory),+)>
+            for $explanation<$crate::__composite_clause_explanation_type!('a; $($theory),+)>
+        {
+            fn from(explanation: $crate::__composite_explanation_type!('a; $($theory),+)) -> Self {
+                return $crate::__composite_explanation_into!(
+                    $explanation { $clause, $last, $core }; explanation; []; $($theory),+
+                );
+            }
+        }
+
+        $crate::__composite_constraint_from_explanation!(
+            $constraint<$($parameter),*>; $explanation { $clause, $last, $core }; []; []; $($theory_constraint),+
+        );
+
+        $crate::__composite_engine_theories!($engine; [] []; $($theory),+);
+
+        $(#[$kind_attribute])*
+        #[derive(Clone, Copy)]
+        $kind_visibility enum $kind {
+            $($($kind_variant,)?)+
+        }
+
+        impl $kind {
+            /// コマンドラインで指定する名前
+            $kind_visibility const NAMES: &[(&str, Self)] = &[$($(($kind_name, Self::$kind_variant),)?)+];
+
+            $kind_visibility fn from_name(name: &str) -> Option<Self> {
+                return Self::NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, kind)| kind);
+            }
+
+            /// 理論を加えた層の伝播の方針
+            $kind_visibility fn propagation_policy_mut(self, engine: &mut $engine) -> &mut $crate::PropagationPolicy {
+                return match self {
+                    $($(
+                        Self::$kind_variant => {
+                            <$engine as $crate::EngineTheoryTrait<$theory>>::propagation_policy_ref_mut(engine)
+                        }
+                    )?)+
+                };
+            }
+
+            /// 理論を加えた層の伝播の統計情報
+            $kind_visibility fn propagation_statistics(self, engine: &$engine) -> &$crate::PropagationStatistics {
+                return match self {
+                    $($(
+                        Self::$kind_variant => {
+                            <$engine as $crate::EngineTheoryTrait<$theory>>::propagation_statistics_ref(engine)
+                        }
+                    )?)+
+                };
+            }
+        }
+
+        $(#[$contains_attribute])*
+        $contains_visibility fn $contains(engine: &$engine, index: u32) -> bool {
+            return $(
+                $crate::TheoryTrait::contains_variable(
+                    <$engine as $crate::EngineTheoryTrait<$theory>>::theory_ref(engine),
+                    index,
+                )
+            )||+;
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_engine_type {
+    ($explain_key:ident; $theory:ident) => {
+        $crate::OuterEngine<$theory, $crate::CoreEngine<$explain_key>>
+    };
+    ($explain_key:ident; $theory:ident, $($rest:ident),+) => {
+        $crate::OuterEngine<$theory, $crate::__composite_engine_type!($explain_key; $($rest),+)>
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_engine_new {
+    ($field:ident) => {
+        $crate::OuterEngine::new($field, $crate::CoreEngine::default())
+    };
+    ($field:ident, $($rest:ident),+) => {
+        $crate::OuterEngine::new($field, $crate::__composite_engine_new!($($rest),+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_explain_key_type {
+    ($theory:ident) => {
+        $crate::Either<<$theory as $crate::TheoryTrait>::ExplainKey, $crate::CoreEngineExplainKey>
+    };
+    ($theory:ident, $($rest:ident),+) => {
+        $crate::Either<
+            <$theory as $crate::TheoryTrait>::ExplainKey,
+            $crate::__composite_explain_key_type!($($rest),+),
+        >
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_explain_key_into_either {
+    ($explain_key:ident, $value:expr;) => {
+        match $value {
+            $explain_key::CoreEngine(k) => k,
+            _ => unreachable!(),
+        }
+    };
+    ($explain_key:ident, $value:expr; $theory:ident $(, $rest:ident)*) => {
+        match $value {
+            $explain_key::$theory(k) => $crate::Either::Left(k),
+            other => $crate::Either::Right($crate::__composite_explain_key_into_either!($explain_key, other; $($rest),*)),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_constraint_slot_type {
+    (($theory_constraint:ty)) => {
+        $theory_constraint
+    };
+    ([$parameter:ident]) => {
+        $parameter
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_constraint_type {
+    ($theory_constraint:tt) => {
+        $crate::Either<$crate::__composite_constraint_slot_type!($theory_constraint), $crate::Literal>
+    };
+    ($theory_constraint:tt, $($rest:tt),+) => {
+        $crate::Either<
+            $crate::__composite_constraint_slot_type!($theory_constraint),
+            $crate::__composite_constraint_type!($($rest),+),
+        >
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_wrap_right {
+    ([]; $value:expr) => {
+        $value
+    };
+    ([_ $($depth:tt)*]; $value:expr) => {
+        $crate::Either::Right($crate::__composite_wrap_right!([$($depth)*]; $value))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_constraint_from {
+    ($constraint:ident<$($parameter:ident),*>; [$($depth:tt)*];) => {};
+    ($constraint:ident<$($parameter:ident),*>; [$($depth:tt)*]; ($theory_constraint:ty) $(, $rest:tt)*) => {
+        impl<$($parameter),*> From<$theory_constraint> for $constraint<$($parameter),*> {
+            fn from(constraint: $theory_constraint) -> Self {
+                return $crate::__composite_wrap_right!([$($depth)*]; $crate::Either::Left(constraint));
+            }
+        }
+
+        $crate::__composite_constraint_from!($constraint<$($parameter),*>; [$($depth)* _]; $($rest),*);
+    };
+    ($constraint:ident<$($parameter:ident),*>; [$($depth:tt)*]; [$theory_parameter:ident] $(, $rest:tt)*) => {
+        $crate::__composite_constraint_from!($constraint<$($parameter),*>; [$($depth)* _]; $($rest),*);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_explanation_type {
+    ($lifetime:lifetime; $theory:ident) => {
+        $crate::Either<<$theory as $crate::TheoryTrait>::ExplanationConstraint<$lifetime>, $crate::Literal>
+    };
+    ($lifetime:lifetime; $theory:ident, $($rest:ident),+) => {
+        $crate::Either<
+            <$theory as $crate::TheoryTrait>::ExplanationConstraint<$lifetime>,
+            $crate::__composite_explanation_type!($lifetime; $($rest),+),
+        >
+    };
+}
+
+/// 最も内側の理論を除いた説明の Either の入れ子
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_clause_explanation_type {
+    ($lifetime:lifetime; $theory:ident, $last:ident) => {
+        <$theory as $crate::TheoryTrait>::ExplanationConstraint<$lifetime>
+    };
+    ($lifetime:lifetime; $theory:ident, $($rest:ident),+) => {
+        $crate::Either<
+            <$theory as $crate::TheoryTrait>::ExplanationConstraint<$lifetime>,
+            $crate::__composite_clause_explanation_type!($lifetime; $($rest),+),
+        >
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_explanation_into {
+    (
+        $explanation:ident { $clause:ident, $last:ident, $core:ident }; $value:expr; [$($depth:tt)*]; $theory:ident
+    ) => {
+        match $value {
+            $crate::Either::Left(explanation) => $explanation::$last(explanation),
+            $crate::Either::Right(literal) => $explanation::$core(literal),
+        }
+    };
+    (
+        $explanation:ident { $clause:ident, $last:ident, $core:ident }; $value:expr; [$($depth:tt)*];
+        $theory:ident, $last_theory:ident
+    ) => {
+        match $value {
+            $crate::Either::Left(explanation) => {
+                $explanation::$clause($crate::__composite_wrap_right!([$($depth)*]; explanation))
+            }
+            $crate::Either::Right(inner) => $crate::__composite_explanation_into!(
+                $explanation { $clause, $last, $core }; inner; [$($depth)* _]; $last_theory
+            ),
+        }
+    };
+    (
+        $explanation:ident { $clause:ident, $last:ident, $core:ident }; $value:expr; [$($depth:tt)*];
+        $theory:ident, $($rest:ident),+
+    ) => {
+        match $value {
+            $crate::Either::Left(explanation) => $explanation::$clause(
+                $crate::__composite_wrap_right!([$($depth)*]; $crate::Either::Left(explanation))
+            ),
+            $crate::Either::Right(inner) => $crate::__composite_explanation_into!(
+                $explanation { $clause, $last, $core }; inner; [$($depth)* _]; $($rest),+
+            ),
+        }
+    };
+}
+
+/// 制約を先頭から見て，角括弧で与えた制約の深さ [$clause_parameter [$clause_depth]] と
+/// 最も内側の理論の制約の深さを求めてから From を実装する
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_constraint_from_explanation {
+    (
+        $constraint:ident<$($parameter:ident),*>; $explanation:ident { $clause:ident, $last:ident, $core:ident };
+        [$clause_parameter:ident [$($clause_depth:tt)*]]; [$($depth:tt)*]; $last_constraint:tt
+    ) => {
+        impl<$($parameter),*> From<$explanation<$clause_parameter>> for $constraint<$($parameter),*> {
+            fn from(constraint: $explanation<$clause_parameter>) -> Self {
+                return match constraint {
+                    $explanation::$clause(clause) => {
+                        $crate::__composite_wrap_right!([$($clause_depth)*]; $crate::Either::Left(clause))
+                    }
+                    $explanation::$last(constraint) => {
+                        $crate::__composite_wrap_right!([$($depth)*]; $crate::Either::Left(constraint))
+                    }
+                    $explanation::$core(literal) => $crate::__composite_wrap_right!([$($depth)* _]; literal),
+                };
+            }
+        }
+    };
+    (
+        $constraint:ident<$($parameter:ident),*>; $explanation:ident { $clause:ident, $last:ident, $core:ident };
+        [$($clause_slot:tt)*]; [$($depth:tt)*]; ($theory_constraint:ty), $($rest:tt),+
+    ) => {
+        $crate::__composite_constraint_from_explanation!(
+            $constraint<$($parameter),*>; $explanation { $clause, $last, $core };
+            [$($clause_slot)*]; [$($depth)* _]; $($rest),+
+        );
+    };
+    (
+        $constraint:ident<$($parameter:ident),*>; $explanation:ident { $clause:ident, $last:ident, $core:ident };
+        []; [$($depth:tt)*]; [$theory_parameter:ident], $($rest:tt),+
+    ) => {
+        $crate::__composite_constraint_from_explanation!(
+            $constraint<$($parameter),*>; $explanation { $clause, $last, $core };
+            [$theory_parameter [$($depth)*]]; [$($depth)* _]; $($rest),+
+        );
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_summary_type {
+    ($theory:ident) => {
+        (<$theory as $crate::TheoryTrait>::Summary, $crate::CoreEngineSummary)
+    };
+    ($theory:ident, $($rest:ident),+) => {
+        (<$theory as $crate::TheoryTrait>::Summary, $crate::__composite_summary_type!($($rest),+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_summary_pattern {
+    ($core_engine:ident; $field:ident) => {
+        ($field, $core_engine)
+    };
+    ($core_engine:ident; $field:ident, $($rest:ident),+) => {
+        ($field, $crate::__composite_summary_pattern!($core_engine; $($rest),+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __composite_engine_theories {
+    ($engine:ident; [$($chain:tt)*] [$($chain_mut:tt)*];) => {};
+    ($engine:ident; [$($chain:tt)*] [$($chain_mut:tt)*]; $theory:ident $(, $rest:ident)*) => {
+        impl $crate::EngineTheoryTrait<$theory> for $engine {
+            #[inline(always)]
+            fn theory_ref(&self) -> &$theory {
+                return self $($chain)* .theory();
+            }
+
+            #[inline(always)]
+            fn theory_ref_mut(&mut self) -> &mut $theory {
+                return self $($chain_mut)* .theory_mut();
+            }
+
+            #[inline(always)]
+            fn propagation_policy_ref_mut(&mut self) -> &mut $crate::PropagationPolicy {
+                return self $($chain_mut)* .propagation_policy_mut();
+            }
+
+            #[inline(always)]
+            fn propagation_statistics_ref(&self) -> &$crate::PropagationStatistics {
+                return self $($chain)* .propagation_statistics();
+            }
+        }
+
+        $crate::__composite_engine_theories!(
+            $engine; [$($chain)* .inner_engine()] [$($chain_mut)* .inner_engine_mut()]; $($rest),*
+        );
+    };
+}
@@ -3,7 +3,9 @@ use std::hint::unreachable_unchecked;
 use utility::Array;
 
 use crate::{
+    compose::ProgressColumnsTrait,
     engine::{EngineAddConstraintTrait, EngineTrait},
+    theory::DerivedClauses,
     types::{Boolean, Literal, PropagationResult, Reason},
 };
 
@@ -17,6 +19,14 @@ pub struct CoreEngineSummary {
     pub number_of_fixed_variables: u32,
 }
 
+impl ProgressColumnsTrait for CoreEngineSummary {
+    const HEADERS: &'static [&'static str] = &["FIXED"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_fixed_variables].into_iter();
+    }
+}
+
 #[derive(Clone)]
 pub struct CoreEngine<CompositeExplainKeyT>
 where
@@ -27,6 +37,8 @@ where
     assignment_stack: Array<u32, Assignment<CompositeExplainKeyT>>,
     decision_stack: Array<u32, Decision>,
     backjump_result: Array<u32, Literal>,
+    /// バックジャンプで積み直す割り当て(新しい順)
+    kept_assignments: Array<u32, Assignment<CompositeExplainKeyT>>,
 }
 
 impl<CompositeExplainKeyT> Default for CoreEngine<CompositeExplainKeyT>
@@ -40,6 +52,7 @@ where
             assignment_stack: Array::default(),
             decision_stack: Array::default(),
             backjump_result: Array::default(),
+            kept_assignments: Array::default(),
         }
     }
 }
@@ -116,6 +129,15 @@ where
         }
     }
 
+    #[inline(always)]
+    fn set_reason(&mut self, index: u32, reason: Reason<Self::CompositeExplainKey>) {
+        debug_assert!(self.assignment_orders[index] != Self::NULL_ASSIGNMENT_ORDER);
+        debug_assert!(
+            reason.is_decision() == self.assignment_stack[self.assignment_orders[index]].reason.is_decision()
+        );
+        self.assignment_stack[self.assignment_orders[index]].reason = reason;
+    }
+
     #[inline(always)]
     fn get_assignment_order_range(&self, decision_level: u32) -> std::ops::Range<u32> {
         debug_assert!(decision_level <= self.current_decision_level());
@@ -129,6 +151,14 @@ where
         }
     }
 
+    #[inline(always)]
+    fn get_decision_literal(&self, decision_level: u32) -> Literal {
+        debug_assert!(0 < decision_level && decision_level <= self.current_decision_level());
+        let assignment = &self.assignment_stack[self.decision_stack[decision_level - 1].assignment_order];
+        debug_assert!(assignment.reason.is_decision());
+        return Literal::new(assignment.index, self.states[assignment.index].value());
+    }
+
     #[inline(always)]
     fn get_assignment(&self, assignment_order: u32) -> Literal {
         let index = self.assignment_stack[assignment_order].index;
@@ -164,28 +194,99 @@ where
         return PropagationResult::Noconflict;
     }
 
+    fn assign_at_level(
+        &mut self,
+        literal: Literal,
+        reason: Reason<Self::CompositeExplainKey>,
+        decision_level: u32,
+    ) -> PropagationResult<Self::CompositeExplainKey> {
+        debug_assert!(!reason.is_decision());
+        debug_assert!(decision_level <= self.current_decision_level());
+        debug_assert!(!self.states[literal.index()].is_assigned());
+        debug_assert!(self.assignment_orders[literal.index()] == Self::NULL_ASSIGNMENT_ORDER);
+        // 決定レベル 0 の割り当ては決定レベル 0 の範囲にしか並ばないようにする
+        let decision_level = if decision_level == 0 { self.current_decision_level() } else { decision_level };
+        let assignment_order = self.assignment_stack.len();
+        self.assignment_stack.push(Assignment {
+            index: literal.index(),
+            decision_level: decision_level,
+            reason: reason,
+        });
+        self.states[literal.index()].assign(literal.value());
+        self.assignment_orders[literal.index()] = assignment_order;
+        return PropagationResult::Noconflict;
+    }
+
     #[inline(always)]
-    fn explain(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
+    fn explain_propagation(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
+        return explain_key.literal;
+    }
+
+    fn explain_lazily(&mut self, _literal: Literal, _explain_key: Self::ExplainKey) -> Self::CompositeExplainKey {
+        // CoreEngine の伝播は説明を遅延させない
+        unreachable!()
+    }
+
+    #[inline(always)]
+    fn explain_conflict(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
         return explain_key.literal;
     }
 
     fn backjump(&mut self, backjump_level: u32) -> impl Iterator<Item = Literal> + '_ {
         self.backjump_result.clear();
-        while self.decision_stack.len() > backjump_level {
-            let assignment = self.assignment_stack.pop().unwrap();
-            debug_assert!(assignment.decision_level == self.decision_stack.len());
-            if assignment.reason.is_decision() {
-                let decision = self.decision_stack.pop().unwrap();
-                debug_assert!(decision.assignment_order == self.assignment_stack.len());
+        if self.decision_stack.len() > backjump_level {
+            let backjump_assignment_order = self.decision_stack[backjump_level].assignment_order;
+            self.decision_stack.truncate(backjump_level);
+            while self.assignment_stack.len() > backjump_assignment_order {
+                let assignment = self.assignment_stack.pop().unwrap();
+                if assignment.decision_level <= backjump_level {
+                    // chronological backtracking で並んだ低い決定レベルの割り当ては残す
+                    debug_assert!(!assignment.reason.is_decision());
+                    self.kept_assignments.push(assignment);
+                    continue;
+                }
+                let value = self.states[assignment.index].value();
+                self.states[assignment.index].unassign();
+                self.assignment_orders[assignment.index] = Self::NULL_ASSIGNMENT_ORDER;
+                self.backjump_result.push(Literal::new(assignment.index, value));
+            }
+            // 残した割り当てをもとの順に積み直す
+            while let Some(assignment) = self.kept_assignments.pop() {
+                self.assignment_orders[assignment.index] = self.assignment_stack.len();
+                self.assignment_stack.push(assignment);
             }
-            let value = self.states[assignment.index].value();
-            self.states[assignment.index].unassign();
-            self.assignment_orders[assignment.index] = Self::NULL_ASSIGNMENT_ORDER;
-            self.backjump_result.push(Literal::new(assignment.index, value));
         }
         return self.backjump_result.iter().cloned();
     }
 
+    fn final_check(&mut self) -> PropagationResult<Self::CompositeExplainKey> {
+        return PropagationResult::Noconflict;
+    }
+
+    fn suggest_decision(&mut self) -> Option<Literal> {
+        return None;
+    }
+
+    fn suggest_phase(&self, _index: u32) -> Option<Boolean> {
+        return None;
+    }
+
+    fn propagate_deferred(&mut self) -> PropagationResult<Self::CompositeExplainKey> {
+        return PropagationResult::Noconflict;
+    }
+
+    fn propagation_time(&self) -> std::time::Duration {
+        return std::time::Duration::ZERO;
+    }
+
+    fn mark_as_inner_engine(&mut self) {
+        // 理論を持たないのでなにもしない
+    }
+
+    fn simplify_at_root(&mut self, _effort: usize, _derived_clauses: &mut DerivedClauses) -> u32 {
+        return 0;
+    }
+
     fn reduce_constraints(&mut self) {
         // なにもしない
     }
@@ -1,6 +1,9 @@
-use std::ops::Range;
+use std::{ops::Range, time::Duration};
 
-use crate::types::{Boolean, Literal, PropagationResult, Reason};
+use crate::{
+    theory::DerivedClauses,
+    types::{Boolean, Literal, PropagationResult, Reason},
+};
 
 // NOTE: immutable なメソッド・ Theory に見せるためのメソッド・その他のメソッドに分類して trait を分けることも考えられるが，
 // 面倒な割に今のところメリットもないのでやらない
@@ -33,10 +36,34 @@ pub trait EngineTrait {
 
     fn get_assignment_order(&self, index: u32) -> u32;
 
+    /// 決定レベル decision_level の決定から次の決定の前までの割り当ての順番の範囲
+    ///
+    /// chronological backtracking の後は，より低い決定レベルのリテラルを含むことがある
     fn get_assignment_order_range(&self, decision_level: u32) -> Range<u32>;
 
+    /// 決定レベル decision_level(1 以上)で決定したリテラル．理論が提案した決定も含む
+    fn get_decision_literal(&self, decision_level: u32) -> Literal;
+
     fn get_reason(&self, index: u32) -> Option<Reason<Self::CompositeExplainKey>>;
 
+    /// 割り当て理由を置き換える
+    fn set_reason(&mut self, index: u32, reason: Reason<Self::CompositeExplainKey>);
+
+    /// 割り当て理由を返す．説明を必要になったときに作る伝播であれば，ここで説明を作って割り当て理由を置き換える
+    fn get_explained_reason(&mut self, index: u32) -> Option<Reason<Self::CompositeExplainKey>>
+    where
+        Self::CompositeExplainKey: Into<Self::ExplainKey>,
+    {
+        let reason = self.get_reason(index)?;
+        let Reason::LazyPropagation { explain_key } = reason else {
+            return Some(reason);
+        };
+        let literal = Literal::new(index, self.get_value(index));
+        let explained_reason = Reason::Propagation { explain_key: self.explain_lazily(literal, explain_key.into()) };
+        self.set_reason(index, explained_reason);
+        return Some(explained_reason);
+    }
+
     fn get_assignment(&self, assignment_order: u32) -> Literal;
 
     fn add_variable(&mut self, initial_value: Boolean);
@@ -48,11 +75,58 @@ pub trait EngineTrait {
         reason: Reason<Self::CompositeExplainKey>,
     ) -> PropagationResult<Self::CompositeExplainKey>;
 
-    // NOTE: 基数制約よりも一般の制約条件を扱う際には explain_conflict と explain_propagation とに分ける必要がありそう
-    fn explain(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_>;
+    /// 伝播したリテラルを現在の決定レベル以下の決定レベル decision_level で割り当てる
+    ///
+    /// decision_level は割り当て理由の他のリテラルの決定レベルの最大値以上とする．
+    /// chronological backtracking の後は，現在の決定レベルより低い決定レベルのリテラルが割り当ての列の途中に並ぶ．
+    #[must_use]
+    fn assign_at_level(
+        &mut self,
+        literal: Literal,
+        reason: Reason<Self::CompositeExplainKey>,
+        decision_level: u32,
+    ) -> PropagationResult<Self::CompositeExplainKey>;
+
+    /// 伝播したリテラルの割り当て理由となる節(伝播したリテラル以外は偽)を返す
+    fn explain_propagation(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_>;
+
+    /// Reason::LazyPropagation で伝播したリテラルの説明を作り，Reason::Propagation に用いる CompositeExplainKey を返す
+    fn explain_lazily(&mut self, literal: Literal, explain_key: Self::ExplainKey) -> Self::CompositeExplainKey;
 
+    /// 矛盾の原因となる節(全てのリテラルが偽)を返す
+    fn explain_conflict(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_>;
+
+    /// backjump_level より高い決定レベルの割り当てを取り消し，取り消したリテラルを返す
+    ///
+    /// 取り消す範囲に並ぶ backjump_level 以下の決定レベルのリテラルは，割り当てを保ったまま割り当ての列の末尾に積み直す．
+    /// 理論には一度取り消したものとして伝え，次の伝播で割り当て直したものとして伝える．
     fn backjump(&mut self, backjump_level: u32) -> impl Iterator<Item = Literal> + '_;
 
+    /// 全ての変数に割り当てた後，内側の理論から順に final_check を呼び出す
+    ///
+    /// 矛盾しなければ割り当ては解である
+    #[must_use]
+    fn final_check(&mut self) -> PropagationResult<Self::CompositeExplainKey>;
+
+    /// 外側の理論から順に次に決定するリテラルの提案を求め，最初の提案を返す
+    fn suggest_decision(&mut self) -> Option<Literal>;
+
+    /// 外側の理論から順に変数に割り当てる値の提案を求め，最初の提案を返す
+    fn suggest_phase(&self, index: u32) -> Option<Boolean>;
+
+    /// 後回しにした理論も含めて，全ての理論が伝播し終えるまで伝播させる
+    #[must_use]
+    fn propagate_deferred(&mut self) -> PropagationResult<Self::CompositeExplainKey>;
+
+    /// 時間を計っている層の理論が伝播に費やした時間の合計
+    fn propagation_time(&self) -> Duration;
+
+    /// 外側の層に包まれたことを知らせる．外側の層がなければ後回しにする理論も後回しにしない
+    fn mark_as_inner_engine(&mut self);
+
+    /// 決定レベル 0 で内側の理論から順に制約を整理させ，削除した制約の数の合計を返す
+    fn simplify_at_root(&mut self, effort: usize, derived_clauses: &mut DerivedClauses) -> u32;
+
     fn reduce_constraints(&mut self);
 
     fn summary(&self) -> Self::Summary;
@@ -1,15 +1,23 @@
 // #![feature(try_trait_v2)]
 
+mod compose;
 mod core_engine;
 mod engine;
 mod outer_engine;
 mod theory;
 mod types;
+mod undo_log;
 
 pub use {
-    core_engine::{CoreEngine, CoreEngineExplainKey},
+    compose::{EngineGetTheoryTrait, EngineTheoryTrait, ProgressColumnsTrait},
+    core_engine::{CoreEngine, CoreEngineExplainKey, CoreEngineSummary},
     engine::{EngineAddConstraintTrait, EngineTrait},
-    outer_engine::OuterEngine,
-    theory::{TheoryAddConstraintTrait, TheoryTrait},
+    outer_engine::{OuterEngine, PropagationPolicy, PropagationStatistics},
+    theory::{DerivedClauses, TheoryAddConstraintTrait, TheoryTrait},
     types::{Boolean, Literal, LiteralArray, PropagationResult, Reason},
+    undo_log::UndoLog,
 };
+
+// compose_engine! が展開先で用いる
+#[doc(hidden)]
+pub use either::Either;
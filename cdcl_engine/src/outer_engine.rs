@@ -1,11 +1,41 @@
+use std::time::{Duration, Instant};
+
 use either::Either;
 
 use crate::{
     engine::{EngineAddConstraintTrait, EngineTrait},
-    theory::{TheoryAddConstraintTrait, TheoryTrait},
+    theory::{DerivedClauses, TheoryAddConstraintTrait, TheoryTrait},
     types::{Boolean, Literal, PropagationResult, Reason},
+    undo_log::UndoLog,
 };
 
+/// OuterEngine の層ごとの伝播の方針
+#[derive(Default, Clone, Copy)]
+pub struct PropagationPolicy {
+    /// 外側の層も含めて，後回しにしない理論が全て伝播し終えてからこの層の理論に伝播させるか
+    ///
+    /// 最も外側の層は後回しにしない．
+    pub is_deferred: bool,
+    /// この層の理論が伝播に費やした時間を計るか
+    pub is_timed: bool,
+}
+
+/// OuterEngine の層ごとの伝播の統計情報
+#[derive(Default, Clone)]
+pub struct PropagationStatistics {
+    /// 理論に渡したリテラルの数
+    pub number_of_processed_literals: u64,
+    /// 理論が伝播に費やした時間．時間を計っている内側の層の時間は除き，計っていない内側の層の時間は含む
+    pub propagation_time: Duration,
+}
+
+/// 理論を一つ加えた層
+///
+/// 内側の層ほど優先して伝播させる．内側のエンジンに割り当てると内側の理論が先に伝播し終え，
+/// その後にこの層の理論が割り当てを順に処理する．後回しにする内側の層があれば，この層の理論が処理し終えてから
+/// 伝播させ，新たな割り当てがあればまたこの層の理論が処理する．矛盾すれば，残りの層に伝播させずにすぐに返す．
+/// 理論が状態を変えるときは undo_log に記録させ，バックジャンプで取り消される割り当ての処理以降の変更を戻す．
+/// 内側の層が探索中に作った変数は，理論を呼び出す前に add_variable で知らせる．
 #[derive(Clone)]
 pub struct OuterEngine<TheoryT, InnerEngineT>
 where
@@ -14,7 +44,14 @@ where
 {
     theory: TheoryT,
     inner_engine: InnerEngineT,
+    /// 理論に知らせた変数の数
+    number_of_variables: u32,
     number_of_propagateds: u32,
+    undo_log: UndoLog<TheoryT::UndoEntry>,
+    /// 外側の層に包まれているか
+    is_inner_engine: bool,
+    policy: PropagationPolicy,
+    statistics: PropagationStatistics,
 }
 
 impl<TheoryT, InnerEngineT> OuterEngine<TheoryT, InnerEngineT>
@@ -22,8 +59,53 @@ where
     TheoryT: TheoryTrait,
     InnerEngineT: EngineTrait,
 {
-    pub fn new(theory: TheoryT, inner_engine: InnerEngineT) -> Self {
-        Self { theory: theory, inner_engine: inner_engine, number_of_propagateds: 0 }
+    pub fn new(theory: TheoryT, mut inner_engine: InnerEngineT) -> Self {
+        inner_engine.mark_as_inner_engine();
+        Self {
+            theory: theory,
+            number_of_variables: inner_engine.number_of_variables(),
+            inner_engine: inner_engine,
+            number_of_propagateds: 0,
+            undo_log: UndoLog::default(),
+            is_inner_engine: false,
+            policy: PropagationPolicy::default(),
+            statistics: PropagationStatistics::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn theory(&self) -> &TheoryT {
+        return &self.theory;
+    }
+
+    #[inline(always)]
+    pub fn inner_engine(&self) -> &InnerEngineT {
+        return &self.inner_engine;
+    }
+
+    #[inline(always)]
+    pub fn theory_mut(&mut self) -> &mut TheoryT {
+        return &mut self.theory;
+    }
+
+    #[inline(always)]
+    pub fn inner_engine_mut(&mut self) -> &mut InnerEngineT {
+        return &mut self.inner_engine;
+    }
+
+    #[inline(always)]
+    pub fn propagation_policy(&self) -> &PropagationPolicy {
+        return &self.policy;
+    }
+
+    #[inline(always)]
+    pub fn propagation_policy_mut(&mut self) -> &mut PropagationPolicy {
+        return &mut self.policy;
+    }
+
+    #[inline(always)]
+    pub fn propagation_statistics(&self) -> &PropagationStatistics {
+        return &self.statistics;
     }
 }
 
@@ -91,11 +173,21 @@ where
         return self.inner_engine.get_reason(index);
     }
 
+    #[inline(always)]
+    fn set_reason(&mut self, index: u32, reason: Reason<Self::CompositeExplainKey>) {
+        self.inner_engine.set_reason(index, reason);
+    }
+
     #[inline(always)]
     fn get_assignment_order_range(&self, decision_level: u32) -> std::ops::Range<u32> {
         return self.inner_engine.get_assignment_order_range(decision_level);
     }
 
+    #[inline(always)]
+    fn get_decision_literal(&self, decision_level: u32) -> Literal {
+        return self.inner_engine.get_decision_literal(decision_level);
+    }
+
     #[inline(always)]
     fn get_assignment(&self, assignment_order: u32) -> Literal {
         return self.inner_engine.get_assignment(assignment_order);
@@ -103,7 +195,7 @@ where
 
     fn add_variable(&mut self, initial_value: Boolean) {
         self.inner_engine.add_variable(initial_value);
-        self.theory.add_variable();
+        self.add_new_variables_to_theory();
     }
 
     fn assign(
@@ -112,32 +204,115 @@ where
         reason: Reason<Self::CompositeExplainKey>,
     ) -> PropagationResult<Self::CompositeExplainKey> {
         let inner_propagation_result = self.inner_engine.assign(literal, reason);
-        if inner_propagation_result.is_conflict() {
-            return inner_propagation_result;
-        } else {
-            return self.propagate();
-        }
+        return self.propagate_assignment(inner_propagation_result);
+    }
+
+    fn assign_at_level(
+        &mut self,
+        literal: Literal,
+        reason: Reason<Self::CompositeExplainKey>,
+        decision_level: u32,
+    ) -> PropagationResult<Self::CompositeExplainKey> {
+        let inner_propagation_result = self.inner_engine.assign_at_level(literal, reason, decision_level);
+        return self.propagate_assignment(inner_propagation_result);
+    }
+
+    #[inline(always)]
+    fn explain_propagation(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            Either::Left(theory_explain_key) => Either::Left(self.theory.explain_propagation(theory_explain_key)),
+            Either::Right(inner_explain_key) => Either::Right(self.inner_engine.explain_propagation(inner_explain_key)),
+        };
+    }
+
+    fn explain_lazily(&mut self, literal: Literal, explain_key: Self::ExplainKey) -> Self::CompositeExplainKey {
+        self.add_new_variables_to_theory();
+        return match explain_key {
+            Either::Left(theory_explain_key) => {
+                self.theory.explain_lazily(literal, theory_explain_key, &self.inner_engine).into()
+            }
+            Either::Right(inner_explain_key) => self.inner_engine.explain_lazily(literal, inner_explain_key),
+        };
     }
 
     #[inline(always)]
-    fn explain(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
+    fn explain_conflict(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
         return match explain_key {
-            Either::Left(theory_explain_key) => Either::Left(self.theory.explain(theory_explain_key)),
-            Either::Right(inner_explain_key) => Either::Right(self.inner_engine.explain(inner_explain_key)),
+            Either::Left(theory_explain_key) => Either::Left(self.theory.explain_conflict(theory_explain_key)),
+            Either::Right(inner_explain_key) => Either::Right(self.inner_engine.explain_conflict(inner_explain_key)),
         };
     }
 
     fn backjump(&mut self, backjump_level: u32) -> impl Iterator<Item = Literal> {
+        // 内側の層が積み直すリテラルも処理した変更を戻し，次の伝播で処理し直させる
         let backjump_assignment_order = self.inner_engine.get_assignment_order_range(backjump_level).end;
         debug_assert!(backjump_assignment_order <= self.number_of_propagateds);
-        let unassigning_literals = (backjump_assignment_order..self.number_of_propagateds)
-            .rev()
-            .map(|assignment_order| self.inner_engine.get_assignment(assignment_order));
-        self.theory.unassign(unassigning_literals);
+        self.add_new_variables_to_theory();
+        while let Some(entry) = self.undo_log.pop(backjump_assignment_order) {
+            self.theory.undo(entry);
+        }
+        self.theory.backjump(backjump_level);
         self.number_of_propagateds = backjump_assignment_order;
         return self.inner_engine.backjump(backjump_level);
     }
 
+    fn final_check(&mut self) -> PropagationResult<Self::CompositeExplainKey> {
+        let inner_result = self.inner_engine.final_check();
+        if inner_result.is_conflict() {
+            return inner_result;
+        }
+        // 内側の理論が割り当てたリテラルを先に伝える
+        let propagation_result = self.propagate();
+        if propagation_result.is_conflict() {
+            return propagation_result;
+        }
+        // 内側の理論が未割り当ての変数を作ったときは，それらを割り当ててから調べる
+        if self.inner_engine.number_of_assigneds() < self.inner_engine.number_of_variables() {
+            return PropagationResult::Noconflict;
+        }
+        self.add_new_variables_to_theory();
+        self.undo_log.set_assignment_order(self.number_of_propagateds);
+        let result = self.theory.final_check(&mut self.inner_engine, &mut self.undo_log);
+        if result.is_conflict() {
+            return result;
+        } else {
+            return self.propagate();
+        }
+    }
+
+    fn suggest_decision(&mut self) -> Option<Literal> {
+        self.add_new_variables_to_theory();
+        if let Some(literal) = self.theory.suggest_decision(&self.inner_engine) {
+            debug_assert!(!self.inner_engine.is_assigned(literal.index()));
+            return Some(literal);
+        }
+        return self.inner_engine.suggest_decision();
+    }
+
+    fn suggest_phase(&self, index: u32) -> Option<Boolean> {
+        return self.theory.suggest_phase(index).or_else(|| self.inner_engine.suggest_phase(index));
+    }
+
+    fn propagate_deferred(&mut self) -> PropagationResult<Self::CompositeExplainKey> {
+        return self.propagate();
+    }
+
+    fn propagation_time(&self) -> Duration {
+        return self.statistics.propagation_time + self.inner_engine.propagation_time();
+    }
+
+    fn mark_as_inner_engine(&mut self) {
+        self.is_inner_engine = true;
+    }
+
+    fn simplify_at_root(&mut self, effort: usize, derived_clauses: &mut DerivedClauses) -> u32 {
+        debug_assert!(self.current_decision_level() == 0);
+        let number_of_removed_constraints = self.inner_engine.simplify_at_root(effort, derived_clauses);
+        self.add_new_variables_to_theory();
+        return number_of_removed_constraints
+            + self.theory.simplify_at_root(effort, &self.inner_engine, derived_clauses);
+    }
+
     fn reduce_constraints(&mut self) {
         self.theory.reduce_constraints();
         self.inner_engine.reduce_constraints();
@@ -162,7 +337,9 @@ where
     ) -> PropagationResult<Self::CompositeExplainKey> {
         let result = match constraint {
             Either::Left(theory_constraint) => {
-                self.theory.add_constraint(theory_constraint, is_learnt, &mut self.inner_engine)
+                self.add_new_variables_to_theory();
+                self.undo_log.set_assignment_order(self.number_of_propagateds);
+                self.theory.add_constraint(theory_constraint, is_learnt, &mut self.inner_engine, &mut self.undo_log)
             }
             Either::Right(inner_constraint) => self.inner_engine.add_constraint(inner_constraint, is_learnt),
         };
@@ -180,18 +357,69 @@ where
     InnerEngineT: EngineTrait,
     InnerEngineT::CompositeExplainKey: From<TheoryT::ExplainKey>,
 {
+    /// 内側の層が作った変数と，理論が内側のエンジンに作らせた変数を理論に知らせる
+    fn add_new_variables_to_theory(&mut self) {
+        while self.number_of_variables < self.inner_engine.number_of_variables() {
+            self.theory.add_variable();
+            self.number_of_variables += 1;
+        }
+    }
+
+    /// 内側のエンジンに割り当てた後，後回しにしなければ伝播させる
+    fn propagate_assignment(
+        &mut self,
+        inner_propagation_result: PropagationResult<<Self as EngineTrait>::CompositeExplainKey>,
+    ) -> PropagationResult<<Self as EngineTrait>::CompositeExplainKey> {
+        if inner_propagation_result.is_conflict() {
+            return inner_propagation_result;
+        } else if self.policy.is_deferred && self.is_inner_engine {
+            // 外側の層が propagate_deferred で伝播させる
+            return PropagationResult::Noconflict;
+        } else {
+            return self.propagate();
+        }
+    }
+
+    /// この層と内側の層の全ての理論が伝播し終えるまで伝播させる
+    ///
+    /// chronological backtracking の後は，現在の決定レベルより低い決定レベルのリテラルも処理する
     fn propagate(&mut self) -> PropagationResult<<Self as EngineTrait>::CompositeExplainKey> {
-        while self.number_of_propagateds < self.inner_engine.number_of_assigneds() {
-            let literal = self.inner_engine.get_assignment(self.number_of_propagateds);
-            debug_assert!(
-                self.inner_engine.get_decision_level(literal.index()) == self.inner_engine.current_decision_level()
-            );
-            self.number_of_propagateds += 1;
-            let result = self.theory.assign(literal, &mut self.inner_engine);
+        if !self.policy.is_timed {
+            return self.propagate_untimed();
+        }
+        let start_time = Instant::now();
+        let inner_propagation_time = self.inner_engine.propagation_time();
+        let result = self.propagate_untimed();
+        // 内側の層が計った時間を除く
+        self.statistics.propagation_time +=
+            start_time.elapsed().saturating_sub(self.inner_engine.propagation_time() - inner_propagation_time);
+        return result;
+    }
+
+    fn propagate_untimed(&mut self) -> PropagationResult<<Self as EngineTrait>::CompositeExplainKey> {
+        loop {
+            while self.number_of_propagateds < self.inner_engine.number_of_assigneds() {
+                let literal = self.inner_engine.get_assignment(self.number_of_propagateds);
+                debug_assert!(
+                    self.inner_engine.get_decision_level(literal.index()) <= self.inner_engine.current_decision_level()
+                );
+                self.undo_log.set_assignment_order(self.number_of_propagateds);
+                self.number_of_propagateds += 1;
+                self.statistics.number_of_processed_literals += 1;
+                self.add_new_variables_to_theory();
+                let result = self.theory.assign(literal, &mut self.inner_engine, &mut self.undo_log);
+                if result.is_conflict() {
+                    return result;
+                }
+            }
+            // 後回しにした内側の理論を伝播させ，新たな割り当てがなければ全ての層が伝播し終えている
+            let result = self.inner_engine.propagate_deferred();
             if result.is_conflict() {
                 return result;
             }
+            if self.number_of_propagateds == self.inner_engine.number_of_assigneds() {
+                return PropagationResult::Noconflict;
+            }
         }
-        return PropagationResult::Noconflict;
     }
 }
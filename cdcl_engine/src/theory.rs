@@ -1,29 +1,132 @@
+use utility::Array;
+
 use crate::{
     engine::EngineTrait,
-    types::{Literal, PropagationResult},
+    types::{Boolean, Literal, PropagationResult},
+    undo_log::UndoLog,
 };
 
+/// TheoryTrait::simplify_at_root で理論が導いた，内側の層に加える節
+#[derive(Default, Clone)]
+pub struct DerivedClauses {
+    pub units: Array<u32, Literal>,
+    pub binary_clauses: Array<u32, [Literal; 2]>,
+}
+
+impl DerivedClauses {
+    pub fn is_empty(&self) -> bool {
+        return self.units.is_empty() && self.binary_clauses.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.units.clear();
+        self.binary_clauses.clear();
+    }
+}
+
 pub trait TheoryTrait {
     type ExplainKey;
     type ExplanationConstraint<'a>
     where
         Self: 'a;
     type Summary;
+    /// バックジャンプで戻す状態の変更．状態を持たない理論は () とする
+    type UndoEntry: Clone;
 
+    /// 変数が加わったことを知らせる
+    ///
+    /// 探索中に内側の層の理論が作った変数も，この層の理論を次に呼び出す前に OuterEngine が知らせる．
+    /// 理論自身が内側のエンジンに作らせた変数も同様に後から知らせる
     fn add_variable(&mut self);
 
+    /// literal の割り当てを処理する．状態を変えたときは，それを戻すための値を undo_log に記録する
     fn assign<EngineT>(
         &mut self,
         literal: Literal,
         engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT: EngineTrait,
         EngineT::CompositeExplainKey: From<Self::ExplainKey>;
 
-    fn explain(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_>;
+    /// 伝播したリテラルの割り当て理由となる節(伝播したリテラル以外は偽)を返す
+    fn explain_propagation(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_>;
+
+    /// 矛盾の原因となる節(全てのリテラルが偽)を返す
+    ///
+    /// 伝播と矛盾で同じ制約を説明に用いる理論はそのままでよい
+    fn explain_conflict(&self, explain_key: Self::ExplainKey) -> Self::ExplanationConstraint<'_> {
+        return self.explain_propagation(explain_key);
+    }
+
+    /// Reason::LazyPropagation で伝播したリテラルの説明を作り，explain_propagation に渡せる ExplainKey を返す
+    ///
+    /// 説明は割り当ての処理より後に作るので undo_log には記録できない．リテラルが割り当てられている間は保持すること．
+    /// 説明を遅延させない理論は呼び出されない
+    fn explain_lazily<EngineT: EngineTrait>(
+        &mut self,
+        _literal: Literal,
+        _explain_key: Self::ExplainKey,
+        _engine: &EngineT,
+    ) -> Self::ExplainKey {
+        unreachable!()
+    }
+
+    /// undo_log に記録した変更を戻す
+    ///
+    /// バックジャンプで取り消される割り当ての処理以降に記録した変更を，OuterEngine が新しい順に渡す
+    fn undo(&mut self, entry: Self::UndoEntry);
 
-    fn unassign(&mut self, unassigned_literals: impl Iterator<Item = Literal>);
+    /// 決定レベル backjump_level にバックジャンプし，記録した変更を全て戻した後に呼び出す．既定ではなにもしない
+    fn backjump(&mut self, _backjump_level: u32) {}
+
+    /// 全ての変数に割り当てた後，解として受け入れる前に割り当てを調べる
+    ///
+    /// 割り当てだけでは判定できない理論は，ここで制約を追加したり矛盾を返したりする．既定ではなにもしない
+    fn final_check<EngineT>(
+        &mut self,
+        _engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        return PropagationResult::Noconflict;
+    }
+
+    /// 次に決定するリテラルを提案する
+    ///
+    /// 提案するリテラルは未割り当てでなければならない．既定ではなにも提案せず，呼び出し側が選ぶ
+    fn suggest_decision<EngineT: EngineTrait>(&mut self, _engine: &EngineT) -> Option<Literal> {
+        return None;
+    }
+
+    /// 呼び出し側が決定に選んだ変数に割り当てる値を提案する．既定ではなにも提案しない
+    fn suggest_phase(&self, _index: u32) -> Option<Boolean> {
+        return None;
+    }
+
+    /// 変数が節として扱えない制約に現れるか否か
+    ///
+    /// 節だけを見て変数を witness にしたり除去したりしてよいかの判定に使う．既定では現れない
+    fn contains_variable(&self, _index: u32) -> bool {
+        return false;
+    }
+
+    /// 決定レベル 0 で，根で充足された制約の削除などにより制約を整理し，削除した制約の数を返す
+    ///
+    /// 調べるリテラルの数がおおよそ effort を超えたら打ち切る．導いた単位節や 2 リテラルの節は derived_clauses に加え，
+    /// 呼び出し側が内側の層に加える．既定ではなにもしない
+    fn simplify_at_root<EngineT: EngineTrait>(
+        &mut self,
+        _effort: usize,
+        _engine: &EngineT,
+        _derived_clauses: &mut DerivedClauses,
+    ) -> u32 {
+        return 0;
+    }
 
     fn reduce_constraints(&mut self);
 
@@ -36,6 +139,7 @@ pub trait TheoryAddConstraintTrait<ConstraintT>: TheoryTrait {
         constraint: ConstraintT,
         is_learnt: bool,
         engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT::CompositeExplainKey: From<Self::ExplainKey>;
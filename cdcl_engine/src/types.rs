@@ -53,6 +53,13 @@ where
         /// 伝播が発生した制約条件
         explain_key: CompositeExplainKeyT,
     },
+    /// 説明を必要になったときに作る伝播
+    ///
+    /// EngineTrait::get_explained_reason で説明を作ると Propagation に置き換わる
+    LazyPropagation {
+        /// 説明を作るために理論が用いる値
+        explain_key: CompositeExplainKeyT,
+    },
 }
 
 impl<CompositeExplainKeyT> Reason<CompositeExplainKeyT>
@@ -63,14 +70,14 @@ where
     pub fn is_decision(&self) -> bool {
         return match &self {
             Self::Decision => true,
-            Self::Propagation { .. } => false,
+            Self::Propagation { .. } | Self::LazyPropagation { .. } => false,
         };
     }
     #[inline(always)]
     pub fn is_propagation(&self) -> bool {
         return match &self {
             Self::Decision => false,
-            Self::Propagation { .. } => true,
+            Self::Propagation { .. } | Self::LazyPropagation { .. } => true,
         };
     }
 }
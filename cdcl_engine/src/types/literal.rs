@@ -2,7 +2,7 @@ use std::{hint::unreachable_unchecked, ops::Not};
 
 use super::boolean::Boolean;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Literal {
     bits: u32,
 }
@@ -0,0 +1,96 @@
+use utility::Array;
+
+/// バックジャンプで戻すための状態の変更の記録
+///
+/// OuterEngine が層ごとに一つ持ち，理論が割り当てを処理する前にその割り当ての位置を set_assignment_order で設定する．
+/// 理論は状態を変えるときに，変更を戻すための値を push する．記録した位置は単調に増えるので，
+/// バックジャンプで取り消される位置以降の変更は全て末尾に並び，決定レベルが順に並ばない場合も新しい順に取り出せる．
+#[derive(Clone)]
+pub struct UndoLog<EntryT> {
+    entries: Array<u32, EntryT>,
+    /// 変更を記録したときに処理していた割り当ての位置
+    assignment_orders: Array<u32, u32>,
+    /// これから記録する変更の割り当ての位置
+    assignment_order: u32,
+}
+
+impl<EntryT> Default for UndoLog<EntryT> {
+    fn default() -> Self {
+        return Self { entries: Array::default(), assignment_orders: Array::default(), assignment_order: 0 };
+    }
+}
+
+impl<EntryT> UndoLog<EntryT> {
+    /// これから記録する変更を，assignment_order 番目の割り当てを処理した変更とする
+    #[inline(always)]
+    pub fn set_assignment_order(&mut self, assignment_order: u32) {
+        debug_assert!(self.assignment_orders.last().is_none_or(|&last| last <= assignment_order));
+        self.assignment_order = assignment_order;
+    }
+
+    /// 変更を記録する
+    #[inline(always)]
+    pub fn push(&mut self, entry: EntryT) {
+        self.entries.push(entry);
+        self.assignment_orders.push(self.assignment_order);
+    }
+
+    /// 最後に記録した変更が backjump_assignment_order 番目以降の割り当てを処理した変更であれば取り出す
+    #[inline(always)]
+    pub fn pop(&mut self, backjump_assignment_order: u32) -> Option<EntryT> {
+        if self.assignment_orders.last().is_none_or(|&assignment_order| assignment_order < backjump_assignment_order) {
+            return None;
+        }
+        self.assignment_orders.pop();
+        return self.entries.pop();
+    }
+
+    pub fn len(&self) -> u32 {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 割り当ての決定レベルが順に並ばない trail で，取り消される位置以降の変更が全て取り出されることを確かめる
+    #[test]
+    fn test_pop_with_interleaved_decision_levels() {
+        // chronological backtracking の後は，低い決定レベルの割り当てが高い決定レベルの割り当ての後に並ぶ
+        let decision_levels: [u32; 7] = [1, 2, 2, 1, 3, 1, 2];
+        let mut undo_log = UndoLog::<u32>::default();
+        for (assignment_order, _) in decision_levels.iter().enumerate() {
+            undo_log.set_assignment_order(assignment_order as u32);
+            undo_log.push(assignment_order as u32);
+            undo_log.push(assignment_order as u32 + 100);
+        }
+        // 決定レベル 1 へのバックジャンプでは，決定レベル 2 が始まる 1 番目以降の割り当てが取り消され，
+        // 3 番目・5 番目の決定レベル 1 の割り当ては積み直されて処理し直される
+        let backjump_assignment_order = decision_levels.iter().position(|&level| level > 1).unwrap() as u32;
+        let mut popped_entries = Vec::new();
+        while let Some(entry) = undo_log.pop(backjump_assignment_order) {
+            popped_entries.push(entry);
+        }
+        let expected_entries = Vec::from_iter(
+            (backjump_assignment_order..decision_levels.len() as u32).rev().flat_map(|k| [k + 100, k]),
+        );
+        assert_eq!(popped_entries, expected_entries);
+        assert_eq!(undo_log.len(), 2);
+        // 積み直した割り当てを処理し直して記録できる
+        undo_log.set_assignment_order(backjump_assignment_order);
+        undo_log.push(3);
+        undo_log.set_assignment_order(backjump_assignment_order + 1);
+        undo_log.push(5);
+        assert_eq!(undo_log.pop(backjump_assignment_order + 1), Some(5));
+        assert_eq!(undo_log.pop(backjump_assignment_order + 1), None);
+        assert_eq!(undo_log.pop(0), Some(3));
+        assert_eq!(undo_log.pop(0), Some(100));
+        assert_eq!(undo_log.pop(0), Some(0));
+        assert!(undo_log.is_empty());
+    }
+}
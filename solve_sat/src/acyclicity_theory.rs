@@ -0,0 +1,480 @@
+use std::collections::VecDeque;
+
+use cdcl_engine::{
+    EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait,
+    UndoLog,
+};
+use either::Either;
+use utility::Array;
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// literal が真であれば有向辺 from -> to が存在するという宣言
+///
+/// 宣言した辺のうち存在するものからなるグラフは閉路を含んではならない．
+#[derive(Clone, Copy)]
+pub struct AcyclicityEdge {
+    pub from: u32,
+    pub to: u32,
+    pub literal: Literal,
+}
+
+#[derive(Clone, Copy)]
+pub enum AcyclicityExplainKey {
+    /// literal と explanation_literals[start..end] (偽のリテラル)からなる節
+    Propagation { literal: Literal, start: u32, end: u32 },
+    /// conflicting_literals からなる節
+    Conflict,
+}
+
+/// バックジャンプで戻す状態の変更
+#[derive(Clone, Copy)]
+pub enum AcyclicityUndoEntry {
+    /// literal が真になったことを処理し，literal を持つ辺をグラフに加えた
+    Processed { literal: Literal },
+    /// explanation_literals[start..] に伝播の説明を積んだ
+    Explanation { start: u32 },
+}
+
+#[derive(Default, Clone)]
+pub struct AcyclicityTheorySummary {
+    pub number_of_edges: u32,
+    pub number_of_acyclicity_propagations: u32,
+}
+
+impl ProgressColumnsTrait for AcyclicityTheorySummary {
+    const HEADERS: &'static [&'static str] = &["ACYCLICITY_PROPAGATION"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_acyclicity_propagations].into_iter();
+    }
+}
+
+/// 幅優先探索の結果．探索した頂点と，そこに至る辺
+#[derive(Default, Clone)]
+struct Search {
+    /// 頂点 -> 探索した印
+    stamps: Array<u32, u32>,
+    stamp: u32,
+    /// 頂点 -> 探索でその頂点に至った辺(始点は NULL)
+    parent_edges: Array<u32, u32>,
+    nodes: Array<u32, u32>,
+}
+
+impl Search {
+    fn contains(&self, node: u32) -> bool {
+        return self.stamps[node] == self.stamp;
+    }
+}
+
+/// 非巡回性の理論
+///
+/// 真になった辺のリテラルのうちこの理論が処理済みのものからなるグラフを保ち，辺 u -> v が加わるときに
+/// v から u に到達できれば閉路になるので矛盾とする．辺を加えた後は，v から到達できる頂点 x から
+/// u に到達できる頂点 y への未割り当ての辺 x -> y は閉路 y -> ... -> u -> v -> ... -> x -> y を作るので偽にする．
+/// 伝播・矛盾の説明は，閉路上の辺のリテラルの否定からなる節として伝播時に記録する．
+#[derive(Default, Clone)]
+pub struct AcyclicityTheory {
+    edges: Array<u32, AcyclicityEdge>,
+    /// 辺 -> グラフに加えたか否か
+    is_in_graph: Array<u32, bool>,
+    /// 頂点 -> その頂点から出る辺
+    outgoing_edges: Array<u32, Array<u32, u32>>,
+    /// 頂点 -> その頂点に入る辺
+    incoming_edges: Array<u32, Array<u32, u32>>,
+    /// リテラル(bits) -> そのリテラルを持つ辺
+    edges_of_literal: Array<u32, Array<u32, u32>>,
+    /// リテラル(bits) -> この理論が真になったことを処理済みか否か
+    is_processed_true: Array<u32, bool>,
+    forward_search: Search,
+    backward_search: Search,
+    explanation_literals: Array<u32, Literal>,
+    conflicting_literals: Array<u32, Literal>,
+    summary: AcyclicityTheorySummary,
+}
+
+impl AcyclicityTheory {
+    const NULL: u32 = u32::MAX;
+
+    fn add_node(&mut self, node: u32) {
+        while self.outgoing_edges.len() <= node {
+            self.outgoing_edges.push(Array::default());
+            self.incoming_edges.push(Array::default());
+            for search in [&mut self.forward_search, &mut self.backward_search] {
+                search.stamps.push(0);
+                search.parent_edges.push(Self::NULL);
+            }
+        }
+    }
+
+    /// グラフの辺を is_forward であれば順向きに，そうでなければ逆向きにたどって start から到達できる頂点を探す．
+    /// target に到達すれば打ち切って true を返す
+    fn search(&mut self, start: u32, target: u32, is_forward: bool) -> bool {
+        let search = if is_forward { &mut self.forward_search } else { &mut self.backward_search };
+        search.stamp += 1;
+        search.nodes.clear();
+        search.stamps[start] = search.stamp;
+        search.parent_edges[start] = Self::NULL;
+        search.nodes.push(start);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            if node == target {
+                return true;
+            }
+            let adjacent_edges = if is_forward { &self.outgoing_edges[node] } else { &self.incoming_edges[node] };
+            for &edge_id in adjacent_edges.iter() {
+                if !self.is_in_graph[edge_id] {
+                    continue;
+                }
+                let edge = &self.edges[edge_id];
+                let next = if is_forward { edge.to } else { edge.from };
+                if search.stamps[next] != search.stamp {
+                    search.stamps[next] = search.stamp;
+                    search.parent_edges[next] = edge_id;
+                    search.nodes.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        return false;
+    }
+
+    /// 探索で node に至った辺のリテラルの否定を literals に積む
+    fn push_path_literals(
+        edges: &Array<u32, AcyclicityEdge>,
+        search: &Search,
+        mut node: u32,
+        is_forward: bool,
+        literals: &mut Array<u32, Literal>,
+    ) {
+        while search.parent_edges[node] != Self::NULL {
+            let edge = &edges[search.parent_edges[node]];
+            literals.push(!edge.literal);
+            node = if is_forward { edge.from } else { edge.to };
+        }
+    }
+
+    /// 辺をグラフに加え，閉路ができれば矛盾とし，できなければ閉路を作る辺を偽にする
+    fn add_edge_to_graph<EngineT>(
+        &mut self,
+        edge_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<AcyclicityUndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<AcyclicityExplainKey>,
+    {
+        let AcyclicityEdge { from, to, literal } = self.edges[edge_id];
+        if self.search(to, from, true) {
+            self.conflicting_literals.clear();
+            self.conflicting_literals.push(!literal);
+            Self::push_path_literals(&self.edges, &self.forward_search, from, true, &mut self.conflicting_literals);
+            return PropagationResult::Conflict { explain_key: AcyclicityExplainKey::Conflict.into() };
+        }
+        self.is_in_graph[edge_id] = true;
+        self.search(from, Self::NULL, false);
+        for k in 0..self.forward_search.nodes.len() {
+            let node = self.forward_search.nodes[k];
+            for l in 0..self.outgoing_edges[node].len() {
+                let other_edge_id = self.outgoing_edges[node][l];
+                let other_edge = self.edges[other_edge_id];
+                if engine.is_assigned(other_edge.literal.index()) || !self.backward_search.contains(other_edge.to) {
+                    continue;
+                }
+                // other_edge.to -> ... -> from -> to -> ... -> node -> other_edge.to が閉路になる
+                let start = self.explanation_literals.len();
+                self.explanation_literals.push(!literal);
+                Self::push_path_literals(&self.edges, &self.forward_search, node, true, &mut self.explanation_literals);
+                Self::push_path_literals(
+                    &self.edges,
+                    &self.backward_search,
+                    other_edge.to,
+                    false,
+                    &mut self.explanation_literals,
+                );
+                let end = self.explanation_literals.len();
+                undo_log.push(AcyclicityUndoEntry::Explanation { start: start });
+                self.summary.number_of_acyclicity_propagations += 1;
+                let explain_key =
+                    AcyclicityExplainKey::Propagation { literal: !other_edge.literal, start: start, end: end };
+                let result =
+                    engine.assign(!other_edge.literal, Reason::Propagation { explain_key: explain_key.into() });
+                if result.is_conflict() {
+                    return result;
+                }
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+impl TheoryTrait for AcyclicityTheory {
+    type ExplainKey = AcyclicityExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = AcyclicityTheorySummary;
+    type UndoEntry = AcyclicityUndoEntry;
+
+    fn add_variable(&mut self) {
+        self.edges_of_literal.push(Array::default());
+        self.edges_of_literal.push(Array::default());
+        self.is_processed_true.push(false);
+        self.is_processed_true.push(false);
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        self.is_processed_true[assigned_literal.bits()] = true;
+        undo_log.push(AcyclicityUndoEntry::Processed { literal: assigned_literal });
+        for k in 0..self.edges_of_literal[assigned_literal.bits()].len() {
+            let edge_id = self.edges_of_literal[assigned_literal.bits()][k];
+            if self.is_in_graph[edge_id] {
+                continue;
+            }
+            let result = self.add_edge_to_graph(edge_id, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn explain_propagation(&self, explain_key: AcyclicityExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            AcyclicityExplainKey::Propagation { literal, start, end } => ClauseView::new(Either::Left(
+                [literal].into_iter().chain(self.explanation_literals[start..end].iter().cloned()),
+            )),
+            AcyclicityExplainKey::Conflict => ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned())),
+        };
+    }
+
+    fn undo(&mut self, entry: AcyclicityUndoEntry) {
+        match entry {
+            AcyclicityUndoEntry::Processed { literal } => {
+                // 処理した後に加えた辺も含めて，literal を持つ辺をグラフから取り除く
+                self.is_processed_true[literal.bits()] = false;
+                for &edge_id in self.edges_of_literal[literal.bits()].iter() {
+                    self.is_in_graph[edge_id] = false;
+                }
+            }
+            AcyclicityUndoEntry::Explanation { start } => self.explanation_literals.truncate(start),
+        }
+    }
+
+    /// 変数が辺のリテラルに現れるか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return !self.edges_of_literal[2 * index].is_empty() || !self.edges_of_literal[2 * index + 1].is_empty();
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<AcyclicityEdge> for AcyclicityTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        edge: AcyclicityEdge,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        self.add_node(u32::max(edge.from, edge.to));
+        let edge_id = self.edges.len();
+        self.edges.push(edge);
+        self.is_in_graph.push(false);
+        self.outgoing_edges[edge.from].push(edge_id);
+        self.incoming_edges[edge.to].push(edge_id);
+        self.edges_of_literal[edge.literal.bits()].push(edge_id);
+        self.summary.number_of_edges += 1;
+        if self.is_processed_true[edge.literal.bits()] {
+            return self.add_edge_to_graph(edge_id, engine, undo_log);
+        }
+        if engine.is_assigned(edge.literal.index()) {
+            // 偽であれば辺は存在せず，処理していない真であれば後で assign されたときに処理する
+            return PropagationResult::Noconflict;
+        }
+        // 加えると閉路になる辺は偽にする
+        if self.search(edge.to, edge.from, true) {
+            let start = self.explanation_literals.len();
+            Self::push_path_literals(
+                &self.edges,
+                &self.forward_search,
+                edge.from,
+                true,
+                &mut self.explanation_literals,
+            );
+            let end = self.explanation_literals.len();
+            undo_log.push(AcyclicityUndoEntry::Explanation { start: start });
+            self.summary.number_of_acyclicity_propagations += 1;
+            let explain_key = AcyclicityExplainKey::Propagation { literal: !edge.literal, start: start, end: end };
+            return engine.assign(!edge.literal, Reason::Propagation { explain_key: explain_key.into() });
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::{AcyclicityEdge, AcyclicityExplainKey, AcyclicityTheory};
+    use crate::theory_test::{
+        TestedExplainKey, TestedTheory, add_constraint, assert_conflict_is_valid, assert_explanations_are_valid,
+        assignment_bits, check_random_search, conflict_clause, enumerate_bits, holds_in_bits, literal, make_engine,
+        reason_clause, sorted,
+    };
+
+    impl TestedExplainKey for AcyclicityExplainKey {}
+
+    impl TestedTheory for AcyclicityTheory {
+        type Solution = u32;
+
+        fn holds(&self, literal: Literal, bits: &u32) -> bool {
+            return holds_in_bits(literal, *bits);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> u32 {
+            return assignment_bits(engine);
+        }
+
+        fn checkpoint(&self) -> u32 {
+            return self.explanation_literals.len();
+        }
+
+        /// グラフが真の辺からなって閉路を含まず，未割り当ての辺を一つ加えても閉路ができない
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for index in 0..engine.number_of_variables() {
+                for literal in [literal(index, true), literal(index, false)] {
+                    assert_eq!(self.is_processed_true[literal.bits()], engine.is_true(literal));
+                }
+            }
+            let graph_edges = Vec::from_iter(self.edges.iter().filter(|edge| engine.is_true(edge.literal)).cloned());
+            for (edge_id, edge) in self.edges.iter().enumerate() {
+                assert_eq!(self.is_in_graph[edge_id as u32], engine.is_true(edge.literal));
+            }
+            assert!(is_acyclic(&graph_edges));
+            // 同じリテラルを持つ辺が合わせて作る閉路は，リテラルが真になったときに矛盾として検出する
+            for edge in self.edges.iter().filter(|edge| !engine.is_assigned(edge.literal.index())) {
+                let mut edges = graph_edges.clone();
+                edges.push(*edge);
+                assert!(is_acyclic(&edges));
+            }
+        }
+    }
+
+    fn edge(from: u32, to: u32, literal: Literal) -> AcyclicityEdge {
+        return AcyclicityEdge { from: from, to: to, literal: literal };
+    }
+
+    /// グラフが閉路を含まないか
+    fn is_acyclic<'a>(edges: impl IntoIterator<Item = &'a AcyclicityEdge>) -> bool {
+        let edges = Vec::from_iter(edges);
+        let number_of_nodes = edges.iter().map(|edge| u32::max(edge.from, edge.to) + 1).max().unwrap_or(0);
+        // 入次数が 0 の頂点を取り除き続けて全て取り除ければ閉路はない
+        let mut in_degrees = vec![0; number_of_nodes as usize];
+        for edge in edges.iter() {
+            in_degrees[edge.to as usize] += 1;
+        }
+        let mut nodes = Vec::from_iter((0..number_of_nodes).filter(|&node| in_degrees[node as usize] == 0));
+        let mut number_of_removed_nodes = 0;
+        while let Some(node) = nodes.pop() {
+            number_of_removed_nodes += 1;
+            for edge in edges.iter().filter(|edge| edge.from == node) {
+                in_degrees[edge.to as usize] -= 1;
+                if in_degrees[edge.to as usize] == 0 {
+                    nodes.push(edge.to);
+                }
+            }
+        }
+        return number_of_removed_nodes == number_of_nodes;
+    }
+
+    fn enumerate_solutions(number_of_variables: u32, edges: &[AcyclicityEdge]) -> Vec<u32> {
+        return enumerate_bits(number_of_variables, |bits| {
+            is_acyclic(edges.iter().filter(|edge| holds_in_bits(edge.literal, bits)))
+        });
+    }
+
+    #[test]
+    fn test_edge_closing_cycle_is_falsified() {
+        // 0 -> 1 (x0), 1 -> 2 (x1), 2 -> 0 (x2)
+        let edges = [edge(0, 1, literal(0, true)), edge(1, 2, literal(1, true)), edge(2, 0, literal(2, true))];
+        let solutions = enumerate_solutions(3, &edges);
+        let mut engine = make_engine(AcyclicityTheory::default(), 3);
+        for &edge in edges.iter() {
+            assert!(add_constraint(&mut engine, edge).is_no_conflict());
+        }
+        assert!(engine.assign(literal(0, true), Reason::Decision).is_no_conflict());
+        assert!(!engine.is_assigned(2));
+        assert!(engine.assign(literal(1, true), Reason::Decision).is_no_conflict());
+        assert!(engine.is_false(literal(2, true)));
+        let clause = sorted(reason_clause(&mut engine, literal(2, false)).unwrap());
+        assert!(clause == vec![literal(0, false), literal(1, false), literal(2, false)]);
+        assert_explanations_are_valid(&mut engine, &solutions);
+        engine.theory().assert_consistent(&engine);
+        let _ = engine.backjump(1).count();
+        assert!(!engine.is_assigned(1) && !engine.is_assigned(2));
+        assert_eq!(engine.theory().explanation_literals.len(), 0);
+        engine.theory().assert_consistent(&engine);
+    }
+
+    #[test]
+    fn test_edge_added_after_assignment_conflicts() {
+        // 0 -> 1 (x0) が存在するときに 1 -> 0 (x1) を真のリテラルで加えると閉路になる
+        let edges = [edge(0, 1, literal(0, true)), edge(1, 0, literal(1, true))];
+        let solutions = enumerate_solutions(2, &edges);
+        let mut engine = make_engine(AcyclicityTheory::default(), 2);
+        assert!(add_constraint(&mut engine, edges[0]).is_no_conflict());
+        assert!(engine.assign(literal(0, true), Reason::Decision).is_no_conflict());
+        assert!(engine.assign(literal(1, true), Reason::Decision).is_no_conflict());
+        let PropagationResult::Conflict { explain_key } = add_constraint(&mut engine, edges[1]) else {
+            panic!("expected a conflict");
+        };
+        assert_conflict_is_valid(&engine, explain_key, &solutions);
+        assert!(sorted(conflict_clause(&engine, explain_key)) == vec![literal(0, false), literal(1, false)]);
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(29);
+        for _ in 0..500 {
+            let number_of_variables = rng.gen_range(2..8);
+            let number_of_nodes = rng.gen_range(2..6);
+            let edges = Vec::from_iter((0..rng.gen_range(1..9)).map(|_| {
+                edge(
+                    rng.gen_range(0..number_of_nodes),
+                    rng.gen_range(0..number_of_nodes),
+                    literal(rng.gen_range(0..number_of_variables), rng.gen_bool(0.5)),
+                )
+            }));
+            let solutions = enumerate_solutions(number_of_variables, &edges);
+            let mut engine = make_engine(AcyclicityTheory::default(), number_of_variables);
+            let mut result = PropagationResult::Noconflict;
+            for &edge in edges.iter() {
+                result = add_constraint(&mut engine, edge);
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
@@ -9,7 +9,14 @@ use crate::{
 
 pub enum AnalyzeResult<ClauseT: ClauseTrait, IteratorT: Iterator<Item = u32> + Clone> {
     Unsatisfiable,
-    Backjumpable { backjump_decision_level: u32, learnt_clause: Constraint<ClauseT>, related_variables: IteratorT },
+    Backjumpable {
+        backjump_decision_level: u32,
+        /// 学習節で唯一最も高い決定レベル．chronological backtracking の後は現在の決定レベルより低いことがある
+        conflict_decision_level: u32,
+        learnt_clause: Constraint<ClauseT>,
+        related_variables: IteratorT,
+        reason_side_variables: IteratorT,
+    },
 }
 
 #[derive(Default)]
@@ -17,19 +24,28 @@ pub struct Analyze {
     simplify: Simplify,
     learnt_clause: Map<u32, Boolean>,
     related_variables: Set<u32>,
+    /// 学習節のリテラルの割り当て理由にだけ現れた変数
+    reason_side_variables: Set<u32>,
+    is_reason_side_enabled: bool,
     buffer: Array<u32, Literal>,
 }
 
 impl Analyze {
+    /// 解析のたびに reason_side_variables を求めるか否かを設定する
+    pub fn set_reason_side_enabled(&mut self, is_reason_side_enabled: bool) {
+        self.is_reason_side_enabled = is_reason_side_enabled;
+    }
+
     pub fn analyze<'a>(
         &'a mut self,
         explain_key: ExplainKey,
-        engine: &SATEngine,
-    ) -> AnalyzeResult<impl ClauseTrait + 'a, impl Iterator<Item = u32> + Clone + 'a> {
+        engine: &mut SATEngine,
+    ) -> AnalyzeResult<impl ClauseTrait + use<'a>, impl Iterator<Item = u32> + Clone + use<'a>> {
         self.learnt_clause.clear();
         self.related_variables.clear();
+        self.reason_side_variables.clear();
         {
-            let conflicting_constraint = engine.explain(explain_key.into()).into();
+            let conflicting_constraint = engine.explain_conflict(explain_key.into()).into();
             // eprintln!("CONFLICTING {}", &conflicting_constraint);
             Self::resolve(&mut self.learnt_clause, conflicting_constraint, engine);
         }
@@ -39,8 +55,9 @@ impl Analyze {
                 return AnalyzeResult::Unsatisfiable;
             }
 
-            if let Some(backjump_decision_level) = Self::calculate_backjump_decision_level(&self.learnt_clause, engine)
-            {
+            let [conflict_decision_level, backjump_decision_level] =
+                Self::calculate_top2_decision_levels(&self.learnt_clause, engine);
+            if conflict_decision_level > backjump_decision_level {
                 // バックジャンプ可能な節が得られた
                 self.buffer.clear();
                 self.buffer.extend(self.learnt_clause.iter().map(|(&index, &value)| Literal::new(index, value)));
@@ -49,22 +66,29 @@ impl Analyze {
                 for literal in self.buffer.iter() {
                     self.related_variables.insert(literal.index());
                 }
+                if self.is_reason_side_enabled {
+                    self.collect_reason_side_variables(engine);
+                }
                 return AnalyzeResult::Backjumpable {
                     backjump_decision_level: backjump_decision_level,
+                    conflict_decision_level: conflict_decision_level,
                     learnt_clause: make_constraint(self.buffer.iter().cloned()),
                     related_variables: self.related_variables.iter().cloned(),
+                    reason_side_variables: self.reason_side_variables.iter().cloned(),
                 };
             }
 
-            // 最後に割り当てられたリテラルを特定
-            let last_assigned_literal = Self::find_last_assigned_literal(&self.learnt_clause, engine);
-            // 割り当て理由を取得
-            let reason = engine.get_reason(last_assigned_literal.index()).unwrap();
+            // 最も高い決定レベルのリテラルのうち最後に割り当てられたものを特定
+            // (chronological backtracking の後は，より低い決定レベルのリテラルが後に割り当てられていることがある)
+            let last_assigned_literal =
+                Self::find_last_assigned_literal(&self.learnt_clause, conflict_decision_level, engine);
+            // 割り当て理由を取得(説明がまだなければここで作る)
+            let reason = engine.get_explained_reason(last_assigned_literal.index()).unwrap();
             let Reason::Propagation { explain_key } = reason else {
                 // 割り当て理由は伝播であるはず
                 unreachable!()
             };
-            let reason_constraint = engine.explain(explain_key.into()).into();
+            let reason_constraint = engine.explain_propagation(explain_key.into()).into();
             // eprintln!("REASON_CONSTRAINT: {}", &reason_constraint);
 
             // 節融合
@@ -75,7 +99,27 @@ impl Analyze {
         }
     }
 
-    fn calculate_backjump_decision_level(learnt_clause: &Map<u32, Boolean>, engine: &SATEngine) -> Option<u32> {
+    /// 学習節のリテラルの割り当て理由に現れ，解析に現れなかった変数を集める
+    fn collect_reason_side_variables(&mut self, engine: &mut SATEngine) {
+        for k in 0..self.buffer.len() {
+            let index = self.buffer[k].index();
+            let Some(Reason::Propagation { explain_key }) = engine.get_explained_reason(index) else {
+                continue;
+            };
+            let reason_constraint: Constraint<_> = engine.explain_propagation(explain_key.into()).into();
+            for literal in reason_constraint.iter() {
+                if literal.index() != index
+                    && engine.get_decision_level(literal.index()) != 0
+                    && !self.related_variables.contains_key(literal.index())
+                {
+                    self.reason_side_variables.insert(literal.index());
+                }
+            }
+        }
+    }
+
+    /// 学習節のリテラルの決定レベルのうち大きいもの 2 つ(重複を含む)
+    fn calculate_top2_decision_levels(learnt_clause: &Map<u32, Boolean>, engine: &SATEngine) -> [u32; 2] {
         let mut top2_decision_levels = [0; 2];
         for (&index, &value) in learnt_clause.iter() {
             let literal = Literal::new(index, value);
@@ -88,19 +132,22 @@ impl Analyze {
                 top2_decision_levels[1] = decision_level;
             }
         }
-        if top2_decision_levels[0] > top2_decision_levels[1] {
-            return Some(top2_decision_levels[1]);
-        } else {
-            return None;
-        }
+        return top2_decision_levels;
     }
 
-    fn find_last_assigned_literal(learnt_clause: &Map<u32, Boolean>, engine: &SATEngine) -> Literal {
+    fn find_last_assigned_literal(
+        learnt_clause: &Map<u32, Boolean>,
+        decision_level: u32,
+        engine: &SATEngine,
+    ) -> Literal {
         let mut max_assignment_order = None;
         let mut last_assigned_literal = None;
         for (&index, &value) in learnt_clause.iter() {
             let literal = Literal::new(index, value);
             debug_assert!(engine.is_false(literal));
+            if engine.get_decision_level(literal.index()) != decision_level {
+                continue;
+            }
             let assignment_order = engine.get_assignment_order(literal.index());
             if max_assignment_order.is_none_or(|x| assignment_order > x) {
                 max_assignment_order = Some(assignment_order);
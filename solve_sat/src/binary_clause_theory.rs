@@ -1,5 +1,9 @@
-use cdcl_engine::{Literal, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait};
-use utility::Array;
+use std::collections::HashMap;
+
+use cdcl_engine::{
+    Boolean, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait, UndoLog,
+};
+use utility::{Array, Set};
 
 #[derive(Clone, Copy)]
 pub struct BinaryClause {
@@ -38,18 +42,155 @@ pub struct BinaryClauseTheoryExplainKey {
 pub struct BinaryClauseTheorySummary {
     pub number_of_binary_clauses: u32,
     pub number_of_learnt_binary_clauses: u32,
+    pub number_of_transitively_reduced_binary_clauses: u32,
 }
 
-#[derive(Default, Clone)]
+impl ProgressColumnsTrait for BinaryClauseTheorySummary {
+    const HEADERS: &'static [&'static str] = &["BINARY_CLAUSE", "BINARY_LEARNT_CLAUSE", "TRANSITIVELY_REDUCED"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [
+            self.number_of_binary_clauses,
+            self.number_of_learnt_binary_clauses,
+            self.number_of_transitively_reduced_binary_clauses,
+        ]
+        .into_iter();
+    }
+}
+
+#[derive(Clone)]
 pub struct BinaryClauseTheory {
     implications: Array<u32, [Array<u32, Literal>; 2]>,
+    /// 重複検出用の節集合(値は学習節であるか否か)
+    binary_clauses: HashMap<[Literal; 2], bool>,
+    /// 1 回の推移簡約で走査する辺数の上限
+    transitive_reduction_effort: usize,
+    /// 次回の推移簡約を開始するリテラル
+    transitive_reduction_cursor: u32,
+    visited_literals: Set<u32>,
+    literal_stack: Array<u32, Literal>,
     summary: BinaryClauseTheorySummary,
 }
 
+impl BinaryClauseTheory {
+    pub fn new(transitive_reduction_effort: usize) -> Self {
+        Self {
+            implications: Array::default(),
+            binary_clauses: HashMap::default(),
+            transitive_reduction_effort: transitive_reduction_effort,
+            transitive_reduction_cursor: 0,
+            visited_literals: Set::default(),
+            literal_stack: Array::default(),
+            summary: BinaryClauseTheorySummary::default(),
+        }
+    }
+
+    /// literal が真になったときに含意されるリテラルを返す
+    #[inline(always)]
+    pub fn implications(&self, literal: Literal) -> impl Iterator<Item = Literal> + Clone + '_ {
+        return self.implications[literal.index()][literal.value()].iter().cloned();
+    }
+
+    #[inline(always)]
+    fn make_key(l0: Literal, l1: Literal) -> [Literal; 2] {
+        return if l0.bits() < l1.bits() { [l0, l1] } else { [l1, l0] };
+    }
+
+    /// 2 項節を (節, 学習節か否か) で列挙する
+    pub fn iter_binary_clauses(&self) -> impl Iterator<Item = (BinaryClause, bool)> + '_ {
+        return (0..self.implications.len()).flat_map(move |index| {
+            [Boolean::FALSE, Boolean::TRUE].into_iter().flat_map(move |value| {
+                let l0 = Literal::new(index, !value);
+                self.implications[index][value]
+                    .iter()
+                    .filter(move |l1| l0.bits() < l1.bits())
+                    .map(move |&l1| (BinaryClause::new(l0, l1), self.binary_clauses[&Self::make_key(l0, l1)]))
+            })
+        });
+    }
+
+    pub fn remove_binary_clause(&mut self, l0: Literal, l1: Literal) {
+        let is_learnt = self.binary_clauses.remove(&Self::make_key(l0, l1)).unwrap();
+        for (l, m) in [(l0, l1), (l1, l0)] {
+            let implications = &mut self.implications[l.index()][!l.value()];
+            let position = implications.iter().position(|&x| x == m).unwrap();
+            implications.swap_remove(position as u32);
+        }
+        self.summary.number_of_binary_clauses -= 1;
+        if is_learnt {
+            self.summary.number_of_learnt_binary_clauses -= 1;
+        }
+    }
+
+    /// 推移簡約: 他の 2 項節の連鎖から導かれる 2 項節を削除する
+    fn reduce_transitively(&mut self) {
+        let number_of_literals = 2 * self.implications.len();
+        if number_of_literals == 0 {
+            return;
+        }
+        let mut ticks = 0;
+        for _ in 0..number_of_literals {
+            if ticks >= self.transitive_reduction_effort {
+                break;
+            }
+            self.transitive_reduction_cursor %= number_of_literals;
+            let source = Literal::new(
+                self.transitive_reduction_cursor / 2,
+                Boolean::from(self.transitive_reduction_cursor % 2 == 1),
+            );
+            self.transitive_reduction_cursor += 1;
+            let mut k = 0;
+            while k < self.implications[source.index()][source.value()].len() {
+                let target = self.implications[source.index()][source.value()][k];
+                if self.is_reachable_indirectly(source, target, &mut ticks) {
+                    // source -> target は他の経路から導かれるので (!source or target) を削除
+                    self.remove_binary_clause(!source, target);
+                    self.summary.number_of_transitively_reduced_binary_clauses += 1;
+                } else {
+                    k += 1;
+                }
+                if ticks >= self.transitive_reduction_effort {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// source から target へ，(!source or target) の辺を用いずに到達可能かを判定する
+    ///
+    /// 直接の辺 source -> target だけでなく対偶の辺 !target -> !source も同じ節なので用いない
+    fn is_reachable_indirectly(&mut self, source: Literal, target: Literal, ticks: &mut usize) -> bool {
+        self.visited_literals.clear();
+        self.literal_stack.clear();
+        self.visited_literals.insert(source.bits());
+        self.literal_stack.push(source);
+        while let Some(literal) = self.literal_stack.pop() {
+            for &next in self.implications[literal.index()][literal.value()].iter() {
+                *ticks += 1;
+                if (literal == source && next == target) || (literal == !target && next == !source) {
+                    continue;
+                }
+                if next == target {
+                    return true;
+                }
+                if !self.visited_literals.contains_key(next.bits()) {
+                    self.visited_literals.insert(next.bits());
+                    self.literal_stack.push(next);
+                }
+            }
+            if *ticks >= self.transitive_reduction_effort {
+                return false;
+            }
+        }
+        return false;
+    }
+}
+
 impl TheoryTrait for BinaryClauseTheory {
     type ExplainKey = BinaryClauseTheoryExplainKey;
     type ExplanationConstraint<'a> = BinaryClause;
     type Summary = BinaryClauseTheorySummary;
+    type UndoEntry = ();
     fn add_variable(&mut self) {
         self.implications.push([Array::default(), Array::default()]);
     }
@@ -58,18 +199,24 @@ impl TheoryTrait for BinaryClauseTheory {
         &mut self,
         assigned_literal: Literal,
         engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> cdcl_engine::PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT: cdcl_engine::EngineTrait,
         EngineT::CompositeExplainKey: From<Self::ExplainKey>,
     {
         debug_assert!(engine.is_true(assigned_literal));
-        debug_assert!(engine.get_decision_level(assigned_literal.index()) == engine.current_decision_level());
+        // chronological backtracking で積み直したリテラルであれば，その決定レベルで伝播する
+        let decision_level = engine.get_decision_level(assigned_literal.index());
         for &literal in self.implications[assigned_literal.index()][assigned_literal.value()].iter() {
             let explain_key = Self::ExplainKey { binary_clause: BinaryClause::new(!assigned_literal, literal) };
             if !engine.is_assigned(literal.index()) {
                 // literal が未割り当てであれば literal に真を割り当て
-                let inner_result = engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+                let inner_result = engine.assign_at_level(
+                    literal,
+                    Reason::Propagation { explain_key: explain_key.into() },
+                    decision_level,
+                );
                 if inner_result.is_conflict() {
                     return inner_result;
                 }
@@ -82,18 +229,17 @@ impl TheoryTrait for BinaryClauseTheory {
     }
 
     #[inline(always)]
-    fn explain(&self, explain_key: BinaryClauseTheoryExplainKey) -> BinaryClause {
+    fn explain_propagation(&self, explain_key: BinaryClauseTheoryExplainKey) -> BinaryClause {
         return explain_key.binary_clause;
     }
 
     #[inline(always)]
-    fn unassign(&mut self, _unassigned_literals: impl Iterator<Item = Literal>) {
-        // なにもしない
+    fn undo(&mut self, _entry: ()) {
+        // 状態を記録しないのでなにもしない
     }
 
-    #[inline(always)]
     fn reduce_constraints(&mut self) {
-        // なにもしない
+        self.reduce_transitively();
     }
 
     fn summary(&self) -> Self::Summary {
@@ -107,6 +253,7 @@ impl TheoryAddConstraintTrait<BinaryClause> for BinaryClauseTheory {
         binary_clause: BinaryClause,
         is_learnt: bool,
         engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT::CompositeExplainKey: From<Self::ExplainKey>,
@@ -116,10 +263,17 @@ impl TheoryAddConstraintTrait<BinaryClause> for BinaryClauseTheory {
         debug_assert!(!engine.is_false(l0) || !engine.is_false(l1));
 
         // 既に存在する制約との重複を確認
-        if self.implications[l0.index()][!l0.value()].contains(&l1) {
+        if let Some(is_learnt_clause) = self.binary_clauses.get_mut(&Self::make_key(l0, l1)) {
+            debug_assert!(self.implications[l0.index()][!l0.value()].contains(&l1));
             debug_assert!(self.implications[l1.index()][!l1.value()].contains(&l0));
+            if *is_learnt_clause && !is_learnt {
+                // 学習節と同じ節が元の節として追加された場合は元の節として扱う
+                *is_learnt_clause = false;
+                self.summary.number_of_learnt_binary_clauses -= 1;
+            }
             return PropagationResult::Noconflict;
         }
+        self.binary_clauses.insert(Self::make_key(l0, l1), is_learnt);
 
         // summary を更新
         self.summary.number_of_binary_clauses += 1;
@@ -135,15 +289,76 @@ impl TheoryAddConstraintTrait<BinaryClause> for BinaryClauseTheory {
         // 伝播の発生を確認
         if engine.is_false(l0) && !engine.is_assigned(l1.index()) {
             // l0 が FALSE， l1 が未割り当てであれば l1 を真に
-            debug_assert!(engine.get_decision_level(l0.index()) == engine.current_decision_level());
-            return engine.assign(l1, Reason::Propagation { explain_key: explain_key.into() });
+            let decision_level = engine.get_decision_level(l0.index());
+            return engine.assign_at_level(l1, Reason::Propagation { explain_key: explain_key.into() }, decision_level);
         } else if !engine.is_assigned(l0.index()) && engine.is_false(l1) {
             // l1 が未割り当て， l1 が FALSE であれば l0 を真に
-            debug_assert!(engine.get_decision_level(l1.index()) == engine.current_decision_level());
-            return engine.assign(l0, Reason::Propagation { explain_key: explain_key.into() });
+            let decision_level = engine.get_decision_level(l1.index());
+            return engine.assign_at_level(l0, Reason::Propagation { explain_key: explain_key.into() }, decision_level);
         } else {
             // それ以外
             return PropagationResult::Noconflict;
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, Literal, TheoryTrait};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::BinaryClauseTheory;
+
+    fn make_theory(number_of_variables: u32, clauses: &[[Literal; 2]]) -> BinaryClauseTheory {
+        let mut theory = BinaryClauseTheory::new(usize::MAX);
+        for _ in 0..number_of_variables {
+            theory.add_variable();
+        }
+        for &[l0, l1] in clauses {
+            if theory.binary_clauses.contains_key(&BinaryClauseTheory::make_key(l0, l1)) {
+                continue;
+            }
+            theory.binary_clauses.insert(BinaryClauseTheory::make_key(l0, l1), false);
+            theory.implications[l0.index()][!l0.value()].push(l1);
+            theory.implications[l1.index()][!l1.value()].push(l0);
+            theory.summary.number_of_binary_clauses += 1;
+        }
+        return theory;
+    }
+
+    /// 割り当て bits (変数 i の値は i ビット目) が全ての 2 項節を充足するか
+    fn is_satisfied(theory: &BinaryClauseTheory, bits: u32) -> bool {
+        let is_true = |literal: Literal| Boolean::from((bits >> literal.index()) & 1 == 1) == literal.value();
+        return theory.iter_binary_clauses().all(|(clause, _)| clause.iter().any(is_true));
+    }
+
+    #[test]
+    fn test_reduce_transitively_keeps_self_justified_clause() {
+        let s = Literal::new(0, Boolean::TRUE);
+        let t = Literal::new(1, Boolean::TRUE);
+        let mut theory = make_theory(2, &[[!s, !t], [s, t], [!s, t]]);
+        theory.reduce_transitively();
+        assert_eq!(theory.iter_binary_clauses().count(), 3);
+        // s = 1, t = 0 は (!s or t) を充足しない
+        assert!(!is_satisfied(&theory, 0b01));
+    }
+
+    #[test]
+    fn test_reduce_transitively_preserves_models() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        for _ in 0..1000 {
+            let number_of_variables = rng.gen_range(2..7);
+            let clauses = Vec::from_iter((0..rng.gen_range(1..12)).filter_map(|_| {
+                let l0 = Literal::new(rng.gen_range(0..number_of_variables), Boolean::from(rng.gen_bool(0.5)));
+                let l1 = Literal::new(rng.gen_range(0..number_of_variables), Boolean::from(rng.gen_bool(0.5)));
+                (l0.index() != l1.index()).then_some([l0, l1])
+            }));
+            let original = make_theory(number_of_variables, &clauses);
+            let mut reduced = original.clone();
+            reduced.reduce_transitively();
+            for bits in 0..(1 << number_of_variables) {
+                assert_eq!(is_satisfied(&original, bits), is_satisfied(&reduced, bits));
+            }
+        }
+    }
+}
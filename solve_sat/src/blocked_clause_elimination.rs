@@ -0,0 +1,266 @@
+use cdcl_engine::{EngineGetTheoryTrait, EngineTrait, Literal};
+use utility::{Array, Set};
+
+use crate::{
+    binary_clause_theory::{BinaryClause, BinaryClauseTheory},
+    clause_theory::ClauseTheory,
+    engine::{SATEngine, contains_theory_variable},
+    reconstruction_stack::ReconstructionStack,
+};
+
+#[derive(Clone, Copy)]
+enum Origin {
+    Clause { row_id: u32 },
+    BinaryClause,
+}
+
+#[derive(Clone)]
+struct OccurrenceClause {
+    literals: Array<u32, Literal>,
+    origin: Origin,
+    is_learnt: bool,
+    is_removed: bool,
+}
+
+#[derive(Default, Clone)]
+pub struct BlockedClauseEliminationSummary {
+    pub number_of_blocked_clauses: u32,
+    pub number_of_covered_clauses: u32,
+}
+
+/// 決定レベル 0 での blocked clause elimination (および covered clause elimination)
+///
+/// ClauseTheory と BinaryClauseTheory の節から出現リストを作り，学習節でない節のうち
+/// blocked (CCE が有効ならば covered) なものを削除して ReconstructionStack に積む．
+/// 学習節も出現リストに含めて判定するので，学習節を含む節集合全体について充足可能性が保たれる．
+/// 基数制約・擬似ブール制約に現れる変数は出現リストで扱えないので，そのリテラルは witness にしない．
+pub struct BlockedClauseElimination {
+    is_covered_clause_elimination_enabled: bool,
+    clauses: Array<u32, OccurrenceClause>,
+    /// リテラル(bits) -> そのリテラルを含む節の番号
+    occurrences: Array<u32, Array<u32, u32>>,
+    /// 判定中の節(covered literal を追加したもの)
+    extended_clause: Array<u32, Literal>,
+    /// extended_clause に含まれるリテラル(bits)
+    extended_clause_literals: Set<u32>,
+    /// covered literal addition の各段階での (用いたリテラル, 追加前の節)
+    covered_literal_additions: Array<u32, (Literal, Array<u32, Literal>)>,
+    intersection: Array<u32, Literal>,
+    resolution_candidate_literals: Set<u32>,
+    summary: BlockedClauseEliminationSummary,
+}
+
+enum Elimination {
+    Blocked { witness: Literal },
+    Covered { witness: Literal },
+    None,
+}
+
+impl BlockedClauseElimination {
+    pub fn new(is_covered_clause_elimination_enabled: bool) -> Self {
+        Self {
+            is_covered_clause_elimination_enabled: is_covered_clause_elimination_enabled,
+            clauses: Array::default(),
+            occurrences: Array::default(),
+            extended_clause: Array::default(),
+            extended_clause_literals: Set::default(),
+            covered_literal_additions: Array::default(),
+            intersection: Array::default(),
+            resolution_candidate_literals: Set::default(),
+            summary: BlockedClauseEliminationSummary::default(),
+        }
+    }
+
+    pub fn summary(&self) -> &BlockedClauseEliminationSummary {
+        return &self.summary;
+    }
+
+    pub fn eliminate(&mut self, engine: &mut SATEngine, reconstruction_stack: &mut ReconstructionStack, effort: usize) {
+        debug_assert!(engine.current_decision_level() == 0);
+        self.build_occurrences(engine);
+        let mut ticks = 0;
+        for clause_id in 0..self.clauses.len() {
+            if ticks >= effort {
+                break;
+            }
+            if self.clauses[clause_id].is_learnt || self.clauses[clause_id].is_removed {
+                continue;
+            }
+            let elimination = self.try_eliminate(clause_id, engine, &mut ticks);
+            let witness = match elimination {
+                Elimination::None => continue,
+                Elimination::Blocked { witness } => {
+                    self.summary.number_of_blocked_clauses += 1;
+                    witness
+                }
+                Elimination::Covered { witness } => {
+                    self.summary.number_of_covered_clauses += 1;
+                    witness
+                }
+            };
+            // 解の修復は積んだ順と逆順に行われるので，covered literal addition の各段階を先に積む
+            for (literal, clause) in self.covered_literal_additions.iter() {
+                reconstruction_stack.push(*literal, clause.iter().cloned());
+            }
+            reconstruction_stack.push(witness, self.extended_clause.iter().cloned());
+            self.remove(clause_id, engine);
+        }
+    }
+
+    fn build_occurrences(&mut self, engine: &SATEngine) {
+        self.clauses.clear();
+        for (row_id, literals, is_learnt) in engine.get_theory::<ClauseTheory>().iter_clauses() {
+            self.clauses.push(OccurrenceClause {
+                literals: Array::from_iter(literals.iter().cloned()),
+                origin: Origin::Clause { row_id: row_id },
+                is_learnt: is_learnt,
+                is_removed: false,
+            });
+        }
+        for (binary_clause, is_learnt) in engine.get_theory::<BinaryClauseTheory>().iter_binary_clauses() {
+            self.clauses.push(OccurrenceClause {
+                literals: Array::from_iter(binary_clause.iter()),
+                origin: Origin::BinaryClause,
+                is_learnt: is_learnt,
+                is_removed: false,
+            });
+        }
+        for occurrence in self.occurrences.iter_mut() {
+            occurrence.clear();
+        }
+        self.occurrences.resize_with(2 * engine.number_of_variables(), Array::default);
+        for clause_id in 0..self.clauses.len() {
+            for literal in self.clauses[clause_id].literals.iter() {
+                self.occurrences[literal.bits()].push(clause_id);
+            }
+        }
+    }
+
+    fn remove(&mut self, clause_id: u32, engine: &mut SATEngine) {
+        let clause = &mut self.clauses[clause_id];
+        debug_assert!(!clause.is_removed);
+        clause.is_removed = true;
+        match clause.origin {
+            Origin::Clause { row_id } => engine.get_theory_mut::<ClauseTheory>().remove_clause(row_id),
+            Origin::BinaryClause => {
+                let binary_clause = BinaryClause::from_iter(clause.literals.iter().cloned());
+                let [l0, l1]: [Literal; 2] = binary_clause.into();
+                engine.get_theory_mut::<BinaryClauseTheory>().remove_binary_clause(l0, l1);
+            }
+        }
+    }
+
+    fn try_eliminate(&mut self, clause_id: u32, engine: &SATEngine, ticks: &mut usize) -> Elimination {
+        self.extended_clause.clear();
+        self.extended_clause_literals.clear();
+        self.covered_literal_additions.clear();
+        for &literal in self.clauses[clause_id].literals.iter() {
+            self.extended_clause.push(literal);
+            self.extended_clause_literals.insert(literal.bits());
+        }
+        // blocked clause の判定
+        for k in 0..self.extended_clause.len() {
+            let literal = self.extended_clause[k];
+            if !Self::is_frozen(literal, engine) && self.is_blocked(clause_id, literal, ticks) {
+                return Elimination::Blocked { witness: literal };
+            }
+        }
+        if !self.is_covered_clause_elimination_enabled {
+            return Elimination::None;
+        }
+        // covered literal addition を繰り返し，節が blocked になれば covered
+        let mut k = 0;
+        while k < self.extended_clause.len() {
+            let literal = self.extended_clause[k];
+            k += 1;
+            if Self::is_frozen(literal, engine) {
+                continue;
+            }
+            if !self.calculate_covered_literals(clause_id, literal, ticks) {
+                // 非恒真な導出節が存在しないので blocked
+                return Elimination::Covered { witness: literal };
+            }
+            if self.intersection.iter().all(|l| self.extended_clause_literals.contains_key(l.bits())) {
+                continue;
+            }
+            self.covered_literal_additions.push((literal, self.extended_clause.clone()));
+            for m in 0..self.intersection.len() {
+                let covered_literal = self.intersection[m];
+                if !self.extended_clause_literals.contains_key(covered_literal.bits()) {
+                    self.extended_clause.push(covered_literal);
+                    self.extended_clause_literals.insert(covered_literal.bits());
+                }
+            }
+            // 追加したリテラルによって blocked になる可能性があるので先頭から再判定
+            k = 0;
+        }
+        return Elimination::None;
+    }
+
+    /// literal を witness にできないか否か
+    fn is_frozen(literal: Literal, engine: &SATEngine) -> bool {
+        return engine.is_assigned(literal.index()) || contains_theory_variable(engine, literal.index());
+    }
+
+    /// extended_clause が literal について blocked であるかを判定する
+    fn is_blocked(&self, clause_id: u32, literal: Literal, ticks: &mut usize) -> bool {
+        for &other_clause_id in self.occurrences[(!literal).bits()].iter() {
+            let other_clause = &self.clauses[other_clause_id];
+            if other_clause_id == clause_id || other_clause.is_removed {
+                continue;
+            }
+            *ticks += other_clause.literals.len() as usize;
+            if !self.is_tautological_resolvent(other_clause, literal) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    #[inline(always)]
+    fn is_tautological_resolvent(&self, other_clause: &OccurrenceClause, literal: Literal) -> bool {
+        return other_clause
+            .literals
+            .iter()
+            .any(|&l| l != !literal && self.extended_clause_literals.contains_key((!l).bits()));
+    }
+
+    /// literal についての非恒真な導出節の相手全てに共通するリテラルを intersection に求める
+    /// 非恒真な導出節が存在しなければ false を返す
+    fn calculate_covered_literals(&mut self, clause_id: u32, literal: Literal, ticks: &mut usize) -> bool {
+        self.intersection.clear();
+        let mut is_first = true;
+        for k in 0..self.occurrences[(!literal).bits()].len() {
+            let other_clause_id = self.occurrences[(!literal).bits()][k];
+            let other_clause = &self.clauses[other_clause_id];
+            if other_clause_id == clause_id || other_clause.is_removed {
+                continue;
+            }
+            *ticks += other_clause.literals.len() as usize;
+            if self.is_tautological_resolvent(other_clause, literal) {
+                continue;
+            }
+            if is_first {
+                is_first = false;
+                self.intersection.extend(other_clause.literals.iter().cloned().filter(|&l| l != !literal));
+            } else {
+                self.resolution_candidate_literals.clear();
+                for l in other_clause.literals.iter() {
+                    self.resolution_candidate_literals.insert(l.bits());
+                }
+                let mut m = 0;
+                while m < self.intersection.len() {
+                    if self.resolution_candidate_literals.contains_key(self.intersection[m].bits()) {
+                        m += 1;
+                    } else {
+                        self.intersection.swap_remove(m);
+                    }
+                }
+            }
+            if self.intersection.is_empty() {
+                break;
+            }
+        }
+        return !is_first;
+    }
+}
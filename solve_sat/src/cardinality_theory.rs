@@ -0,0 +1,394 @@
+use cdcl_engine::{
+    EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait,
+    UndoLog,
+};
+use either::Either;
+use utility::Array;
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// 基数制約 sum(literals) >= bound
+#[derive(Clone)]
+pub struct CardinalityConstraint {
+    pub literals: Array<u32, Literal>,
+    pub bound: u32,
+}
+
+impl CardinalityConstraint {
+    /// 相補なリテラルの組は常に一方だけが真になるので取り除いて bound を 1 減らす
+    pub fn new(literals: impl Iterator<Item = Literal>, mut bound: u32) -> Self {
+        let mut normalized_literals = Array::<u32, Literal>::default();
+        for literal in literals {
+            if let Some(k) = normalized_literals.iter().position(|&l| l == !literal) {
+                normalized_literals.swap_remove(k as u32);
+                bound = bound.saturating_sub(1);
+            } else {
+                assert!(!normalized_literals.contains(&literal), "duplicate literal in cardinality constraint");
+                normalized_literals.push(literal);
+            }
+        }
+        return Self { literals: normalized_literals, bound: bound };
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum CardinalityExplainKey {
+    /// literal と explanation_literals[start..end] (偽のリテラル)からなる節
+    Propagation { literal: Literal, start: u32, end: u32 },
+    /// conflicting_literals からなる節
+    Conflict,
+}
+
+/// バックジャンプで戻す状態の変更
+#[derive(Clone, Copy)]
+pub enum CardinalityUndoEntry {
+    /// literal が偽になり，それを含む制約の number_of_falses を増やした
+    Falsified { literal: Literal },
+    /// explanation_literals[start..] に伝播の説明を積んだ
+    Explanation { start: u32 },
+}
+
+#[derive(Default, Clone)]
+pub struct CardinalityTheorySummary {
+    pub number_of_cardinality_constraints: u32,
+    pub number_of_cardinality_propagations: u32,
+}
+
+impl ProgressColumnsTrait for CardinalityTheorySummary {
+    const HEADERS: &'static [&'static str] = &["CARDINALITY_PROPAGATION"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_cardinality_propagations].into_iter();
+    }
+}
+
+#[derive(Clone)]
+struct Row {
+    literals: Array<u32, Literal>,
+    bound: u32,
+    /// 偽が割り当てられたリテラルの数
+    number_of_falses: u32,
+}
+
+/// 基数制約の理論
+///
+/// 制約ごとに偽のリテラルの数を数え，偽でないリテラルの数が bound に等しくなれば残りを全て真にする．
+/// 伝播の説明は，伝播したリテラルとその時点で偽であったリテラルからなる節として伝播時に記録する．
+#[derive(Default, Clone)]
+pub struct CardinalityTheory {
+    rows: Array<u32, Row>,
+    /// リテラル(bits) -> そのリテラルを含む制約の番号
+    occurrences: Array<u32, Array<u32, u32>>,
+    explanation_literals: Array<u32, Literal>,
+    conflicting_literals: Array<u32, Literal>,
+    candidate_literals: Array<u32, Literal>,
+    summary: CardinalityTheorySummary,
+}
+
+impl CardinalityTheory {
+    /// 偽のリテラルの数が len - bound 以上になった制約について，矛盾の検出と伝播を行う
+    fn propagate<EngineT>(
+        &mut self,
+        row_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<CardinalityUndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<CardinalityExplainKey>,
+    {
+        let row = &self.rows[row_id];
+        let start = self.explanation_literals.len();
+        self.candidate_literals.clear();
+        for &literal in row.literals.iter() {
+            if engine.is_false(literal) {
+                self.explanation_literals.push(literal);
+            } else {
+                self.candidate_literals.push(literal);
+            }
+        }
+        let end = self.explanation_literals.len();
+        if self.candidate_literals.len() < row.bound {
+            // 偽でないリテラルが足りないので矛盾
+            self.conflicting_literals.clear();
+            self.conflicting_literals.extend(self.explanation_literals[start..end].iter().cloned());
+            self.explanation_literals.truncate(start);
+            return PropagationResult::Conflict { explain_key: CardinalityExplainKey::Conflict.into() };
+        }
+        if self.candidate_literals.len() > row.bound {
+            self.explanation_literals.truncate(start);
+            return PropagationResult::Noconflict;
+        }
+        // 偽でないリテラルは全て真でなければならない
+        let mut is_batch_pushed = false;
+        for k in 0..self.candidate_literals.len() {
+            let literal = self.candidate_literals[k];
+            if engine.is_true(literal) {
+                continue;
+            }
+            if engine.is_false(literal) {
+                // 伝播の途中で偽になったので矛盾
+                self.conflicting_literals.clear();
+                self.conflicting_literals.extend(self.explanation_literals[start..end].iter().cloned());
+                self.conflicting_literals.push(literal);
+                if !is_batch_pushed {
+                    self.explanation_literals.truncate(start);
+                }
+                return PropagationResult::Conflict { explain_key: CardinalityExplainKey::Conflict.into() };
+            }
+            if !is_batch_pushed {
+                undo_log.push(CardinalityUndoEntry::Explanation { start: start });
+                is_batch_pushed = true;
+            }
+            self.summary.number_of_cardinality_propagations += 1;
+            let explain_key = CardinalityExplainKey::Propagation { literal: literal, start: start, end: end };
+            let result = engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        if !is_batch_pushed {
+            self.explanation_literals.truncate(start);
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+impl TheoryTrait for CardinalityTheory {
+    type ExplainKey = CardinalityExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = CardinalityTheorySummary;
+    type UndoEntry = CardinalityUndoEntry;
+
+    fn add_variable(&mut self) {
+        self.occurrences.push(Array::default());
+        self.occurrences.push(Array::default());
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        let falsified_literal = !assigned_literal;
+        // 後から加えた制約も偽のリテラルを数えるので，制約に現れないリテラルも記録する．
+        // 矛盾で途中で戻っても undo と整合するように，先に全ての計数を更新する
+        undo_log.push(CardinalityUndoEntry::Falsified { literal: falsified_literal });
+        for &row_id in self.occurrences[falsified_literal.bits()].iter() {
+            self.rows[row_id].number_of_falses += 1;
+        }
+        for k in 0..self.occurrences[falsified_literal.bits()].len() {
+            let row_id = self.occurrences[falsified_literal.bits()][k];
+            let row = &self.rows[row_id];
+            if row.number_of_falses + row.bound >= row.literals.len() {
+                let result = self.propagate(row_id, engine, undo_log);
+                if result.is_conflict() {
+                    return result;
+                }
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn explain_propagation(&self, explain_key: CardinalityExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            CardinalityExplainKey::Propagation { literal, start, end } => ClauseView::new(Either::Left(
+                [literal].into_iter().chain(self.explanation_literals[start..end].iter().cloned()),
+            )),
+            CardinalityExplainKey::Conflict => {
+                ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned()))
+            }
+        };
+    }
+
+    fn undo(&mut self, entry: CardinalityUndoEntry) {
+        match entry {
+            CardinalityUndoEntry::Falsified { literal } => {
+                for &row_id in self.occurrences[literal.bits()].iter() {
+                    self.rows[row_id].number_of_falses -= 1;
+                }
+            }
+            CardinalityUndoEntry::Explanation { start } => self.explanation_literals.truncate(start),
+        }
+    }
+
+    /// 変数が基数制約に現れるか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return !self.occurrences[2 * index].is_empty() || !self.occurrences[2 * index + 1].is_empty();
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<CardinalityConstraint> for CardinalityTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        constraint: CardinalityConstraint,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(
+            constraint
+                .literals
+                .iter()
+                .all(|l| constraint.literals.iter().filter(|m| m.index() == l.index()).count() == 1)
+        );
+        if constraint.bound == 0 {
+            // 常に充足される
+            return PropagationResult::Noconflict;
+        }
+        let row_id = self.rows.len();
+        for literal in constraint.literals.iter() {
+            self.occurrences[literal.bits()].push(row_id);
+        }
+        let number_of_falses = constraint.literals.iter().filter(|&&l| engine.is_false(l)).count() as u32;
+        self.rows.push(Row {
+            literals: constraint.literals,
+            bound: constraint.bound,
+            number_of_falses: number_of_falses,
+        });
+        self.summary.number_of_cardinality_constraints += 1;
+        let row = &self.rows[row_id];
+        if row.number_of_falses + row.bound >= row.literals.len() {
+            return self.propagate(row_id, engine, undo_log);
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+    use utility::Array;
+
+    use super::{CardinalityConstraint, CardinalityExplainKey, CardinalityTheory};
+    use crate::theory_test::{
+        TestedExplainKey, TestedTheory, add_constraint, assert_conflict_is_valid, assert_explanations_are_valid,
+        assignment_bits, check_random_search, conflict_clause, enumerate_bits, holds_in_bits, literal, make_engine,
+        reason_clause, sorted,
+    };
+
+    impl TestedExplainKey for CardinalityExplainKey {}
+
+    impl TestedTheory for CardinalityTheory {
+        type Solution = u32;
+
+        fn holds(&self, literal: Literal, bits: &u32) -> bool {
+            return holds_in_bits(literal, *bits);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> u32 {
+            return assignment_bits(engine);
+        }
+
+        fn checkpoint(&self) -> u32 {
+            return self.explanation_literals.len();
+        }
+
+        /// 各制約の number_of_falses がエンジンの割り当てと一致する
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for row in self.rows.iter() {
+                let number_of_falses = row.literals.iter().filter(|&&literal| engine.is_false(literal)).count() as u32;
+                assert_eq!(row.number_of_falses, number_of_falses);
+            }
+        }
+    }
+
+    fn enumerate_solutions(number_of_variables: u32, constraints: &[CardinalityConstraint]) -> Vec<u32> {
+        return enumerate_bits(number_of_variables, |bits| {
+            constraints.iter().all(|constraint| {
+                constraint.literals.iter().filter(|&&literal| holds_in_bits(literal, bits)).count() as u32
+                    >= constraint.bound
+            })
+        });
+    }
+
+    #[test]
+    fn test_at_least_two_of_four_propagates_remaining_literals() {
+        // x0 + x1 + !x2 + x3 >= 2
+        let constraint = CardinalityConstraint::new(
+            [literal(0, true), literal(1, true), literal(2, false), literal(3, true)].into_iter(),
+            2,
+        );
+        let solutions = enumerate_solutions(4, std::slice::from_ref(&constraint));
+        let mut engine = make_engine(CardinalityTheory::default(), 4);
+        assert!(add_constraint(&mut engine, constraint).is_no_conflict());
+        assert_eq!(engine.number_of_assigneds(), 0);
+        assert!(engine.assign(literal(0, false), Reason::Decision).is_no_conflict());
+        assert_eq!(engine.number_of_assigneds(), 1);
+        // 偽のリテラルが 2 つになると残りの 2 つが真になる
+        assert!(engine.assign(literal(2, true), Reason::Decision).is_no_conflict());
+        assert!(engine.is_true(literal(1, true)) && engine.is_true(literal(3, true)));
+        let expected = sorted(vec![literal(0, true), literal(1, true), literal(2, false)]);
+        assert!(sorted(reason_clause(&mut engine, literal(1, true)).unwrap()) == expected);
+        assert_explanations_are_valid(&mut engine, &solutions);
+        // 戻すと伝播と説明も取り消される
+        let _ = engine.backjump(1).count();
+        assert!(!engine.is_assigned(1) && !engine.is_assigned(3));
+        assert_eq!(engine.theory().explanation_literals.len(), 0);
+        engine.theory().assert_consistent(&engine);
+    }
+
+    #[test]
+    fn test_conflict_when_too_many_literals_are_false() {
+        // x0 + x1 + x2 >= 2 に x0 = x1 = 0 を加えると矛盾する
+        let mut engine = make_engine(CardinalityTheory::default(), 3);
+        assert!(engine.assign(literal(0, false), Reason::Decision).is_no_conflict());
+        assert!(engine.assign(literal(1, false), Reason::Decision).is_no_conflict());
+        let constraint =
+            CardinalityConstraint::new([literal(0, true), literal(1, true), literal(2, true)].into_iter(), 2);
+        let solutions = enumerate_solutions(3, std::slice::from_ref(&constraint));
+        let PropagationResult::Conflict { explain_key } = add_constraint(&mut engine, constraint) else {
+            panic!("expected a conflict");
+        };
+        assert_conflict_is_valid(&engine, explain_key, &solutions);
+        assert!(sorted(conflict_clause(&engine, explain_key)) == vec![literal(0, true), literal(1, true)]);
+    }
+
+    fn random_constraint(rng: &mut SmallRng, number_of_variables: u32) -> CardinalityConstraint {
+        let mut indices = Array::from_iter(0..number_of_variables);
+        for k in 0..indices.len() {
+            indices.swap(k, rng.gen_range(k..indices.len()));
+        }
+        indices.truncate(rng.gen_range(1..(number_of_variables + 1)));
+        let bound = rng.gen_range(1..(indices.len() + 1));
+        return CardinalityConstraint::new(indices.into_iter().map(|index| literal(index, rng.gen_bool(0.5))), bound);
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(17);
+        for _ in 0..500 {
+            let number_of_variables = rng.gen_range(2..8);
+            let constraints =
+                Vec::from_iter((0..rng.gen_range(1..5)).map(|_| random_constraint(&mut rng, number_of_variables)));
+            let solutions = enumerate_solutions(number_of_variables, &constraints);
+            let mut engine = make_engine(CardinalityTheory::default(), number_of_variables);
+            let mut result = PropagationResult::Noconflict;
+            for constraint in constraints.iter() {
+                result = add_constraint(&mut engine, constraint.clone());
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use cdcl_engine::Literal;
+use utility::Array;
+
+/// 前処理用の節データベース
+///
+/// 出現リストは節の追加・削除・縮小のたびに更新するので，常に生きている節だけを含む．
+#[derive(Default, Clone)]
+pub struct ClauseDatabase {
+    clauses: Array<u32, Array<u32, Literal>>,
+    is_removed: Array<u32, bool>,
+    /// リテラル(bits) -> そのリテラルを含む節の番号
+    occurrences: Array<u32, Array<u32, u32>>,
+}
+
+impl ClauseDatabase {
+    pub fn new(number_of_variables: u32) -> Self {
+        let mut occurrences = Array::default();
+        occurrences.resize_with(2 * number_of_variables, Array::default);
+        Self { clauses: Array::default(), is_removed: Array::default(), occurrences: occurrences }
+    }
+
+    /// リテラルを整列して重複を除く．恒真な節であれば None を返す
+    pub fn normalize(literals: impl Iterator<Item = Literal>) -> Option<Array<u32, Literal>> {
+        let mut literals = Array::<u32, Literal>::from_iter(literals);
+        literals.sort_unstable_by_key(|l| l.bits());
+        let mut normalized_literals = Array::default();
+        for &literal in literals.iter() {
+            match normalized_literals.last() {
+                Some(&last) if last == literal => continue,
+                Some(&last) if last == !literal => return None,
+                _ => {
+                    normalized_literals.push(literal);
+                }
+            }
+        }
+        return Some(normalized_literals);
+    }
+
+    #[inline(always)]
+    pub fn number_of_variables(&self) -> u32 {
+        return self.occurrences.len() / 2;
+    }
+
+    /// 正規化された(重複・相補なリテラルを含まない)節を追加して節番号を返す
+    pub fn add_clause(&mut self, literals: impl Iterator<Item = Literal>) -> u32 {
+        let clause_id = self.clauses.len();
+        let literals = Array::from_iter(literals);
+        for literal in literals.iter() {
+            self.occurrences[literal.bits()].push(clause_id);
+        }
+        self.clauses.push(literals);
+        self.is_removed.push(false);
+        return clause_id;
+    }
+
+    pub fn remove_clause(&mut self, clause_id: u32) {
+        debug_assert!(!self.is_removed[clause_id]);
+        for k in 0..self.clauses[clause_id].len() {
+            let literal = self.clauses[clause_id][k];
+            Self::remove_occurrence(&mut self.occurrences[literal.bits()], clause_id);
+        }
+        self.clauses[clause_id].clear();
+        self.is_removed[clause_id] = true;
+    }
+
+    /// 節から literal を取り除く
+    pub fn strengthen_clause(&mut self, clause_id: u32, literal: Literal) {
+        debug_assert!(!self.is_removed[clause_id]);
+        let clause = &mut self.clauses[clause_id];
+        let position = clause.iter().position(|&l| l == literal).unwrap();
+        clause.swap_remove(position as u32);
+        Self::remove_occurrence(&mut self.occurrences[literal.bits()], clause_id);
+    }
+
+    #[inline(always)]
+    pub fn is_removed(&self, clause_id: u32) -> bool {
+        return self.is_removed[clause_id];
+    }
+
+    #[inline(always)]
+    pub fn clause(&self, clause_id: u32) -> &[Literal] {
+        return &self.clauses[clause_id][..];
+    }
+
+    pub fn clause_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        return (0..self.clauses.len()).filter(|&clause_id| !self.is_removed[clause_id]);
+    }
+
+    #[inline(always)]
+    pub fn occurrences(&self, literal: Literal) -> &[u32] {
+        return &self.occurrences[literal.bits()][..];
+    }
+
+    fn remove_occurrence(occurrences: &mut Array<u32, u32>, clause_id: u32) {
+        let position = occurrences.iter().position(|&c| c == clause_id).unwrap();
+        occurrences.swap_remove(position as u32);
+    }
+}
@@ -1,6 +1,8 @@
 use cdcl_engine::{
-    EngineTrait, Literal, LiteralArray, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait,
+    DerivedClauses, EngineTrait, Literal, LiteralArray, ProgressColumnsTrait, PropagationResult, Reason,
+    TheoryAddConstraintTrait, TheoryTrait, UndoLog,
 };
+use either::Either;
 use utility::Array;
 
 use super::calculate_lbd::CalculatePLBD;
@@ -39,6 +41,26 @@ where
     }
 }
 
+impl<LeftT, RightT> ClauseTrait for Either<LeftT, RightT>
+where
+    LeftT: ClauseTrait,
+    RightT: ClauseTrait,
+{
+    fn len(&self) -> u32 {
+        match self {
+            Either::Left(clause) => clause.len(),
+            Either::Right(clause) => clause.len(),
+        }
+    }
+
+    fn iter_literals(&self) -> impl Iterator<Item = Literal> + '_ {
+        match self {
+            Either::Left(clause) => Either::Left(clause.iter_literals()),
+            Either::Right(clause) => Either::Right(clause.iter_literals()),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct ClauseExplainKey {
     row_id: u32,
@@ -50,6 +72,14 @@ pub struct ClauseTheorySummary {
     pub number_of_learnt_clauses: u32,
 }
 
+impl ProgressColumnsTrait for ClauseTheorySummary {
+    const HEADERS: &'static [&'static str] = &["CLAUSE", "LEARNT_CLAUSE"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_clauses, self.number_of_learnt_clauses].into_iter();
+    }
+}
+
 #[derive(Clone)]
 struct Row {
     literals: Array<u32, Literal>,
@@ -74,6 +104,8 @@ pub struct ClauseTheory {
     time: usize,
     activity_increase_value: f64,
     // last_reduction_time_stamp: usize,
+    /// simplify_at_root を次に始める行番号
+    simplification_row_id: u32,
     summary: ClauseTheorySummary,
 }
 
@@ -87,15 +119,34 @@ impl ClauseTheory {
             time: 0,
             activity_increase_value: 1.0,
             // last_reduction_time_stamp: 0,
+            simplification_row_id: 0,
             summary: ClauseTheorySummary::default(),
         }
     }
+
+    /// 削除されていない節を (行番号, リテラル, 学習節か否か) で列挙する
+    pub fn iter_clauses(&self) -> impl Iterator<Item = (u32, &[Literal], bool)> + '_ {
+        return (0..self.rows.len())
+            .filter(|&row_id| !self.rows[row_id].is_deleted)
+            .map(|row_id| (row_id, &self.rows[row_id].literals[..], self.rows[row_id].is_learnt));
+    }
+
+    pub fn remove_clause(&mut self, row_id: u32) {
+        let row = &mut self.rows[row_id];
+        debug_assert!(!row.is_deleted);
+        row.is_deleted = true;
+        self.summary.number_of_clauses -= 1;
+        if row.is_learnt {
+            self.summary.number_of_learnt_clauses -= 1;
+        }
+    }
 }
 
 impl TheoryTrait for ClauseTheory {
     type ExplainKey = ClauseExplainKey;
     type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
     type Summary = ClauseTheorySummary;
+    type UndoEntry = ();
 
     fn add_variable(&mut self) {
         self.watches.push([Array::default(), Array::default()]);
@@ -105,6 +156,7 @@ impl TheoryTrait for ClauseTheory {
         &mut self,
         assigned_literal: Literal,
         engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> cdcl_engine::PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT: cdcl_engine::EngineTrait,
@@ -150,10 +202,25 @@ impl TheoryTrait for ClauseTheory {
                         row.plbd = u32::min(row.plbd, plbd);
                     }
                     row.activity += self.activity_increase_value;
+                    // chronological backtracking で積み直した低い決定レベルのリテラルであれば，
+                    // 他のリテラルの決定レベルの最大値で伝播する
+                    let decision_level =
+                        if engine.get_decision_level(assigned_literal.index()) == engine.current_decision_level() {
+                            engine.current_decision_level()
+                        } else {
+                            row.literals
+                                .iter()
+                                .filter(|&&l| l != another_watched_literal)
+                                .map(|&l| engine.get_decision_level(l.index()))
+                                .max()
+                                .unwrap()
+                        };
                     // もう一方の監視リテラルに真を割り当て
-                    let inner_result = engine.assign(another_watched_literal, Reason::Propagation {
-                        explain_key: ClauseExplainKey { row_id: watch.row_id }.into(),
-                    });
+                    let inner_result = engine.assign_at_level(
+                        another_watched_literal,
+                        Reason::Propagation { explain_key: ClauseExplainKey { row_id: watch.row_id }.into() },
+                        decision_level,
+                    );
                     if inner_result.is_conflict() {
                         return inner_result;
                     }
@@ -169,15 +236,71 @@ impl TheoryTrait for ClauseTheory {
         return PropagationResult::Noconflict;
     }
 
-    fn explain(&self, explain_key: ClauseExplainKey) -> Self::ExplanationConstraint<'_> {
+    fn explain_propagation(&self, explain_key: ClauseExplainKey) -> Self::ExplanationConstraint<'_> {
         return ClauseView::new(self.rows[explain_key.row_id].literals.iter().cloned());
     }
 
-    fn unassign(&mut self, _unassigned_literals: impl Iterator<Item = Literal>) {
+    fn undo(&mut self, _entry: ()) {
+        // 状態を記録しないのでなにもしない
+    }
+
+    fn backjump(&mut self, _backjump_level: u32) {
         self.activity_increase_value /= 1.0 - 1.0 / self.activity_time_constant;
         self.time += 1;
     }
 
+    fn simplify_at_root<EngineT: EngineTrait>(
+        &mut self,
+        effort: usize,
+        engine: &EngineT,
+        derived_clauses: &mut DerivedClauses,
+    ) -> u32 {
+        let mut number_of_removed_clauses = 0;
+        let mut ticks = 0;
+        // 前回打ち切った行から順に一巡する
+        for _ in 0..self.rows.len() {
+            if ticks >= effort {
+                break;
+            }
+            if self.simplification_row_id >= self.rows.len() {
+                self.simplification_row_id = 0;
+            }
+            let row_id = self.simplification_row_id;
+            self.simplification_row_id += 1;
+            let row = &mut self.rows[row_id];
+            if row.is_deleted {
+                continue;
+            }
+            ticks += row.literals.len() as usize;
+            if row.literals.iter().any(|&l| engine.is_true(l)) {
+                // 根で充足された節は削除する
+                self.remove_clause(row_id);
+                number_of_removed_clauses += 1;
+                continue;
+            }
+            if engine.is_false(row.literals[0]) || engine.is_false(row.literals[1]) {
+                // 伝播し終えていなければ監視リテラルが偽のことがあるので触らない
+                continue;
+            }
+            // 監視していない偽のリテラルを取り除く
+            let mut position = 2;
+            while position < row.literals.len() {
+                if engine.is_false(row.literals[position]) {
+                    row.literals.swap_remove(position);
+                } else {
+                    position += 1;
+                }
+            }
+            if row.literals.len() == 2 && !row.is_learnt {
+                // 2 リテラルの節になれば内側の層に移す
+                derived_clauses.binary_clauses.push([row.literals[0], row.literals[1]]);
+                self.remove_clause(row_id);
+                number_of_removed_clauses += 1;
+            }
+        }
+        return number_of_removed_clauses;
+    }
+
     fn reduce_constraints(&mut self) {
         for row in self.rows.iter_mut() {
             row.activity /= self.activity_increase_value;
@@ -210,6 +333,7 @@ where
         clause: ClauseT,
         is_learnt: bool,
         engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
     ) -> PropagationResult<EngineT::CompositeExplainKey>
     where
         EngineT::CompositeExplainKey: From<Self::ExplainKey>,
@@ -217,13 +341,18 @@ where
         debug_assert!(clause.len() >= 2);
 
         let mut literals = Array::from_iter(clause.iter_literals());
+        // 偽のリテラルは決定レベルの高い順に並べ，バックジャンプで最初に取り消されるものを監視する
         literals.sort_unstable_by_key(|&l| {
             if engine.is_true(l) {
-                (0, engine.get_assignment_order(l.index()))
+                (0, engine.get_assignment_order(l.index()), 0)
             } else if !engine.is_assigned(l.index()) {
-                (1, 0)
+                (1, 0, 0)
             } else {
-                (2, u32::MAX - engine.get_assignment_order(l.index()))
+                (
+                    2,
+                    u32::MAX - engine.get_decision_level(l.index()),
+                    u32::MAX - engine.get_assignment_order(l.index()),
+                )
             }
         });
         debug_assert!(!engine.is_false(literals[0]));
@@ -251,10 +380,12 @@ where
         let row = self.rows.last().unwrap();
 
         if !engine.is_assigned(row.literals[0].index()) && engine.is_false(row.literals[1]) {
-            debug_assert!(engine.get_decision_level(row.literals[1].index()) == engine.current_decision_level());
-            return engine.assign(row.literals[0], Reason::Propagation {
-                explain_key: ClauseExplainKey { row_id: row_id }.into(),
-            });
+            // chronological backtracking の後は現在の決定レベルより低い決定レベルで伝播することがある
+            return engine.assign_at_level(
+                row.literals[0],
+                Reason::Propagation { explain_key: ClauseExplainKey { row_id: row_id }.into() },
+                engine.get_decision_level(row.literals[1].index()),
+            );
         } else {
             return PropagationResult::Noconflict;
         }
@@ -1,7 +1,52 @@
 use cdcl_engine::Literal;
 use utility::Array;
 
+use crate::{
+    acyclicity_theory::AcyclicityEdge,
+    cardinality_theory::CardinalityConstraint,
+    difference_logic_theory::DifferenceAtom,
+    integer_theory::{IntegerConstraint, OrderLiteral},
+    pseudo_boolean_theory::PseudoBooleanConstraint,
+    xor_theory::XorConstraint,
+};
+
 #[derive(Default)]
 pub struct CNF {
+    pub number_of_variables: u32,
     pub clauses: Array<u32, Array<u32, Literal>>,
+    pub cardinality_constraints: Array<u32, CardinalityConstraint>,
+    pub pseudo_boolean_constraints: Array<u32, PseudoBooleanConstraint>,
+    pub xor_constraints: Array<u32, XorConstraint>,
+    pub acyclicity_edges: Array<u32, AcyclicityEdge>,
+    pub difference_atoms: Array<u32, DifferenceAtom>,
+    /// 整数変数 -> (下限, 上限)
+    pub integer_variables: Array<u32, (i64, i64)>,
+    pub order_literals: Array<u32, OrderLiteral>,
+    pub integer_constraints: Array<u32, IntegerConstraint>,
+}
+
+impl CNF {
+    /// 節と基数制約以外の理論の制約を含むか
+    ///
+    /// 対称性の検出は係数・偶奇・グラフ・整数変数をグラフで表さないので，これらを含む場合は検出しない
+    pub fn has_non_cardinality_theory_constraints(&self) -> bool {
+        return !self.pseudo_boolean_constraints.is_empty()
+            || !self.xor_constraints.is_empty()
+            || !self.acyclicity_edges.is_empty()
+            || !self.difference_atoms.is_empty()
+            || !self.integer_variables.is_empty();
+    }
+
+    /// 理論が割り当てを観測する変数
+    ///
+    /// 節だけを見る前処理はこれらの変数を消去・置換してはならず，決定レベル 0 で固定されても単位節として残す．
+    /// 基数制約・擬似ブール制約・ XOR 制約の変数は決定レベル 0 で簡略化した制約から求めるので含めない
+    pub fn theory_variables(&self) -> impl Iterator<Item = u32> + '_ {
+        return self
+            .acyclicity_edges
+            .iter()
+            .map(|edge| edge.literal.index())
+            .chain(self.difference_atoms.iter().map(|atom| atom.literal.index()))
+            .chain(self.order_literals.iter().map(|order_literal| order_literal.literal.index()));
+    }
 }
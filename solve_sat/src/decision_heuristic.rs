@@ -0,0 +1,175 @@
+use cdcl_engine::EngineTrait;
+
+use crate::{learning_rate_branching::LearningRateBranching, pricer::Pricer, pricer2::Pricer as Pricer2};
+
+/// 次に決定する変数を選ぶ方法
+///
+/// 未割り当ての変数を優先度の高い順に取り出せるように管理し，矛盾の解析のたびに優先度を更新する．
+/// 実行時に切り替えられるように SearchDecisionHeuristic にまとめる．
+pub trait DecisionHeuristic {
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool);
+
+    fn set_to_unassigned(&mut self, index: u32);
+
+    /// 未割り当ての(はずの)変数のうち優先度が最も高いもの．割り当て済みであれば set_to_assigned してから呼び直す
+    fn peek(&self) -> Option<u32>;
+
+    /// 変数の優先度．peek は未割り当ての変数のうちこの値が最も大きいものを返す
+    fn activity(&self, index: u32) -> f64;
+
+    fn set_to_assigned(&mut self, index: u32);
+
+    /// 矛盾の解析の後，バックジャンプの前に呼び出す
+    ///
+    /// related_variables は解析に現れた変数，reason_side_variables は学習節のリテラルの割り当て理由にだけ現れた変数
+    fn increase_price(
+        &mut self,
+        related_variables: impl Iterator<Item = u32>,
+        reason_side_variables: impl Iterator<Item = u32>,
+        engine: &impl EngineTrait,
+        backjump_level: u32,
+    );
+
+    /// 矛盾の解析で reason_side_variables を求める必要があるか
+    fn is_reason_side_rate_used(&self) -> bool {
+        return false;
+    }
+}
+
+/// 実行時に選んだ決定ヒューリスティック
+#[derive(Clone)]
+pub enum SearchDecisionHeuristic {
+    Vsids(Pricer),
+    Adam(Pricer2),
+    LearningRateBranching(LearningRateBranching),
+}
+
+impl DecisionHeuristic for SearchDecisionHeuristic {
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
+        match self {
+            Self::Vsids(heuristic) => heuristic.add_variable(initial_activity, is_assigned),
+            Self::Adam(heuristic) => heuristic.add_variable(initial_activity, is_assigned),
+            Self::LearningRateBranching(heuristic) => heuristic.add_variable(initial_activity, is_assigned),
+        }
+    }
+
+    fn set_to_unassigned(&mut self, index: u32) {
+        match self {
+            Self::Vsids(heuristic) => heuristic.set_to_unassigned(index),
+            Self::Adam(heuristic) => heuristic.set_to_unassigned(index),
+            Self::LearningRateBranching(heuristic) => heuristic.set_to_unassigned(index),
+        }
+    }
+
+    fn peek(&self) -> Option<u32> {
+        return match self {
+            Self::Vsids(heuristic) => heuristic.peek(),
+            Self::Adam(heuristic) => heuristic.peek(),
+            Self::LearningRateBranching(heuristic) => heuristic.peek(),
+        };
+    }
+
+    fn activity(&self, index: u32) -> f64 {
+        return match self {
+            Self::Vsids(heuristic) => heuristic.activity(index),
+            Self::Adam(heuristic) => heuristic.activity(index),
+            Self::LearningRateBranching(heuristic) => heuristic.activity(index),
+        };
+    }
+
+    fn set_to_assigned(&mut self, index: u32) {
+        match self {
+            Self::Vsids(heuristic) => heuristic.set_to_assigned(index),
+            Self::Adam(heuristic) => heuristic.set_to_assigned(index),
+            Self::LearningRateBranching(heuristic) => heuristic.set_to_assigned(index),
+        }
+    }
+
+    fn increase_price(
+        &mut self,
+        related_variables: impl Iterator<Item = u32>,
+        reason_side_variables: impl Iterator<Item = u32>,
+        engine: &impl EngineTrait,
+        backjump_level: u32,
+    ) {
+        match self {
+            Self::Vsids(heuristic) => {
+                heuristic.increase_price(related_variables, reason_side_variables, engine, backjump_level)
+            }
+            Self::Adam(heuristic) => {
+                heuristic.increase_price(related_variables, reason_side_variables, engine, backjump_level)
+            }
+            Self::LearningRateBranching(heuristic) => {
+                heuristic.increase_price(related_variables, reason_side_variables, engine, backjump_level)
+            }
+        }
+    }
+
+    fn is_reason_side_rate_used(&self) -> bool {
+        return match self {
+            Self::Vsids(heuristic) => heuristic.is_reason_side_rate_used(),
+            Self::Adam(heuristic) => heuristic.is_reason_side_rate_used(),
+            Self::LearningRateBranching(heuristic) => heuristic.is_reason_side_rate_used(),
+        };
+    }
+}
+
+/// 値の大きい順に並べる HeapedMap の比較関数
+#[derive(Default, Clone, Copy)]
+pub struct CompareValues;
+
+impl<IndexT, ValueT> FnOnce<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
+where
+    ValueT: std::cmp::PartialOrd,
+{
+    type Output = std::cmp::Ordering;
+    extern "rust-call" fn call_once(
+        self,
+        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
+    ) -> Self::Output {
+        rhs.partial_cmp(lhs).unwrap()
+    }
+}
+
+impl<IndexT, ValueT> FnMut<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
+where
+    ValueT: std::cmp::PartialOrd,
+{
+    extern "rust-call" fn call_mut(
+        &mut self,
+        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
+    ) -> Self::Output {
+        rhs.partial_cmp(lhs).unwrap()
+    }
+}
+
+impl<IndexT, ValueT> Fn<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
+where
+    ValueT: std::cmp::PartialOrd,
+{
+    extern "rust-call" fn call(&self, ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT))) -> Self::Output {
+        rhs.partial_cmp(lhs).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use utility::HeapedMap;
+
+    use super::CompareValues;
+
+    /// CompareValues で並べた HeapedMap が値の大きい順に取り出せることを確かめる
+    #[test]
+    fn test_compare_values_orders_by_descending_value() {
+        let mut queue = HeapedMap::<u32, f64, CompareValues>::default();
+        queue.insert(0, 1.0);
+        queue.insert(1, 3.0);
+        queue.insert(2, 2.0);
+        let mut order = Vec::new();
+        while let Some((&index, _)) = queue.first() {
+            order.push(index);
+            queue.remove(index);
+        }
+        assert_eq!(order, [1, 2, 0]);
+    }
+}
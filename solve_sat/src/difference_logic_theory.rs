@@ -0,0 +1,653 @@
+use cdcl_engine::{
+    EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait,
+    UndoLog,
+};
+use either::Either;
+use utility::{Array, HeapedMap};
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// literal が真であることと整数変数について x - y <= bound であることが同値であるという宣言
+#[derive(Clone, Copy)]
+pub struct DifferenceAtom {
+    pub x: u32,
+    pub y: u32,
+    pub bound: i64,
+    pub literal: Literal,
+}
+
+#[derive(Clone, Copy)]
+pub enum DifferenceLogicExplainKey {
+    /// literal と explanation_literals[start..end] (偽のリテラル)からなる節
+    Propagation { literal: Literal, start: u32, end: u32 },
+    /// conflicting_literals からなる節
+    Conflict,
+}
+
+/// バックジャンプで戻す状態の変更
+#[derive(Clone, Copy)]
+pub enum DifferenceLogicUndoEntry {
+    /// literal が真になったことを処理し，literal が真のとき有効になる辺を有効にした
+    Processed { literal: Literal },
+    /// explanation_literals[start..] に伝播の説明を積んだ
+    Explanation { start: u32 },
+}
+
+#[derive(Default, Clone)]
+pub struct DifferenceLogicTheorySummary {
+    pub number_of_difference_atoms: u32,
+    pub number_of_difference_logic_propagations: u32,
+}
+
+impl ProgressColumnsTrait for DifferenceLogicTheorySummary {
+    const HEADERS: &'static [&'static str] = &["DIFFERENCE_LOGIC_PROPAGATION"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_difference_logic_propagations].into_iter();
+    }
+}
+
+/// literal が真のとき有効になる制約 value(to) <= value(from) + weight
+#[derive(Clone, Copy)]
+struct Edge {
+    from: u32,
+    to: u32,
+    weight: i64,
+    literal: Literal,
+}
+
+#[derive(Default, Clone, Copy)]
+struct CompareDistances;
+
+impl<IndexT> FnOnce<(&(IndexT, i64), &(IndexT, i64))> for CompareDistances {
+    type Output = std::cmp::Ordering;
+    extern "rust-call" fn call_once(self, ((_, lhs), (_, rhs)): (&(IndexT, i64), &(IndexT, i64))) -> Self::Output {
+        lhs.cmp(rhs)
+    }
+}
+
+impl<IndexT> FnMut<(&(IndexT, i64), &(IndexT, i64))> for CompareDistances {
+    extern "rust-call" fn call_mut(&mut self, ((_, lhs), (_, rhs)): (&(IndexT, i64), &(IndexT, i64))) -> Self::Output {
+        lhs.cmp(rhs)
+    }
+}
+
+impl<IndexT> Fn<(&(IndexT, i64), &(IndexT, i64))> for CompareDistances {
+    extern "rust-call" fn call(&self, ((_, lhs), (_, rhs)): (&(IndexT, i64), &(IndexT, i64))) -> Self::Output {
+        lhs.cmp(rhs)
+    }
+}
+
+/// 最短路の探索の結果．距離は values を潜在関数とする被約費用で測る
+#[derive(Default, Clone)]
+struct Search {
+    /// 整数変数 -> 距離が確定した印
+    stamps: Array<u32, u32>,
+    stamp: u32,
+    distances: Array<u32, i64>,
+    /// 整数変数 -> 探索でその整数変数に至った辺(始点は NULL)
+    parent_edges: Array<u32, u32>,
+    /// 距離が確定した整数変数
+    nodes: Array<u32, u32>,
+}
+
+impl Search {
+    fn contains(&self, node: u32) -> bool {
+        return self.stamps[node] == self.stamp;
+    }
+}
+
+/// 差分論理の理論
+///
+/// 真になったリテラルのうちこの理論が処理済みのものに対応する制約を辺とするグラフを保ち，
+/// 全ての有効な制約を満たす整数変数の値 values を Cotton と Maler の方法で差分的に修復する．
+/// 修復中に新しい辺の始点の値が減れば負閉路があるので矛盾とし，閉路上の辺のリテラルで説明する．
+/// 辺を加えた後は，その辺を通る最短路で含意される未割り当ての原子を伝播する．
+/// 辺を取り除いても values は有効な制約を満たし続けるので，バックジャンプでは辺を無効にするだけでよい．
+#[derive(Default, Clone)]
+pub struct DifferenceLogicTheory {
+    edges: Array<u32, Edge>,
+    is_active: Array<u32, bool>,
+    /// 整数変数 -> その整数変数から出る辺
+    outgoing_edges: Array<u32, Array<u32, u32>>,
+    /// 整数変数 -> その整数変数に入る辺
+    incoming_edges: Array<u32, Array<u32, u32>>,
+    /// リテラル(bits) -> そのリテラルが真のとき有効になる辺
+    edges_of_literal: Array<u32, Array<u32, u32>>,
+    /// リテラル(bits) -> この理論が真になったことを処理済みか否か
+    is_processed_true: Array<u32, bool>,
+    values: Array<u32, i64>,
+    /// 修復で書き換える前の値
+    previous_values: Array<u32, (u32, i64)>,
+    queue: HeapedMap<u32, i64, CompareDistances>,
+    forward_search: Search,
+    backward_search: Search,
+    explanation_literals: Array<u32, Literal>,
+    conflicting_literals: Array<u32, Literal>,
+    summary: DifferenceLogicTheorySummary,
+}
+
+impl DifferenceLogicTheory {
+    const NULL: u32 = u32::MAX;
+
+    pub fn number_of_integer_variables(&self) -> u32 {
+        return self.values.len();
+    }
+
+    /// 有効な制約を全て満たす整数変数の値
+    pub fn value(&self, integer_variable: u32) -> i64 {
+        return self.values[integer_variable];
+    }
+
+    fn add_integer_variable(&mut self, integer_variable: u32) {
+        while self.values.len() <= integer_variable {
+            self.values.push(0);
+            self.outgoing_edges.push(Array::default());
+            self.incoming_edges.push(Array::default());
+            for search in [&mut self.forward_search, &mut self.backward_search] {
+                search.stamps.push(0);
+                search.distances.push(0);
+                search.parent_edges.push(Self::NULL);
+            }
+        }
+    }
+
+    fn add_edge(&mut self, edge: Edge) -> u32 {
+        let edge_id = self.edges.len();
+        self.edges.push(edge);
+        self.is_active.push(false);
+        self.outgoing_edges[edge.from].push(edge_id);
+        self.incoming_edges[edge.to].push(edge_id);
+        self.edges_of_literal[edge.literal.bits()].push(edge_id);
+        return edge_id;
+    }
+
+    /// 有効な辺を is_forward であれば順向きに，そうでなければ逆向きにたどって start からの最短距離を求める．
+    /// target の距離が確定すれば打ち切って true を返す
+    fn search(&mut self, start: u32, target: u32, is_forward: bool) -> bool {
+        let search = if is_forward { &mut self.forward_search } else { &mut self.backward_search };
+        search.stamp += 1;
+        search.nodes.clear();
+        search.distances[start] = 0;
+        search.parent_edges[start] = Self::NULL;
+        self.queue.clear();
+        self.queue.insert(start, 0);
+        while let Some((node, distance)) = self.queue.pop_first() {
+            search.stamps[node] = search.stamp;
+            search.nodes.push(node);
+            if node == target {
+                return true;
+            }
+            let adjacent_edges = if is_forward { &self.outgoing_edges[node] } else { &self.incoming_edges[node] };
+            for &edge_id in adjacent_edges.iter() {
+                if !self.is_active[edge_id] {
+                    continue;
+                }
+                let edge = &self.edges[edge_id];
+                let next = if is_forward { edge.to } else { edge.from };
+                if search.stamps[next] == search.stamp {
+                    continue;
+                }
+                // 被約費用は values が有効な制約を満たすので非負になる
+                let reduced_weight = self.values[edge.from] + edge.weight - self.values[edge.to];
+                debug_assert!(reduced_weight >= 0);
+                let next_distance = distance + reduced_weight;
+                if self.queue.get(next).is_none_or(|&d| next_distance < d) {
+                    search.distances[next] = next_distance;
+                    search.parent_edges[next] = edge_id;
+                    self.queue.insert(next, next_distance);
+                }
+            }
+        }
+        return false;
+    }
+
+    /// 探索で node に至った辺のリテラルの否定を literals に積む
+    fn push_path_literals(
+        edges: &Array<u32, Edge>,
+        search: &Search,
+        mut node: u32,
+        is_forward: bool,
+        literals: &mut Array<u32, Literal>,
+    ) {
+        while search.parent_edges[node] != Self::NULL {
+            let edge = &edges[search.parent_edges[node]];
+            literals.push(!edge.literal);
+            node = if is_forward { edge.from } else { edge.to };
+        }
+    }
+
+    /// 辺 edge_id を有効にしたときに values を修復する．負閉路ができれば values を元に戻して conflicting_literals に説明を積み，
+    /// false を返す
+    fn repair_values(&mut self, edge_id: u32) -> bool {
+        let Edge { from, to, weight, .. } = self.edges[edge_id];
+        if self.values[from] + weight >= self.values[to] {
+            return true;
+        }
+        // forward_search を修復に使い，距離は値の減少量とする
+        let search = &mut self.forward_search;
+        search.stamp += 1;
+        search.parent_edges[to] = edge_id;
+        self.previous_values.clear();
+        self.queue.clear();
+        self.queue.insert(to, self.values[from] + weight - self.values[to]);
+        while let Some((node, delta)) = self.queue.pop_first() {
+            if node == from {
+                // from -> to -> ... -> from が負閉路になる
+                for &(node, value) in self.previous_values.iter().rev() {
+                    self.values[node] = value;
+                }
+                self.conflicting_literals.clear();
+                let mut node = from;
+                loop {
+                    let parent_edge_id = search.parent_edges[node];
+                    self.conflicting_literals.push(!self.edges[parent_edge_id].literal);
+                    if parent_edge_id == edge_id {
+                        break;
+                    }
+                    node = self.edges[parent_edge_id].from;
+                }
+                return false;
+            }
+            search.stamps[node] = search.stamp;
+            self.previous_values.push((node, self.values[node]));
+            self.values[node] += delta;
+            for &next_edge_id in self.outgoing_edges[node].iter() {
+                if !self.is_active[next_edge_id] {
+                    continue;
+                }
+                let next_edge = &self.edges[next_edge_id];
+                if search.stamps[next_edge.to] == search.stamp {
+                    continue;
+                }
+                let next_delta = self.values[node] + next_edge.weight - self.values[next_edge.to];
+                if next_delta < 0 && self.queue.get(next_edge.to).is_none_or(|&d| next_delta < d) {
+                    search.parent_edges[next_edge.to] = next_edge_id;
+                    self.queue.insert(next_edge.to, next_delta);
+                }
+            }
+        }
+        return true;
+    }
+
+    /// 伝播の説明の区間を開始する
+    fn begin_explanation(&mut self, undo_log: &mut UndoLog<DifferenceLogicUndoEntry>) -> u32 {
+        let start = self.explanation_literals.len();
+        undo_log.push(DifferenceLogicUndoEntry::Explanation { start: start });
+        self.summary.number_of_difference_logic_propagations += 1;
+        return start;
+    }
+
+    /// 辺を有効にし，負閉路ができれば矛盾とし，できなければその辺を通る最短路で含意される原子を伝播する
+    fn activate_edge<EngineT>(
+        &mut self,
+        edge_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<DifferenceLogicUndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<DifferenceLogicExplainKey>,
+    {
+        if !self.repair_values(edge_id) {
+            return PropagationResult::Conflict { explain_key: DifferenceLogicExplainKey::Conflict.into() };
+        }
+        self.is_active[edge_id] = true;
+        let Edge { from, to, weight, literal } = self.edges[edge_id];
+        self.search(to, Self::NULL, true);
+        self.search(from, Self::NULL, false);
+        for k in 0..self.forward_search.nodes.len() {
+            let node = self.forward_search.nodes[k];
+            // from から node までの最短距離
+            let distance_to_node = weight + self.forward_search.distances[node] + self.values[node] - self.values[to];
+            for l in 0..self.incoming_edges[node].len() {
+                let other_edge_id = self.incoming_edges[node][l];
+                let other_edge = self.edges[other_edge_id];
+                if engine.is_assigned(other_edge.literal.index()) || !self.backward_search.contains(other_edge.from) {
+                    continue;
+                }
+                let distance = self.backward_search.distances[other_edge.from] + self.values[from]
+                    - self.values[other_edge.from]
+                    + distance_to_node;
+                if distance > other_edge.weight {
+                    continue;
+                }
+                // other_edge.from -> ... -> from -> to -> ... -> node の制約から other_edge の制約が従う
+                let start = self.begin_explanation(undo_log);
+                self.explanation_literals.push(!literal);
+                Self::push_path_literals(&self.edges, &self.forward_search, node, true, &mut self.explanation_literals);
+                Self::push_path_literals(
+                    &self.edges,
+                    &self.backward_search,
+                    other_edge.from,
+                    false,
+                    &mut self.explanation_literals,
+                );
+                let end = self.explanation_literals.len();
+                let explain_key =
+                    DifferenceLogicExplainKey::Propagation { literal: other_edge.literal, start: start, end: end };
+                let result = engine.assign(other_edge.literal, Reason::Propagation { explain_key: explain_key.into() });
+                if result.is_conflict() {
+                    return result;
+                }
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// 現在の有効な辺から edge_id の制約が従えば，そのリテラルを伝播する
+    fn propagate_if_implied<EngineT>(
+        &mut self,
+        edge_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<DifferenceLogicUndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<DifferenceLogicExplainKey>,
+    {
+        let Edge { from, to, weight, literal } = self.edges[edge_id];
+        if !self.search(from, to, true) {
+            return PropagationResult::Noconflict;
+        }
+        if self.forward_search.distances[to] + self.values[to] - self.values[from] > weight {
+            return PropagationResult::Noconflict;
+        }
+        let start = self.begin_explanation(undo_log);
+        Self::push_path_literals(&self.edges, &self.forward_search, to, true, &mut self.explanation_literals);
+        let end = self.explanation_literals.len();
+        let explain_key = DifferenceLogicExplainKey::Propagation { literal: literal, start: start, end: end };
+        return engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+    }
+}
+
+impl TheoryTrait for DifferenceLogicTheory {
+    type ExplainKey = DifferenceLogicExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = DifferenceLogicTheorySummary;
+    type UndoEntry = DifferenceLogicUndoEntry;
+
+    fn add_variable(&mut self) {
+        self.edges_of_literal.push(Array::default());
+        self.edges_of_literal.push(Array::default());
+        self.is_processed_true.push(false);
+        self.is_processed_true.push(false);
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        self.is_processed_true[assigned_literal.bits()] = true;
+        undo_log.push(DifferenceLogicUndoEntry::Processed { literal: assigned_literal });
+        for k in 0..self.edges_of_literal[assigned_literal.bits()].len() {
+            let edge_id = self.edges_of_literal[assigned_literal.bits()][k];
+            if self.is_active[edge_id] {
+                continue;
+            }
+            let result = self.activate_edge(edge_id, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn explain_propagation(&self, explain_key: DifferenceLogicExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            DifferenceLogicExplainKey::Propagation { literal, start, end } => ClauseView::new(Either::Left(
+                [literal].into_iter().chain(self.explanation_literals[start..end].iter().cloned()),
+            )),
+            DifferenceLogicExplainKey::Conflict => {
+                ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned()))
+            }
+        };
+    }
+
+    fn undo(&mut self, entry: DifferenceLogicUndoEntry) {
+        match entry {
+            DifferenceLogicUndoEntry::Processed { literal } => {
+                // values は残った有効な制約を満たすので戻さない
+                self.is_processed_true[literal.bits()] = false;
+                for &edge_id in self.edges_of_literal[literal.bits()].iter() {
+                    self.is_active[edge_id] = false;
+                }
+            }
+            DifferenceLogicUndoEntry::Explanation { start } => self.explanation_literals.truncate(start),
+        }
+    }
+
+    /// 変数が原子のリテラルに現れるか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return !self.edges_of_literal[2 * index].is_empty() || !self.edges_of_literal[2 * index + 1].is_empty();
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<DifferenceAtom> for DifferenceLogicTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        atom: DifferenceAtom,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        self.add_integer_variable(u32::max(atom.x, atom.y));
+        self.summary.number_of_difference_atoms += 1;
+        // x - y <= bound と，その否定 y - x <= -bound - 1
+        let edge_ids = [
+            self.add_edge(Edge { from: atom.y, to: atom.x, weight: atom.bound, literal: atom.literal }),
+            self.add_edge(Edge { from: atom.x, to: atom.y, weight: -atom.bound - 1, literal: !atom.literal }),
+        ];
+        for edge_id in edge_ids {
+            let literal = self.edges[edge_id].literal;
+            let result = if self.is_processed_true[literal.bits()] {
+                self.activate_edge(edge_id, engine, undo_log)
+            } else if !engine.is_assigned(literal.index()) {
+                self.propagate_if_implied(edge_id, engine, undo_log)
+            } else {
+                // 偽であれば辺は無効で，処理していない真であれば後で assign されたときに処理する
+                PropagationResult::Noconflict
+            };
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::{DifferenceAtom, DifferenceLogicExplainKey, DifferenceLogicTheory};
+    use crate::theory_test::{
+        TestedExplainKey, TestedTheory, add_constraint, assert_conflict_is_valid, assert_explanations_are_valid,
+        assignment_bits, check_random_search, conflict_clause, enumerate_bits, holds_in_bits, literal, make_engine,
+        reason_clause, sorted,
+    };
+
+    impl TestedExplainKey for DifferenceLogicExplainKey {}
+
+    impl TestedTheory for DifferenceLogicTheory {
+        type Solution = u32;
+
+        fn holds(&self, literal: Literal, bits: &u32) -> bool {
+            return holds_in_bits(literal, *bits);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> u32 {
+            // 全て割り当てたときの values は全ての原子の制約を満たす
+            for edge in self.edges.iter().filter(|edge| engine.is_true(edge.literal)) {
+                assert!(self.value(edge.to) <= self.value(edge.from) + edge.weight);
+            }
+            return assignment_bits(engine);
+        }
+
+        fn checkpoint(&self) -> u32 {
+            return self.explanation_literals.len();
+        }
+
+        /// 有効な辺が真のリテラルの辺と一致して values が満たし，有効な辺から従う未割り当ての原子が残っていない
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for index in 0..engine.number_of_variables() {
+                for literal in [literal(index, true), literal(index, false)] {
+                    assert_eq!(self.is_processed_true[literal.bits()], engine.is_true(literal));
+                }
+            }
+            let mut active_edges = Vec::new();
+            for (edge_id, edge) in self.edges.iter().enumerate() {
+                assert_eq!(self.is_active[edge_id as u32], engine.is_true(edge.literal));
+                if self.is_active[edge_id as u32] {
+                    assert!(self.value(edge.to) <= self.value(edge.from) + edge.weight);
+                    active_edges.push((edge.from, edge.to, edge.weight));
+                }
+            }
+            for edge in self.edges.iter().filter(|edge| !engine.is_assigned(edge.literal.index())) {
+                let distances =
+                    shortest_distances(self.number_of_integer_variables(), &active_edges, Some(edge.from)).unwrap();
+                assert!(distances[edge.to as usize].is_none_or(|distance| distance > edge.weight));
+            }
+        }
+    }
+
+    fn atom(x: u32, y: u32, bound: i64, literal: Literal) -> DifferenceAtom {
+        return DifferenceAtom { x: x, y: y, bound: bound, literal: literal };
+    }
+
+    /// Bellman-Ford 法で start からの最短距離を求める．start が None なら全ての頂点を始点とし，負閉路があれば None を返す
+    fn shortest_distances(
+        number_of_integer_variables: u32,
+        edges: &[(u32, u32, i64)],
+        start: Option<u32>,
+    ) -> Option<Vec<Option<i64>>> {
+        let mut distances = Vec::from_iter(
+            (0..number_of_integer_variables)
+                .map(|node| if start.is_none_or(|start| start == node) { Some(0) } else { None }),
+        );
+        for _ in 0..=number_of_integer_variables {
+            let mut is_updated = false;
+            for &(from, to, weight) in edges.iter() {
+                let Some(distance) = distances[from as usize] else {
+                    continue;
+                };
+                if distances[to as usize].is_none_or(|d| distance + weight < d) {
+                    distances[to as usize] = Some(distance + weight);
+                    is_updated = true;
+                }
+            }
+            if !is_updated {
+                return Some(distances);
+            }
+        }
+        return None;
+    }
+
+    /// 原子のリテラルが真であれば x - y <= bound，偽であれば y - x <= -bound - 1 を満たす整数変数の値があるか
+    fn enumerate_solutions(
+        number_of_variables: u32,
+        number_of_integer_variables: u32,
+        atoms: &[DifferenceAtom],
+    ) -> Vec<u32> {
+        return enumerate_bits(number_of_variables, |bits| {
+            let edges = Vec::from_iter(atoms.iter().map(|atom| match holds_in_bits(atom.literal, bits) {
+                true => (atom.y, atom.x, atom.bound),
+                false => (atom.x, atom.y, -atom.bound - 1),
+            }));
+            shortest_distances(number_of_integer_variables, &edges, None).is_some()
+        });
+    }
+
+    #[test]
+    fn test_transitive_bound_is_propagated() {
+        // x0 - x1 <= 0 (l0), x1 - x2 <= 0 (l1), x0 - x2 <= 0 (l2), x2 - x0 <= -1 (l3)
+        let atoms = [
+            atom(0, 1, 0, literal(0, true)),
+            atom(1, 2, 0, literal(1, true)),
+            atom(0, 2, 0, literal(2, true)),
+            atom(2, 0, -1, literal(3, true)),
+        ];
+        let solutions = enumerate_solutions(4, 3, &atoms);
+        let mut engine = make_engine(DifferenceLogicTheory::default(), 4);
+        for &atom in atoms.iter() {
+            assert!(add_constraint(&mut engine, atom).is_no_conflict());
+        }
+        assert_eq!(engine.number_of_assigneds(), 0);
+        assert!(engine.assign(literal(0, true), Reason::Decision).is_no_conflict());
+        assert!(!engine.is_assigned(2) && !engine.is_assigned(3));
+        // x0 <= x1 <= x2 から x0 - x2 <= 0 が従い，x2 - x0 <= -1 は成り立たない
+        assert!(engine.assign(literal(1, true), Reason::Decision).is_no_conflict());
+        assert!(engine.is_true(literal(2, true)));
+        assert!(engine.is_false(literal(3, true)));
+        let clause = sorted(reason_clause(&mut engine, literal(2, true)).unwrap());
+        assert!(clause == vec![literal(0, false), literal(1, false), literal(2, true)]);
+        assert_explanations_are_valid(&mut engine, &solutions);
+        engine.theory().assert_consistent(&engine);
+        let _ = engine.backjump(1).count();
+        assert!(!engine.is_assigned(2) && !engine.is_assigned(3));
+        assert_eq!(engine.theory().explanation_literals.len(), 0);
+        engine.theory().assert_consistent(&engine);
+    }
+
+    #[test]
+    fn test_negative_cycle_conflicts() {
+        // x0 - x1 <= -1 (l0) と x1 - x0 <= -1 (l1) は同時に満たせない
+        let atoms = [atom(0, 1, -1, literal(0, true)), atom(1, 0, -1, literal(1, true))];
+        let solutions = enumerate_solutions(2, 2, &atoms);
+        let mut engine = make_engine(DifferenceLogicTheory::default(), 2);
+        assert!(add_constraint(&mut engine, atoms[0]).is_no_conflict());
+        assert!(engine.assign(literal(0, true), Reason::Decision).is_no_conflict());
+        assert!(engine.assign(literal(1, true), Reason::Decision).is_no_conflict());
+        let PropagationResult::Conflict { explain_key } = add_constraint(&mut engine, atoms[1]) else {
+            panic!("expected a conflict");
+        };
+        assert_conflict_is_valid(&engine, explain_key, &solutions);
+        assert!(sorted(conflict_clause(&engine, explain_key)) == vec![literal(0, false), literal(1, false)]);
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(31);
+        for _ in 0..500 {
+            let number_of_variables = rng.gen_range(2..8);
+            let number_of_integer_variables = rng.gen_range(2..5);
+            let atoms = Vec::from_iter((0..rng.gen_range(1..9)).map(|_| {
+                atom(
+                    rng.gen_range(0..number_of_integer_variables),
+                    rng.gen_range(0..number_of_integer_variables),
+                    rng.gen_range(-3..4),
+                    literal(rng.gen_range(0..number_of_variables), rng.gen_bool(0.5)),
+                )
+            }));
+            let solutions = enumerate_solutions(number_of_variables, number_of_integer_variables, &atoms);
+            let mut engine = make_engine(DifferenceLogicTheory::default(), number_of_variables);
+            let mut result = PropagationResult::Noconflict;
+            for &atom in atoms.iter() {
+                result = add_constraint(&mut engine, atom);
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
@@ -1,44 +1,42 @@
-use cdcl_engine::{CoreEngine, CoreEngineExplainKey, Literal, OuterEngine, TheoryTrait};
+use cdcl_engine::{Literal, compose_engine};
 use either::Either;
 
 use crate::{
-    binary_clause_theory::{BinaryClause, BinaryClauseTheory, BinaryClauseTheoryExplainKey},
-    clause_theory::{ClauseExplainKey, ClauseTheory, ClauseTrait, ClauseView},
+    acyclicity_theory::{AcyclicityEdge, AcyclicityTheory},
+    binary_clause_theory::{BinaryClause, BinaryClauseTheory},
+    cardinality_theory::{CardinalityConstraint, CardinalityTheory},
+    clause_theory::{ClauseTheory, ClauseTrait, ClauseView},
+    difference_logic_theory::{DifferenceAtom, DifferenceLogicTheory},
+    external_propagator::{ExternalClause, ExternalPropagatorTheory},
+    integer_theory::{IntegerConstraint, IntegerTheory},
+    pseudo_boolean_theory::{PseudoBooleanConstraint, PseudoBooleanTheory},
+    xor_theory::{XorConstraint, XorTheory},
 };
 
-#[derive(Clone, Copy)]
-pub enum ExplainKey {
-    CoreEngine(CoreEngineExplainKey),
-    BinaryClauseTheory(<BinaryClauseTheory as TheoryTrait>::ExplainKey),
-    ClauseTheory(<ClauseTheory as TheoryTrait>::ExplainKey),
-}
-
-impl From<CoreEngineExplainKey> for ExplainKey {
-    fn from(explain_key: CoreEngineExplainKey) -> Self {
-        return Self::CoreEngine(explain_key);
-    }
-}
-
-impl From<BinaryClauseTheoryExplainKey> for ExplainKey {
-    fn from(explain_key: BinaryClauseTheoryExplainKey) -> Self {
-        return Self::BinaryClauseTheory(explain_key);
-    }
-}
-
-impl From<ClauseExplainKey> for ExplainKey {
-    fn from(explain_key: ClauseExplainKey) -> Self {
-        return Self::ClauseTheory(explain_key);
-    }
-}
-
-impl From<ExplainKey> for Either<ClauseExplainKey, Either<BinaryClauseTheoryExplainKey, CoreEngineExplainKey>> {
-    fn from(explain_key: ExplainKey) -> Self {
-        return match explain_key {
-            ExplainKey::CoreEngine(k) => Either::Right(Either::Right(k)),
-            ExplainKey::BinaryClauseTheory(k) => Either::Right(Either::Left(k)),
-            ExplainKey::ClauseTheory(k) => Either::Left(k),
-        };
-    }
+compose_engine! {
+    pub type SATEngine;
+    pub fn new_sat_engine;
+    /// いずれかの理論で，変数が節として扱えない制約に現れるか否か
+    pub fn contains_theory_variable;
+    pub enum ExplainKey;
+    /// 理論ごとの統計情報
+    pub struct SATEngineSummary;
+    /// SATEngine に追加できる制約
+    pub type EngineConstraint<ClauseT>;
+    /// 説明は節として扱うので，外部の伝播器・整数変数・差分論理・非巡回性・XOR 制約・擬似ブール制約・基数制約による説明も
+    /// ClauseTheory による説明と同じく Clause にする
+    explanation Constraint { Clause, BinaryClause, MonadicClause };
+    /// 伝播の方針を指定できる理論
+    pub enum TheoryKind;
+    external_propagator: ExternalPropagatorTheory(ExternalClause),
+    integer: IntegerTheory(IntegerConstraint) = Integer("integer"),
+    difference_logic: DifferenceLogicTheory(DifferenceAtom) = DifferenceLogic("difference-logic"),
+    acyclicity: AcyclicityTheory(AcyclicityEdge) = Acyclicity("acyclicity"),
+    xor: XorTheory(XorConstraint) = Xor("xor"),
+    pseudo_boolean: PseudoBooleanTheory(PseudoBooleanConstraint) = PseudoBoolean("pseudo-boolean"),
+    cardinality: CardinalityTheory(CardinalityConstraint) = Cardinality("cardinality"),
+    clause: ClauseTheory[ClauseT] = Clause("clause"),
+    binary_clause: BinaryClauseTheory(BinaryClause) = BinaryClause("binary-clause"),
 }
 
 pub enum Constraint<ClauseT> {
@@ -60,28 +58,6 @@ where
     }
 }
 
-impl<ClauseT> From<Either<ClauseT, Either<BinaryClause, Literal>>> for Constraint<ClauseT> {
-    fn from(either: Either<ClauseT, Either<BinaryClause, Literal>>) -> Self {
-        return match either {
-            Either::Left(clause) => Constraint::Clause(clause),
-            Either::Right(either) => match either {
-                Either::Left(binary_clause) => Constraint::BinaryClause(binary_clause),
-                Either::Right(monadic_clause) => Constraint::MonadicClause(monadic_clause),
-            },
-        };
-    }
-}
-
-impl<ClauseT> From<Constraint<ClauseT>> for Either<ClauseT, Either<BinaryClause, Literal>> {
-    fn from(constraint: Constraint<ClauseT>) -> Self {
-        return match constraint {
-            Constraint::MonadicClause(c) => Either::Right(Either::Right(c)),
-            Constraint::BinaryClause(c) => Either::Right(Either::Left(c)),
-            Constraint::Clause(c) => Either::Left(c),
-        };
-    }
-}
-
 pub fn make_constraint(mut iterator: impl Iterator<Item = Literal> + Clone) -> Constraint<impl ClauseTrait> {
     // TODO: 将来的にはちゃんとしたものを作る
     let len = iterator.clone().count() as u32;
@@ -92,5 +68,3 @@ pub fn make_constraint(mut iterator: impl Iterator<Item = Literal> + Clone) -> C
         _ => Constraint::Clause(ClauseView::new(iterator)),
     };
 }
-
-pub type SATEngine = OuterEngine<ClauseTheory, OuterEngine<BinaryClauseTheory, CoreEngine<ExplainKey>>>;
@@ -0,0 +1,529 @@
+use std::cell::RefCell;
+
+use cdcl_engine::{
+    EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait, TheoryTrait,
+    UndoLog,
+};
+use either::Either;
+use utility::Array;
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// 探索の外から割り当てを観測して介入する伝播器(IPASIR-UP のコールバックに相当する)
+///
+/// ExternalPropagatorTheory が TheoryTrait の assign ・ backjump ・ explain_propagation から呼び出す．
+/// Box<dyn ExternalPropagator> として渡すので，ジェネリックなメソッドは持たない．
+pub trait ExternalPropagator {
+    /// literal が割り当てられた．is_fixed であれば決定レベル 0 での割り当て
+    fn notify_assignment(&mut self, literal: Literal, is_fixed: bool);
+
+    /// 決定レベルが 1 つ上がった
+    fn notify_new_decision_level(&mut self);
+
+    /// 決定レベル new_decision_level までバックトラックした
+    fn notify_backtrack(&mut self, new_decision_level: u32);
+
+    /// 全ての変数に割り当てた解 model を受け入れるか否か
+    ///
+    /// 受け入れない場合は，解を除く節を add_external_clause で返さなければならない．
+    fn check_found_model(&mut self, _model: &[Literal]) -> bool {
+        return true;
+    }
+
+    /// 伝播するリテラルを 1 つ返す．なければ None
+    fn propagate(&mut self) -> Option<Literal> {
+        return None;
+    }
+
+    /// propagate で返した propagated_literal の割り当て理由となる節(propagated_literal 以外は偽)を返す
+    ///
+    /// 矛盾の解析で必要になったときにだけ呼び出す．
+    fn add_reason_clause(&mut self, propagated_literal: Literal) -> Array<u32, Literal>;
+
+    /// 次に決定するリテラルを返す．None であればソルバが選ぶ
+    ///
+    /// 割り当て済みの変数のリテラルを返した場合も，ソルバが選ぶ．
+    fn decide(&mut self) -> Option<Literal> {
+        return None;
+    }
+
+    /// 探索中に追加する節を 1 つ返す．なければ None
+    fn add_external_clause(&mut self) -> Option<Array<u32, Literal>> {
+        return None;
+    }
+}
+
+/// 外部の伝播器から与えられた節
+pub struct ExternalClause {
+    pub literals: Array<u32, Literal>,
+}
+
+#[derive(Clone, Copy)]
+pub enum ExternalPropagatorExplainKey {
+    /// 伝播器が伝播した literal．理由の節は説明が必要になったときに伝播器に問い合わせる
+    Propagation { literal: Literal },
+    /// conflicting_literals からなる節
+    Conflict,
+    /// clauses[clause_id] による伝播・矛盾
+    Clause { clause_id: u32 },
+}
+
+#[derive(Default, Clone)]
+pub struct ExternalPropagatorTheorySummary {
+    pub number_of_external_propagations: u32,
+    pub number_of_external_clauses: u32,
+    pub number_of_rejected_models: u32,
+}
+
+impl ProgressColumnsTrait for ExternalPropagatorTheorySummary {
+    const HEADERS: &'static [&'static str] = &["EXTERNAL_PROPAGATION"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_external_propagations].into_iter();
+    }
+}
+
+/// 外部の伝播器を OuterEngine に組み込むための理論
+///
+/// 伝播器が接続されていなければなにもしない．伝播器には全ての割り当てを通知し，決定レベルの変化は
+/// 次の割り当ての通知の前(上がった場合)と backjump のとき(下がった場合)に通知する．
+/// 伝播器が伝播したリテラルの理由の節は，説明を求められたときに問い合わせて次に同じ変数を伝播するまで保持する．
+/// 伝播器が追加した節はこの理論が保持し，節に現れるリテラルが偽になるたびに伝播・矛盾を調べる．
+#[derive(Default)]
+pub struct ExternalPropagatorTheory {
+    propagator: Option<RefCell<Box<dyn ExternalPropagator>>>,
+    /// 伝播器に通知した決定レベル
+    decision_level: u32,
+    /// 伝播器に通知したリテラル
+    trail: Array<u32, Literal>,
+    /// trail のリテラルの決定レベル
+    trail_decision_levels: Array<u32, u32>,
+    /// 変数 -> 伝播器に問い合わせた理由の節
+    reasons: RefCell<Array<u32, Option<Array<u32, Literal>>>>,
+    conflicting_literals: Array<u32, Literal>,
+    clauses: Array<u32, Array<u32, Literal>>,
+    /// リテラル(bits) -> そのリテラルを含む節
+    clauses_of_literal: Array<u32, Array<u32, u32>>,
+    summary: ExternalPropagatorTheorySummary,
+}
+
+impl ExternalPropagatorTheory {
+    /// 伝播器を接続する．割り当てを始める前に接続しなければならない
+    pub fn connect_propagator(&mut self, propagator: Box<dyn ExternalPropagator>) {
+        debug_assert!(self.trail.is_empty());
+        self.propagator = Some(RefCell::new(propagator));
+    }
+
+    pub fn is_propagator_connected(&self) -> bool {
+        return self.propagator.is_some();
+    }
+
+    /// 伝播器が追加する節を 1 つ取り出す
+    fn pop_external_clause(&mut self) -> Option<Array<u32, Literal>> {
+        return self.propagator.as_mut().and_then(|propagator| propagator.get_mut().add_external_clause());
+    }
+
+    /// 節を加え，単位節になっていれば伝播し，全てのリテラルが偽であれば矛盾とする
+    fn add_clause<EngineT>(
+        &mut self,
+        literals: Array<u32, Literal>,
+        engine: &mut EngineT,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<ExternalPropagatorExplainKey>,
+    {
+        let clause_id = self.clauses.len();
+        for literal in literals.iter() {
+            debug_assert!(literal.index() < engine.number_of_variables());
+            self.clauses_of_literal[literal.bits()].push(clause_id);
+        }
+        self.clauses.push(literals);
+        self.summary.number_of_external_clauses += 1;
+        return self.propagate_clause(clause_id, engine);
+    }
+
+    fn propagate_clause<EngineT>(
+        &mut self,
+        clause_id: u32,
+        engine: &mut EngineT,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<ExternalPropagatorExplainKey>,
+    {
+        let mut unassigned_literal = None;
+        let mut number_of_unassigneds = 0;
+        for &literal in self.clauses[clause_id].iter() {
+            if engine.is_true(literal) {
+                return PropagationResult::Noconflict;
+            }
+            if !engine.is_assigned(literal.index()) {
+                unassigned_literal = Some(literal);
+                number_of_unassigneds += 1;
+            }
+        }
+        let explain_key = ExternalPropagatorExplainKey::Clause { clause_id: clause_id };
+        return match number_of_unassigneds {
+            0 => PropagationResult::Conflict { explain_key: explain_key.into() },
+            1 => {
+                self.summary.number_of_external_propagations += 1;
+                engine.assign(unassigned_literal.unwrap(), Reason::Propagation { explain_key: explain_key.into() })
+            }
+            _ => PropagationResult::Noconflict,
+        };
+    }
+
+    /// 伝播器が追加する節と伝播するリテラルがなくなるまで伝播する
+    fn propagate<EngineT>(&mut self, engine: &mut EngineT) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<ExternalPropagatorExplainKey>,
+    {
+        loop {
+            while let Some(literals) = self.pop_external_clause() {
+                let result = self.add_clause(literals, engine);
+                if result.is_conflict() {
+                    return result;
+                }
+            }
+            let Some(literal) = self.propagator.as_mut().unwrap().get_mut().propagate() else {
+                return PropagationResult::Noconflict;
+            };
+            if engine.is_true(literal) {
+                continue;
+            }
+            if engine.is_false(literal) {
+                // 矛盾は必ず解析されるので，理由の節をすぐに問い合わせる
+                self.conflicting_literals = self.propagator.as_mut().unwrap().get_mut().add_reason_clause(literal);
+                return PropagationResult::Conflict { explain_key: ExternalPropagatorExplainKey::Conflict.into() };
+            }
+            self.summary.number_of_external_propagations += 1;
+            // 以前に同じ変数を伝播したときの理由の節は捨てる
+            self.reasons.get_mut()[literal.index()] = None;
+            let explain_key = ExternalPropagatorExplainKey::Propagation { literal: literal };
+            let result = engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+            if result.is_conflict() {
+                return result;
+            }
+        }
+    }
+}
+
+impl TheoryTrait for ExternalPropagatorTheory {
+    type ExplainKey = ExternalPropagatorExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = ExternalPropagatorTheorySummary;
+    type UndoEntry = Literal;
+
+    fn add_variable(&mut self) {
+        self.reasons.get_mut().push(None);
+        self.clauses_of_literal.push(Array::default());
+        self.clauses_of_literal.push(Array::default());
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        let Some(propagator) = self.propagator.as_mut() else {
+            return PropagationResult::Noconflict;
+        };
+        let propagator = propagator.get_mut();
+        let decision_level = engine.current_decision_level();
+        while self.decision_level < decision_level {
+            propagator.notify_new_decision_level();
+            self.decision_level += 1;
+        }
+        propagator.notify_assignment(assigned_literal, decision_level == 0);
+        self.trail.push(assigned_literal);
+        self.trail_decision_levels.push(decision_level);
+        undo_log.push(assigned_literal);
+        for k in 0..self.clauses_of_literal[(!assigned_literal).bits()].len() {
+            let clause_id = self.clauses_of_literal[(!assigned_literal).bits()][k];
+            let result = self.propagate_clause(clause_id, engine);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return self.propagate(engine);
+    }
+
+    fn explain_propagation(&self, explain_key: ExternalPropagatorExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            ExternalPropagatorExplainKey::Propagation { literal } => {
+                let mut reasons = self.reasons.borrow_mut();
+                let reason = reasons[literal.index()]
+                    .get_or_insert_with(|| self.propagator.as_ref().unwrap().borrow_mut().add_reason_clause(literal));
+                debug_assert!(reason.contains(&literal));
+                ClauseView::new(Either::Left(reason.clone().into_iter()))
+            }
+            ExternalPropagatorExplainKey::Conflict => {
+                ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned()))
+            }
+            ExternalPropagatorExplainKey::Clause { clause_id } => {
+                ClauseView::new(Either::Right(self.clauses[clause_id].iter().cloned()))
+            }
+        };
+    }
+
+    fn undo(&mut self, unassigned_literal: Literal) {
+        debug_assert!(self.trail.last() == Some(&unassigned_literal));
+        self.trail.pop();
+        self.trail_decision_levels.pop();
+    }
+
+    fn backjump(&mut self, _backjump_level: u32) {
+        let Some(propagator) = self.propagator.as_mut() else {
+            return;
+        };
+        // 決定レベル 1 以上の各決定レベルには通知した決定のリテラルが残っている
+        let decision_level = self.trail_decision_levels.last().cloned().unwrap_or(0);
+        if decision_level < self.decision_level {
+            propagator.get_mut().notify_backtrack(decision_level);
+            self.decision_level = decision_level;
+        }
+    }
+
+    /// 伝播器が解を受け入れなければ，伝播器が追加する節を加える
+    fn final_check<EngineT>(
+        &mut self,
+        engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        let Some(propagator) = self.propagator.as_mut() else {
+            return PropagationResult::Noconflict;
+        };
+        if propagator.get_mut().check_found_model(self.trail.as_slice()) {
+            return PropagationResult::Noconflict;
+        }
+        self.summary.number_of_rejected_models += 1;
+        return self.propagate(engine);
+    }
+
+    fn suggest_decision<EngineT: EngineTrait>(&mut self, engine: &EngineT) -> Option<Literal> {
+        let literal = self.propagator.as_mut()?.get_mut().decide()?;
+        debug_assert!(literal.index() < engine.number_of_variables());
+        return if engine.is_assigned(literal.index()) { None } else { Some(literal) };
+    }
+
+    /// 変数を伝播器が観測するか否か．伝播器は全ての変数を観測する
+    fn contains_variable(&self, _index: u32) -> bool {
+        return self.is_propagator_connected();
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 伝播器が追加した節は削除しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<ExternalClause> for ExternalPropagatorTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        clause: ExternalClause,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        return self.add_clause(clause.literals, engine);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use cdcl_engine::{Boolean, Literal};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+    use utility::Array;
+
+    use super::ExternalPropagator;
+    use crate::{
+        cnf::CNF,
+        options::{PropagationOptions, SearchOptions},
+        solve::{SolveResult, solve},
+    };
+
+    /// 各コールバックが呼び出された回数
+    #[derive(Default)]
+    struct CallbackCounts {
+        notify_assignment: u32,
+        notify_new_decision_level: u32,
+        notify_backtrack: u32,
+        check_found_model: u32,
+        propagate: u32,
+        add_reason_clause: u32,
+        decide: u32,
+        add_external_clause: u32,
+    }
+
+    /// implications の含意を伝播し，最初に見つかった解を拒否する伝播器
+    struct TestPropagator {
+        counts: Rc<RefCell<CallbackCounts>>,
+        /// (a, b): a が真であれば b を伝播する
+        implications: Vec<(Literal, Literal)>,
+        /// 変数 -> 通知された，または伝播した値
+        values: Vec<Option<Boolean>>,
+        /// 通知されたリテラルとその決定レベル
+        trail: Vec<(Literal, u32)>,
+        decision_level: u32,
+        /// 変数 -> 伝播したときに含意の前提だったリテラル
+        antecedents: Vec<Option<Literal>>,
+        pending_clauses: Vec<Array<u32, Literal>>,
+        rejected_model: Rc<RefCell<Option<Vec<Literal>>>>,
+    }
+
+    impl TestPropagator {
+        fn is_true(&self, literal: Literal) -> bool {
+            return self.values[literal.index() as usize] == Some(literal.value());
+        }
+    }
+
+    impl ExternalPropagator for TestPropagator {
+        fn notify_assignment(&mut self, literal: Literal, is_fixed: bool) {
+            self.counts.borrow_mut().notify_assignment += 1;
+            assert_eq!(is_fixed, self.decision_level == 0);
+            assert!(self.values[literal.index() as usize].is_none_or(|value| value == literal.value()));
+            self.values[literal.index() as usize] = Some(literal.value());
+            self.trail.push((literal, self.decision_level));
+        }
+
+        fn notify_new_decision_level(&mut self) {
+            self.counts.borrow_mut().notify_new_decision_level += 1;
+            self.decision_level += 1;
+        }
+
+        fn notify_backtrack(&mut self, new_decision_level: u32) {
+            self.counts.borrow_mut().notify_backtrack += 1;
+            assert!(new_decision_level < self.decision_level);
+            self.decision_level = new_decision_level;
+            // 伝播したが通知される前に取り消されたリテラルもあるので，残った割り当てから作り直す
+            self.trail.retain(|&(_, decision_level)| decision_level <= new_decision_level);
+            self.values.fill(None);
+            for &(literal, _) in self.trail.iter() {
+                self.values[literal.index() as usize] = Some(literal.value());
+            }
+        }
+
+        fn check_found_model(&mut self, model: &[Literal]) -> bool {
+            self.counts.borrow_mut().check_found_model += 1;
+            assert_eq!(model.len(), self.values.len());
+            let mut rejected_model = self.rejected_model.borrow_mut();
+            if rejected_model.is_some() {
+                return true;
+            }
+            *rejected_model = Some(model.to_vec());
+            self.pending_clauses.push(Array::from_iter(model.iter().map(|&literal| !literal)));
+            return false;
+        }
+
+        fn propagate(&mut self) -> Option<Literal> {
+            self.counts.borrow_mut().propagate += 1;
+            let &(antecedent, literal) = self
+                .implications
+                .iter()
+                .find(|&&(antecedent, literal)| self.is_true(antecedent) && !self.is_true(literal))?;
+            self.values[literal.index() as usize] = Some(literal.value());
+            self.antecedents[literal.index() as usize] = Some(antecedent);
+            return Some(literal);
+        }
+
+        fn add_reason_clause(&mut self, propagated_literal: Literal) -> Array<u32, Literal> {
+            self.counts.borrow_mut().add_reason_clause += 1;
+            let antecedent = self.antecedents[propagated_literal.index() as usize].unwrap();
+            return Array::from_iter([!antecedent, propagated_literal].into_iter());
+        }
+
+        fn decide(&mut self) -> Option<Literal> {
+            self.counts.borrow_mut().decide += 1;
+            let index = self.values.iter().position(|value| value.is_none())?;
+            return Some(Literal::new(index as u32, Boolean::FALSE));
+        }
+
+        fn add_external_clause(&mut self) -> Option<Array<u32, Literal>> {
+            let clause = self.pending_clauses.pop()?;
+            self.counts.borrow_mut().add_external_clause += 1;
+            return Some(clause);
+        }
+    }
+
+    fn make_random_literal(rng: &mut SmallRng, number_of_variables: u32) -> Literal {
+        return Literal::new(rng.gen_range(0..number_of_variables), Boolean::from(rng.gen_bool(0.5)));
+    }
+
+    /// 伝播器の各コールバックが呼び出され，伝播・追加した節・解の拒否が解に反映されることを確かめる
+    #[test]
+    fn test_external_propagator_callbacks() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        let counts = Rc::new(RefCell::new(CallbackCounts::default()));
+        let number_of_variables = 50;
+        let mut number_of_satisfiables = 0;
+        for _ in 0..20 {
+            let mut cnf = CNF { number_of_variables: number_of_variables, ..CNF::default() };
+            for _ in 0..180 {
+                cnf.clauses.push(Array::from_iter((0..3).map(|_| make_random_literal(&mut rng, number_of_variables))));
+            }
+            let implications = Vec::from_iter((0..15).map(|_| {
+                (make_random_literal(&mut rng, number_of_variables), make_random_literal(&mut rng, number_of_variables))
+            }));
+            let external_clause = Array::from_iter((0..3).map(|_| make_random_literal(&mut rng, number_of_variables)));
+            let rejected_model = Rc::new(RefCell::new(None));
+            let propagator = TestPropagator {
+                counts: counts.clone(),
+                implications: implications.clone(),
+                values: vec![None; number_of_variables as usize],
+                trail: Vec::new(),
+                decision_level: 0,
+                antecedents: vec![None; number_of_variables as usize],
+                pending_clauses: vec![external_clause.clone()],
+                rejected_model: rejected_model.clone(),
+            };
+            let result = solve(
+                &cnf,
+                false,
+                0,
+                &PropagationOptions::default(),
+                &SearchOptions::default(),
+                Some(Box::new(propagator)),
+            );
+            let SolveResult::Satisfiable { model } = result else {
+                continue;
+            };
+            let is_true = |literal: Literal| model[literal.index()] == literal.value();
+            assert!(cnf.clauses.iter().all(|clause| clause.iter().any(|&literal| is_true(literal))));
+            assert!(implications.iter().all(|&(antecedent, literal)| !is_true(antecedent) || is_true(literal)));
+            assert!(external_clause.iter().any(|&literal| is_true(literal)));
+            // 拒否した解とは異なる解が見つかる
+            let rejected_model = rejected_model.borrow().clone().unwrap();
+            assert!(rejected_model.iter().any(|&literal| !is_true(literal)));
+            number_of_satisfiables += 1;
+        }
+        assert!(number_of_satisfiables > 0);
+        let counts = counts.borrow();
+        assert!(counts.notify_assignment > 0);
+        assert!(counts.notify_new_decision_level > 0);
+        assert!(counts.notify_backtrack > 0);
+        assert!(counts.check_found_model > 0);
+        assert!(counts.propagate > 0);
+        assert!(counts.add_reason_clause > 0);
+        assert!(counts.decide > 0);
+        assert!(counts.add_external_clause > 0);
+    }
+}
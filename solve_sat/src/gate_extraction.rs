@@ -0,0 +1,209 @@
+use cdcl_engine::{Boolean, Literal};
+use utility::{Array, Set};
+
+use crate::clause_database::ClauseDatabase;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GateKind {
+    /// output = inputs[0] ∧ inputs[1] ∧ ...
+    And,
+    /// output = inputs[0] ⊕ inputs[1] ⊕ ...
+    Xor,
+    /// output = if inputs[0] then inputs[1] else inputs[2]
+    IfThenElse,
+    /// output = inputs[0]
+    Equivalence,
+}
+
+/// 節の組から認識したゲートの定義
+#[derive(Clone)]
+pub struct Gate {
+    pub kind: GateKind,
+    pub output: Literal,
+    pub inputs: Array<u32, Literal>,
+    /// ゲートを定義する節の番号
+    pub definition: Array<u32, u32>,
+}
+
+#[derive(Default, Clone)]
+pub struct GateSummary {
+    pub number_of_and_gates: u32,
+    pub number_of_xor_gates: u32,
+    pub number_of_if_then_else_gates: u32,
+    pub number_of_equivalence_gates: u32,
+}
+
+impl GateSummary {
+    pub fn add(&mut self, gate: &Gate) {
+        match gate.kind {
+            GateKind::And => self.number_of_and_gates += 1,
+            GateKind::Xor => self.number_of_xor_gates += 1,
+            GateKind::IfThenElse => self.number_of_if_then_else_gates += 1,
+            GateKind::Equivalence => self.number_of_equivalence_gates += 1,
+        }
+    }
+}
+
+/// 節のパターンから AND / XOR / ITE / 等価ゲートの定義を認識する
+#[derive(Default, Clone)]
+pub struct GateExtraction {
+    /// ¬output との 2 項節に現れるリテラル(bits)
+    binary_partners: Set<u32>,
+    literals: Array<u32, Literal>,
+}
+
+impl GateExtraction {
+    /// 認識する XOR ゲートの節の長さの上限
+    const MAX_XOR_LENGTH: u32 = 5;
+
+    /// 全ての変数について，その変数を出力とするゲートを認識する
+    pub fn extract_gates(&mut self, database: &ClauseDatabase) -> Array<u32, Gate> {
+        return Array::from_iter((0..database.number_of_variables()).filter_map(|index| self.find_gate(database, index)));
+    }
+
+    /// 変数 index を出力とするゲートを探す
+    pub fn find_gate(&mut self, database: &ClauseDatabase, index: u32) -> Option<Gate> {
+        for value in [Boolean::TRUE, Boolean::FALSE] {
+            let output = Literal::new(index, value);
+            if let Some(gate) = self.find_and_gate(database, output) {
+                return Some(gate);
+            }
+        }
+        for value in [Boolean::TRUE, Boolean::FALSE] {
+            let output = Literal::new(index, value);
+            if let Some(gate) = self.find_if_then_else_gate(database, output) {
+                return Some(gate);
+            }
+        }
+        return self.find_xor_gate(database, index);
+    }
+
+    /// (¬output ∨ a_i) (i = 1..k) と (output ∨ ¬a_1 ∨ ... ∨ ¬a_k) を探す
+    fn find_and_gate(&mut self, database: &ClauseDatabase, output: Literal) -> Option<Gate> {
+        self.binary_partners.clear();
+        for &clause_id in database.occurrences(!output).iter() {
+            if let &[l0, l1] = database.clause(clause_id) {
+                self.binary_partners.insert((if l0 == !output { l1 } else { l0 }).bits());
+            }
+        }
+        if self.binary_partners.is_empty() {
+            return None;
+        }
+        for &clause_id in database.occurrences(output).iter() {
+            let clause = database.clause(clause_id);
+            if clause.len() < 2
+                || clause.iter().any(|&l| l != output && !self.binary_partners.contains_key((!l).bits()))
+            {
+                continue;
+            }
+            let inputs = Array::from_iter(clause.iter().filter(|&&l| l != output).map(|&l| !l));
+            let mut definition = Array::default();
+            definition.push(clause_id);
+            for &input in inputs.iter() {
+                definition.push(Self::find_clause(database, &[!output, input]).unwrap());
+            }
+            let kind = if inputs.len() == 1 { GateKind::Equivalence } else { GateKind::And };
+            return Some(Gate { kind: kind, output: output, inputs: inputs, definition: definition });
+        }
+        return None;
+    }
+
+    /// (¬output ∨ ¬c ∨ t) (¬output ∨ c ∨ e) (output ∨ ¬c ∨ ¬t) (output ∨ c ∨ ¬e) を探す
+    fn find_if_then_else_gate(&mut self, database: &ClauseDatabase, output: Literal) -> Option<Gate> {
+        let negative_occurrences = database.occurrences(!output);
+        for &then_clause_id in negative_occurrences.iter() {
+            let Some([l0, l1]) = Self::other_literals_of_ternary(database.clause(then_clause_id), !output) else {
+                continue;
+            };
+            for &else_clause_id in negative_occurrences.iter() {
+                if else_clause_id == then_clause_id {
+                    continue;
+                }
+                let Some([m0, m1]) = Self::other_literals_of_ternary(database.clause(else_clause_id), !output) else {
+                    continue;
+                };
+                for (condition, then_literal, else_literal) in
+                    [(!l0, l1, m0), (!l0, l1, m1), (!l1, l0, m0), (!l1, l0, m1)]
+                {
+                    let other_literal = if else_literal == m0 { m1 } else { m0 };
+                    if other_literal != condition || then_literal.index() == else_literal.index() {
+                        continue;
+                    }
+                    let (Some(c0), Some(c1)) = (
+                        Self::find_clause(database, &[output, !condition, !then_literal]),
+                        Self::find_clause(database, &[output, condition, !else_literal]),
+                    ) else {
+                        continue;
+                    };
+                    let mut inputs = Array::default();
+                    inputs.push(condition);
+                    inputs.push(then_literal);
+                    inputs.push(else_literal);
+                    let mut definition = Array::default();
+                    definition.push(then_clause_id);
+                    definition.push(else_clause_id);
+                    definition.push(c0);
+                    definition.push(c1);
+                    return Some(Gate { kind: GateKind::IfThenElse, output: output, inputs: inputs, definition: definition });
+                }
+            }
+        }
+        return None;
+    }
+
+    /// 同じ変数からなり，負のリテラルの数の偶奇が等しい 2^(n-1) 個の節を探す
+    fn find_xor_gate(&mut self, database: &ClauseDatabase, index: u32) -> Option<Gate> {
+        for value in [Boolean::TRUE, Boolean::FALSE] {
+            for &clause_id in database.occurrences(Literal::new(index, value)).iter() {
+                let clause = database.clause(clause_id);
+                let length = clause.len() as u32;
+                if !(3..=Self::MAX_XOR_LENGTH).contains(&length) {
+                    continue;
+                }
+                let mut definition = Array::default();
+                for mask in 0u32..(1 << length) {
+                    if mask.count_ones() % 2 != 0 {
+                        continue;
+                    }
+                    self.literals.clear();
+                    for (k, &literal) in clause.iter().enumerate() {
+                        self.literals.push(if mask & (1 << k) != 0 { !literal } else { literal });
+                    }
+                    match Self::find_clause(database, &self.literals[..]) {
+                        Some(other_clause_id) => {
+                            definition.push(other_clause_id);
+                        }
+                        None => break,
+                    }
+                }
+                if definition.len() != 1 << (length - 1) {
+                    continue;
+                }
+                // 節が禁止する割当て(全リテラルが偽)の偶奇を P とすると，全変数の XOR は ¬P
+                let parity = clause.iter().filter(|l| l.value() == Boolean::FALSE).count() % 2 == 1;
+                let inputs = Array::from_iter(
+                    clause.iter().filter(|l| l.index() != index).map(|l| Literal::new(l.index(), Boolean::TRUE)),
+                );
+                let output = Literal::new(index, Boolean::from(parity));
+                return Some(Gate { kind: GateKind::Xor, output: output, inputs: inputs, definition: definition });
+            }
+        }
+        return None;
+    }
+
+    fn other_literals_of_ternary(clause: &[Literal], literal: Literal) -> Option<[Literal; 2]> {
+        if clause.len() != 3 {
+            return None;
+        }
+        let mut others = clause.iter().cloned().filter(|&l| l != literal);
+        return Some([others.next().unwrap(), others.next().unwrap()]);
+    }
+
+    /// literals と同じリテラルからなる節の番号を返す
+    fn find_clause(database: &ClauseDatabase, literals: &[Literal]) -> Option<u32> {
+        return database.occurrences(literals[0]).iter().cloned().find(|&clause_id| {
+            let clause = database.clause(clause_id);
+            clause.len() == literals.len() && literals.iter().all(|literal| clause.contains(literal))
+        });
+    }
+}
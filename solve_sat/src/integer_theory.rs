@@ -0,0 +1,1097 @@
+use cdcl_engine::{
+    Boolean, EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait,
+    TheoryTrait, UndoLog,
+};
+use either::Either;
+use utility::Array;
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// 整数変数についての制約
+#[derive(Clone)]
+pub enum IntegerConstraint {
+    /// sum(係数 * 整数変数) <= bound
+    Linear { terms: Array<u32, (i64, u32)>, bound: i64 },
+    /// 整数変数の値が互いに異なる
+    AllDifferent { variables: Array<u32, u32> },
+    /// result = values[index]
+    Element { index: u32, result: u32, values: Array<u32, i64> },
+}
+
+/// literal が真であることと整数変数について variable <= value であることが同値であるという宣言
+#[derive(Clone, Copy)]
+pub struct OrderLiteral {
+    pub literal: Literal,
+    pub variable: u32,
+    pub value: i64,
+}
+
+#[derive(Clone, Copy)]
+pub enum IntegerExplainKey {
+    /// literal と explanation_literals[start..end] (偽のリテラル)からなる節
+    Propagation { literal: Literal, start: u32, end: u32 },
+    /// conflicting_literals からなる節
+    Conflict,
+}
+
+#[derive(Default, Clone)]
+pub struct IntegerTheorySummary {
+    pub number_of_integer_variables: u32,
+    pub number_of_integer_constraints: u32,
+    pub number_of_integer_propagations: u32,
+}
+
+impl ProgressColumnsTrait for IntegerTheorySummary {
+    const HEADERS: &'static [&'static str] = &["INTEGER_PROPAGATION"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_integer_propagations].into_iter();
+    }
+}
+
+/// 順序符号化された整数変数
+///
+/// order_literals は作成済みの順序リテラル (v, [x <= v]) (lower <= v < upper) を v の昇順に並べたもので，
+/// [x <= v] は上下限が初めて v に達したときに作る．隣り合う順序リテラルの間の含意 (連鎖の節) は理論が伝播するので，
+/// 伝播が済んだ時点では偽の順序リテラルは先頭に，真の順序リテラルは末尾に並ぶ．
+/// equality_literals[k] は [x = lower + k] を表す．
+#[derive(Clone)]
+struct IntegerVariable {
+    lower: i64,
+    upper: i64,
+    order_literals: Array<u32, (i64, Literal)>,
+    equality_literals: Array<u32, Literal>,
+}
+
+impl IntegerVariable {
+    /// 値が value の順序リテラルの位置か，なければそれを挿入する位置
+    fn find_order_literal(&self, value: i64) -> Result<usize, usize> {
+        return self.order_literals.as_slice().binary_search_by_key(&value, |&(v, _)| v);
+    }
+
+    /// 作成済みであれば [x <= value] を表すリテラル
+    fn order_literal(&self, value: i64) -> Option<Literal> {
+        return self.find_order_literal(value).ok().map(|position| self.order_literals[position as u32].1);
+    }
+
+    fn equality_literal(&self, value: i64) -> Literal {
+        debug_assert!(self.lower <= value && value <= self.upper);
+        return self.equality_literals[(value - self.lower) as u32];
+    }
+
+    /// 最後の偽の順序リテラルから求めた下限
+    ///
+    /// 連鎖の節の伝播が済んでいなければ偽の順序リテラルが先頭に並ばないので，二分探索の結果が偽でなければ探し直す
+    fn lower_bound(&self, engine: &impl EngineTrait) -> i64 {
+        let order_literals = self.order_literals.as_slice();
+        let mut k = order_literals.partition_point(|&(_, literal)| engine.is_false(literal));
+        if k > 0 && !engine.is_false(order_literals[k - 1].1) {
+            k = order_literals
+                .iter()
+                .rposition(|&(_, literal)| engine.is_false(literal))
+                .map_or(0, |position| position + 1);
+        }
+        return if k == 0 { self.lower } else { order_literals[k - 1].0 + 1 };
+    }
+
+    /// 最初の真の順序リテラルから求めた上限
+    fn upper_bound(&self, engine: &impl EngineTrait) -> i64 {
+        let order_literals = self.order_literals.as_slice();
+        let mut k = order_literals.partition_point(|&(_, literal)| !engine.is_true(literal));
+        if k < order_literals.len() && !engine.is_true(order_literals[k].1) {
+            k = order_literals
+                .iter()
+                .position(|&(_, literal)| engine.is_true(literal))
+                .unwrap_or(order_literals.len());
+        }
+        return if k == order_literals.len() { self.upper } else { order_literals[k].0 };
+    }
+
+    /// 下限 lower_bound の理由となる偽のリテラル
+    ///
+    /// 下限は偽の順序リテラル [x <= lower_bound - 1] から求めるので，lower より大きければそのリテラルがある
+    fn lower_bound_reason(&self, lower_bound: i64) -> Option<Literal> {
+        return self.order_literal(lower_bound - 1);
+    }
+
+    /// 上限 upper_bound の理由となる偽のリテラル
+    fn upper_bound_reason(&self, upper_bound: i64) -> Option<Literal> {
+        return self.order_literal(upper_bound).map(|literal| !literal);
+    }
+}
+
+/// 伝播・矛盾の説明
+#[derive(Default, Clone)]
+struct Explanations {
+    explanation_literals: Array<u32, Literal>,
+    conflicting_literals: Array<u32, Literal>,
+    number_of_propagations: u32,
+}
+
+impl Explanations {
+    /// explanation_literals[start..] の偽のリテラルから literal が従うとして伝播する．literal が偽であれば矛盾とする
+    fn imply<EngineT>(
+        &mut self,
+        literal: Literal,
+        start: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        if engine.is_true(literal) {
+            self.explanation_literals.truncate(start);
+            return PropagationResult::Noconflict;
+        }
+        if engine.is_false(literal) {
+            self.explanation_literals.push(literal);
+            return self.contradict(start);
+        }
+        let end = self.explanation_literals.len();
+        undo_log.push(start);
+        self.number_of_propagations += 1;
+        let explain_key = IntegerExplainKey::Propagation { literal: literal, start: start, end: end };
+        return engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+    }
+
+    /// explanation_literals[start..] の偽のリテラルからなる節を矛盾とする
+    fn contradict<CompositeExplainKeyT>(&mut self, start: u32) -> PropagationResult<CompositeExplainKeyT>
+    where
+        CompositeExplainKeyT: From<IntegerExplainKey>,
+    {
+        self.conflicting_literals.clear();
+        self.conflicting_literals
+            .extend(self.explanation_literals[start..self.explanation_literals.len()].iter().cloned());
+        self.explanation_literals.truncate(start);
+        return PropagationResult::Conflict { explain_key: IntegerExplainKey::Conflict.into() };
+    }
+}
+
+/// Lazy clause generation の整数変数の理論
+///
+/// 整数変数は順序リテラル [x <= v] で，all_different・element に現れる整数変数は等号リテラル [x = v] でも表す．
+/// 等号リテラルは読み込み時に作り，順序リテラルは上下限が初めて v に達したときに内側のエンジンに変数を作らせて加える．
+/// 順序リテラルの間の連鎖と，等号リテラルと上下限の関係は理論が伝播し，それぞれを表す節で説明する．
+/// 線形不等式は上下限の伝播を，all_different は値の除去を，element は定義域の無矛盾性を保つ伝播を行い，
+/// いずれも推論に用いた上下限・値のリテラルからなる節で説明する．
+/// 上下限は順序リテラルの値から求め，作ったリテラルはバックジャンプしても残すので，戻す状態は伝播の説明だけである．
+#[derive(Default, Clone)]
+pub struct IntegerTheory {
+    variables: Array<u32, IntegerVariable>,
+    constraints: Array<u32, IntegerConstraint>,
+    /// 整数変数 -> その整数変数を含む制約
+    constraints_of_variable: Array<u32, Array<u32, u32>>,
+    /// 知らされた変数の数
+    number_of_variables: u32,
+    /// 変数 -> (整数変数, 値, 等号リテラルであるか否か)
+    owners: Array<u32, Option<(u32, i64, bool)>>,
+    /// 変数 -> 整数変数のリテラルか，それと同値であると宣言されたリテラルの変数であるか否か
+    is_integer_literal_variable: Array<u32, bool>,
+    /// 線形不等式の各項の上下限
+    bounds: Array<u32, (i64, i64)>,
+    explanations: Explanations,
+    summary: IntegerTheorySummary,
+}
+
+impl IntegerTheory {
+    pub fn number_of_integer_variables(&self) -> u32 {
+        return self.variables.len();
+    }
+
+    /// 値が lower 以上 upper 以下の整数変数を加える．順序リテラルは必要になったときに作る
+    pub fn add_integer_variable(&mut self, lower: i64, upper: i64) -> u32 {
+        debug_assert!(lower <= upper);
+        let variable = self.variables.len();
+        self.variables.push(IntegerVariable {
+            lower: lower,
+            upper: upper,
+            order_literals: Array::default(),
+            equality_literals: Array::default(),
+        });
+        self.constraints_of_variable.push(Array::default());
+        self.summary.number_of_integer_variables += 1;
+        return variable;
+    }
+
+    /// 作成済みの等号リテラル [x = lower], ..., [x = upper] を加える
+    pub fn set_equality_literals(&mut self, variable: u32, equality_literals: Array<u32, Literal>) {
+        let lower = self.variables[variable].lower;
+        debug_assert!(equality_literals.len() as i64 == self.variables[variable].upper - lower + 1);
+        for (k, &literal) in equality_literals.iter().enumerate() {
+            self.register_literal(literal, (variable, lower + k as i64, true));
+        }
+        self.variables[variable].equality_literals = equality_literals;
+    }
+
+    pub fn has_equality_literals(&self, variable: u32) -> bool {
+        return !self.variables[variable].equality_literals.is_empty();
+    }
+
+    /// 整数変数の定義域 (下限, 上限)
+    pub fn domain(&self, variable: u32) -> (i64, i64) {
+        return (self.variables[variable].lower, self.variables[variable].upper);
+    }
+
+    /// 作成済みであれば [variable <= value] を表すリテラル
+    pub fn order_literal(&self, variable: u32, value: i64) -> Option<Literal> {
+        return self.variables[variable].order_literal(value);
+    }
+
+    /// 作成済みの変数のリテラルを [variable <= value] を表す順序リテラルとして加える．lower <= value < upper でなければならない
+    pub fn insert_order_literal(&mut self, variable: u32, value: i64, literal: Literal) {
+        debug_assert!(self.variables[variable].lower <= value && value < self.variables[variable].upper);
+        let Err(position) = self.variables[variable].find_order_literal(value) else {
+            unreachable!();
+        };
+        self.variables[variable].order_literals.insert(position as u32, (value, literal));
+        self.register_literal(literal, (variable, value, false));
+    }
+
+    /// 整数変数のリテラルと同値であると宣言されたリテラルを登録する
+    pub fn add_order_literal(&mut self, order_literal: &OrderLiteral) {
+        self.is_integer_literal_variable[order_literal.literal.index()] = true;
+    }
+
+    /// 現在の割り当てにおける下限．final_check を通った割り当てでは整数変数の値になる
+    pub fn value(&self, variable: u32, engine: &impl EngineTrait) -> i64 {
+        return self.variables[variable].lower_bound(engine);
+    }
+
+    /// リテラルの変数を整数変数のリテラルとして登録する
+    ///
+    /// 理論が作らせた変数は OuterEngine が知らせる前に登録するので，配列が足りなければ伸ばす
+    fn register_literal(&mut self, literal: Literal, owner: (u32, i64, bool)) {
+        if self.owners.len() <= literal.index() {
+            self.owners.resize(literal.index() + 1, None);
+            self.is_integer_literal_variable.resize(literal.index() + 1, false);
+        }
+        self.owners[literal.index()] = Some(owner);
+        self.is_integer_literal_variable[literal.index()] = true;
+    }
+
+    /// [variable <= value] を表すリテラル．まだなければ内側のエンジンに変数を作らせて加える
+    ///
+    /// 隣り合う順序リテラルとの連鎖の節は，いずれかが割り当てられたときに伝播する．lower <= value < upper でなければならない
+    fn get_or_add_order_literal(&mut self, variable: u32, value: i64, engine: &mut impl EngineTrait) -> Literal {
+        if let Some(literal) = self.variables[variable].order_literal(value) {
+            return literal;
+        }
+        engine.add_variable(Boolean::FALSE);
+        let literal = Literal::new(engine.number_of_variables() - 1, Boolean::TRUE);
+        self.insert_order_literal(variable, value, literal);
+        return literal;
+    }
+
+    /// 順序リテラル [variable <= value] の割り当てを，隣り合う順序リテラルと範囲外になった値の等号リテラルに伝播する
+    fn propagate_order_literal<EngineT>(
+        &mut self,
+        variable: u32,
+        value: i64,
+        is_true: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        let integer_variable = &self.variables[variable];
+        let position = integer_variable.find_order_literal(value).unwrap();
+        let order_literals = integer_variable.order_literals.as_slice();
+        // [x <= value] が真なら [x <= 次の値] が真で，(value, 次の値] の値は取らない．
+        // 偽なら [x <= 前の値] が偽で，(前の値, value] の値は取らない．その先の値は隣の順序リテラルが伝播する
+        let (false_literal, neighbor, values) = if is_true {
+            let next = order_literals.get(position + 1).cloned();
+            (
+                !order_literals[position].1,
+                next.map(|(_, literal)| literal),
+                value + 1..=next.map_or(integer_variable.upper, |(v, _)| v),
+            )
+        } else {
+            let previous = if position > 0 { Some(order_literals[position - 1]) } else { None };
+            (
+                order_literals[position].1,
+                previous.map(|(_, literal)| !literal),
+                previous.map_or(integer_variable.lower, |(v, _)| v + 1)..=value,
+            )
+        };
+        if let Some(neighbor) = neighbor {
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(false_literal);
+            let result = self.explanations.imply(neighbor, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        if integer_variable.equality_literals.is_empty() {
+            return PropagationResult::Noconflict;
+        }
+        for v in values {
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(false_literal);
+            let result = self.explanations.imply(!integer_variable.equality_literal(v), start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// 等号リテラル [variable = value] の割り当てを上下限に伝播する
+    fn propagate_equality_literal<EngineT>(
+        &mut self,
+        variable: u32,
+        value: i64,
+        is_true: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        if !is_true {
+            return self.propagate_equality_bounds(variable, engine, undo_log);
+        }
+        // [x = value] -> [x <= value], [x = value] -> ![x <= value - 1]
+        let (lower, upper) = self.domain(variable);
+        let equality_literal = self.variables[variable].equality_literal(value);
+        if value < upper {
+            let literal = self.get_or_add_order_literal(variable, value, engine);
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(!equality_literal);
+            let result = self.explanations.imply(literal, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        if value > lower {
+            let literal = self.get_or_add_order_literal(variable, value - 1, engine);
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(!equality_literal);
+            let result = self.explanations.imply(!literal, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// 上下限と等号リテラルの関係 [x <= v] & ![x <= v - 1] -> [x = v] を伝播する
+    ///
+    /// 上下限が一致すればその値の等号リテラルを真にし，下限・上限の値の等号リテラルが偽であれば上下限を狭める
+    fn propagate_equality_bounds<EngineT>(
+        &mut self,
+        variable: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        let integer_variable = &self.variables[variable];
+        let lower_bound = integer_variable.lower_bound(engine);
+        let upper_bound = integer_variable.upper_bound(engine);
+        if lower_bound > upper_bound {
+            // 連鎖の節の伝播が済んでいない．いずれ連鎖の節が矛盾する
+            return PropagationResult::Noconflict;
+        }
+        let lower_bound_reason = integer_variable.lower_bound_reason(lower_bound);
+        let upper_bound_reason = integer_variable.upper_bound_reason(upper_bound);
+        let lower_equality_literal = integer_variable.equality_literal(lower_bound);
+        let upper_equality_literal = integer_variable.equality_literal(upper_bound);
+        if lower_bound == upper_bound {
+            let start = self.explanations.explanation_literals.len();
+            self.explanations
+                .explanation_literals
+                .extend(lower_bound_reason.into_iter().chain(upper_bound_reason));
+            return self.explanations.imply(lower_equality_literal, start, engine, undo_log);
+        }
+        if engine.is_false(lower_equality_literal) {
+            // x >= lower_bound かつ x != lower_bound なので ![x <= lower_bound]
+            let literal = self.get_or_add_order_literal(variable, lower_bound, engine);
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(lower_equality_literal);
+            self.explanations.explanation_literals.extend(lower_bound_reason);
+            let result = self.explanations.imply(!literal, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        if engine.is_false(upper_equality_literal) {
+            // x <= upper_bound かつ x != upper_bound なので [x <= upper_bound - 1]
+            let literal = self.get_or_add_order_literal(variable, upper_bound - 1, engine);
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(upper_equality_literal);
+            self.explanations.explanation_literals.extend(upper_bound_reason);
+            let result = self.explanations.imply(literal, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// 線形不等式の excluded 番目以外の項の，最小値を与える上下限の理由を reasons に積む
+    fn push_linear_reasons(
+        terms: &Array<u32, (i64, u32)>,
+        variables: &Array<u32, IntegerVariable>,
+        bounds: &Array<u32, (i64, i64)>,
+        excluded: u32,
+        reasons: &mut Array<u32, Literal>,
+    ) {
+        for (k, &(coefficient, variable)) in terms.iter().enumerate() {
+            if k as u32 == excluded {
+                continue;
+            }
+            let (lower_bound, upper_bound) = bounds[k as u32];
+            let reason = if coefficient > 0 {
+                variables[variable].lower_bound_reason(lower_bound)
+            } else {
+                variables[variable].upper_bound_reason(upper_bound)
+            };
+            reasons.extend(reason);
+        }
+    }
+
+    fn propagate_linear<EngineT>(
+        &mut self,
+        constraint_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        let IntegerConstraint::Linear { terms, bound } = &self.constraints[constraint_id] else {
+            unreachable!();
+        };
+        // slack = bound - (各項の最小値の和)
+        self.bounds.clear();
+        let mut slack = *bound;
+        for &(coefficient, variable) in terms.iter() {
+            let lower_bound = self.variables[variable].lower_bound(engine);
+            let upper_bound = self.variables[variable].upper_bound(engine);
+            self.bounds.push((lower_bound, upper_bound));
+            slack -= if coefficient > 0 { coefficient * lower_bound } else { coefficient * upper_bound };
+        }
+        if slack < 0 {
+            self.explanations.conflicting_literals.clear();
+            Self::push_linear_reasons(
+                terms,
+                &self.variables,
+                &self.bounds,
+                u32::MAX,
+                &mut self.explanations.conflicting_literals,
+            );
+            return PropagationResult::Conflict { explain_key: IntegerExplainKey::Conflict.into() };
+        }
+        for k in 0..terms.len() {
+            let IntegerConstraint::Linear { terms, .. } = &self.constraints[constraint_id] else {
+                unreachable!();
+            };
+            let (coefficient, variable) = terms[k];
+            let (lower_bound, upper_bound) = self.bounds[k];
+            // 新たな上限 v は [x <= v] を，新たな下限 v は ![x <= v - 1] を伝播する
+            let (value, is_upper_bound) = if coefficient > 0 {
+                let new_upper_bound = lower_bound + slack / coefficient;
+                if new_upper_bound >= upper_bound {
+                    continue;
+                }
+                (new_upper_bound, true)
+            } else {
+                let new_lower_bound = upper_bound - slack / -coefficient;
+                if new_lower_bound <= lower_bound {
+                    continue;
+                }
+                (new_lower_bound - 1, false)
+            };
+            let start = self.explanations.explanation_literals.len();
+            Self::push_linear_reasons(
+                terms,
+                &self.variables,
+                &self.bounds,
+                k,
+                &mut self.explanations.explanation_literals,
+            );
+            let literal = self.get_or_add_order_literal(variable, value, engine);
+            let result =
+                self.explanations.imply(if is_upper_bound { literal } else { !literal }, start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// variable = value になったので，他の整数変数から value を除く
+    fn propagate_all_different<EngineT>(
+        &mut self,
+        constraint_id: u32,
+        variable: u32,
+        value: i64,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        let IntegerConstraint::AllDifferent { variables: all_different_variables } = &self.constraints[constraint_id]
+        else {
+            unreachable!();
+        };
+        let equality_literal = self.variables[variable].equality_literal(value);
+        for &other_variable in all_different_variables.iter() {
+            let other = &self.variables[other_variable];
+            if other_variable == variable || value < other.lower || value > other.upper {
+                continue;
+            }
+            let start = self.explanations.explanation_literals.len();
+            self.explanations.explanation_literals.push(!equality_literal);
+            let result = self.explanations.imply(!other.equality_literal(value), start, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// values[index] の値を取り得ない index と，どの index からも得られない result の値を除く
+    fn propagate_element<EngineT>(
+        &mut self,
+        constraint_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        let IntegerConstraint::Element { index, result, values } = &self.constraints[constraint_id] else {
+            unreachable!();
+        };
+        let index = &self.variables[*index];
+        let result = &self.variables[*result];
+        let explanations = &mut self.explanations;
+        for j in index.lower..=index.upper {
+            let index_literal = index.equality_literal(j);
+            if engine.is_false(index_literal) {
+                continue;
+            }
+            let start = explanations.explanation_literals.len();
+            if 0 <= j && j < values.len() as i64 && result.lower <= values[j as u32] && values[j as u32] <= result.upper
+            {
+                let result_literal = result.equality_literal(values[j as u32]);
+                if !engine.is_false(result_literal) {
+                    continue;
+                }
+                explanations.explanation_literals.push(result_literal);
+            }
+            let propagation_result = explanations.imply(!index_literal, start, engine, undo_log);
+            if propagation_result.is_conflict() {
+                return propagation_result;
+            }
+        }
+        let first = index.lower.max(0);
+        let last = index.upper.min(values.len() as i64 - 1);
+        for v in result.lower..=result.upper {
+            let result_literal = result.equality_literal(v);
+            if engine.is_false(result_literal) {
+                continue;
+            }
+            let start = explanations.explanation_literals.len();
+            let mut is_supported = false;
+            for j in first..=last {
+                if values[j as u32] != v {
+                    continue;
+                }
+                let index_literal = index.equality_literal(j);
+                if !engine.is_false(index_literal) {
+                    is_supported = true;
+                    break;
+                }
+                explanations.explanation_literals.push(index_literal);
+            }
+            if is_supported {
+                explanations.explanation_literals.truncate(start);
+                continue;
+            }
+            let propagation_result = explanations.imply(!result_literal, start, engine, undo_log);
+            if propagation_result.is_conflict() {
+                return propagation_result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn propagate_constraint<EngineT>(
+        &mut self,
+        constraint_id: u32,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<IntegerExplainKey>,
+    {
+        return match &self.constraints[constraint_id] {
+            IntegerConstraint::Linear { .. } => self.propagate_linear(constraint_id, engine, undo_log),
+            IntegerConstraint::AllDifferent { variables } => {
+                for k in 0..variables.len() {
+                    let IntegerConstraint::AllDifferent { variables } = &self.constraints[constraint_id] else {
+                        unreachable!();
+                    };
+                    let variable = variables[k];
+                    let lower_bound = self.variables[variable].lower_bound(engine);
+                    if lower_bound != self.variables[variable].upper_bound(engine) {
+                        continue;
+                    }
+                    let result = self.propagate_all_different(constraint_id, variable, lower_bound, engine, undo_log);
+                    if result.is_conflict() {
+                        return result;
+                    }
+                }
+                PropagationResult::Noconflict
+            }
+            IntegerConstraint::Element { .. } => self.propagate_element(constraint_id, engine, undo_log),
+        };
+    }
+}
+
+impl TheoryTrait for IntegerTheory {
+    type ExplainKey = IntegerExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = IntegerTheorySummary;
+    /// 伝播の説明を explanation_literals に積んだ区間の始まり
+    type UndoEntry = u32;
+
+    fn add_variable(&mut self) {
+        self.number_of_variables += 1;
+        // 理論が作らせた変数は作ったときに登録してある
+        if self.owners.len() < self.number_of_variables {
+            self.owners.push(None);
+            self.is_integer_literal_variable.push(false);
+        }
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        let Some((variable, value, is_equality)) = self.owners[assigned_literal.index()] else {
+            return PropagationResult::Noconflict;
+        };
+        let integer_variable = &self.variables[variable];
+        let literal = if is_equality {
+            integer_variable.equality_literal(value)
+        } else {
+            integer_variable.order_literal(value).unwrap()
+        };
+        let is_true = assigned_literal == literal;
+        let has_equality_literals = !integer_variable.equality_literals.is_empty();
+        let result = if is_equality {
+            self.propagate_equality_literal(variable, value, is_true, engine, undo_log)
+        } else {
+            self.propagate_order_literal(variable, value, is_true, engine, undo_log)
+        };
+        if result.is_conflict() {
+            return result;
+        }
+        // 順序リテラルは上下限が変わったときだけ伝播する(連鎖の節によって弱い順序リテラルも割り当てられるため)
+        let integer_variable = &self.variables[variable];
+        let is_bound_changed = !is_equality
+            && if is_true {
+                integer_variable.upper_bound(engine) == value
+            } else {
+                integer_variable.lower_bound(engine) == value + 1
+            };
+        if is_bound_changed && has_equality_literals {
+            let result = self.propagate_equality_bounds(variable, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        for k in 0..self.constraints_of_variable[variable].len() {
+            let constraint_id = self.constraints_of_variable[variable][k];
+            let result = match &self.constraints[constraint_id] {
+                IntegerConstraint::Linear { .. } if is_bound_changed => {
+                    self.propagate_linear(constraint_id, engine, undo_log)
+                }
+                IntegerConstraint::AllDifferent { .. } if is_equality && is_true => {
+                    self.propagate_all_different(constraint_id, variable, value, engine, undo_log)
+                }
+                IntegerConstraint::Element { .. } if is_equality => {
+                    self.propagate_element(constraint_id, engine, undo_log)
+                }
+                _ => PropagationResult::Noconflict,
+            };
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn explain_propagation(&self, explain_key: IntegerExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            IntegerExplainKey::Propagation { literal, start, end } => ClauseView::new(Either::Left(
+                [literal].into_iter().chain(self.explanations.explanation_literals[start..end].iter().cloned()),
+            )),
+            IntegerExplainKey::Conflict => {
+                ClauseView::new(Either::Right(self.explanations.conflicting_literals.iter().cloned()))
+            }
+        };
+    }
+
+    fn undo(&mut self, start: u32) {
+        self.explanations.explanation_literals.truncate(start);
+    }
+
+    fn final_check<EngineT>(
+        &mut self,
+        engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        // 上下限の間に順序リテラルがなく値が決まらない整数変数は，下限の値の順序リテラルを作って探索で決めさせる
+        for variable in 0..self.variables.len() {
+            let lower_bound = self.variables[variable].lower_bound(engine);
+            if lower_bound < self.variables[variable].upper_bound(engine) {
+                self.get_or_add_order_literal(variable, lower_bound, engine);
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    /// 変数が整数変数のリテラルであるか，それと同値であると宣言されているか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return self.is_integer_literal_variable[index];
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        let mut summary = self.summary.clone();
+        summary.number_of_integer_propagations = self.explanations.number_of_propagations;
+        return summary;
+    }
+}
+
+impl TheoryAddConstraintTrait<IntegerConstraint> for IntegerTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        constraint: IntegerConstraint,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        let constraint_id = self.constraints.len();
+        let mut variables = match &constraint {
+            IntegerConstraint::Linear { terms, .. } => Array::from_iter(terms.iter().map(|&(_, variable)| variable)),
+            IntegerConstraint::AllDifferent { variables } => variables.clone(),
+            IntegerConstraint::Element { index, result, .. } => Array::from_iter([*index, *result].into_iter()),
+        };
+        variables.sort_unstable_by(|lhs, rhs| lhs.cmp(rhs));
+        let has_duplicates = variables.as_slice().windows(2).any(|pair| pair[0] == pair[1]);
+        if has_duplicates && let IntegerConstraint::AllDifferent { .. } = &constraint {
+            // 同じ整数変数が 2 回現れる all_different は充足不能
+            self.explanations.conflicting_literals.clear();
+            return PropagationResult::Conflict { explain_key: IntegerExplainKey::Conflict.into() };
+        }
+        for (k, &variable) in variables.iter().enumerate() {
+            if k == 0 || variables[k as u32 - 1] != variable {
+                self.constraints_of_variable[variable].push(constraint_id);
+            }
+        }
+        self.constraints.push(constraint);
+        self.summary.number_of_integer_constraints += 1;
+        // 定義域が 1 点の整数変数などは，上下限が変わらなくても等号リテラルを定める
+        for &variable in variables.iter() {
+            if !self.has_equality_literals(variable) {
+                continue;
+            }
+            let result = self.propagate_equality_bounds(variable, engine, undo_log);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return self.propagate_constraint(constraint_id, engine, undo_log);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+    use utility::Array;
+
+    use super::{IntegerConstraint, IntegerExplainKey, IntegerTheory};
+    use crate::theory_test::{
+        TestEngine, TestedExplainKey, TestedTheory, add_constraint, assert_explanations_are_valid, check_random_search,
+        sorted,
+    };
+
+    impl TestedExplainKey for IntegerExplainKey {}
+
+    impl TestedTheory for IntegerTheory {
+        type Solution = Vec<i64>;
+
+        /// 整数変数の値が values のときにリテラルが真であるか
+        fn holds(&self, literal: Literal, values: &Vec<i64>) -> bool {
+            let (variable, value, is_equality) = self.owners[literal.index()].unwrap();
+            let is_true =
+                if is_equality { values[variable as usize] == value } else { values[variable as usize] <= value };
+            return is_true == (literal.value() == Boolean::TRUE);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> Vec<i64> {
+            return Vec::from_iter(
+                (0..self.number_of_integer_variables()).map(|variable| self.value(variable, engine)),
+            );
+        }
+
+        fn checkpoint(&self) -> u32 {
+            return self.explanations.explanation_literals.len();
+        }
+
+        /// 順序リテラルが連鎖の節に従って [x <= v] が真なら [x <= v + 1] も真になっている
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for variable in self.variables.iter() {
+                for pair in variable.order_literals.as_slice().windows(2) {
+                    assert!(!engine.is_true(pair[0].1) || engine.is_true(pair[1].1));
+                    assert!(!engine.is_false(pair[1].1) || engine.is_false(pair[0].1));
+                }
+            }
+        }
+    }
+
+    fn add_literal(engine: &mut TestEngine<IntegerTheory>) -> Literal {
+        engine.add_variable(Boolean::FALSE);
+        return Literal::new(engine.number_of_variables() - 1, Boolean::TRUE);
+    }
+
+    /// 定義域が domains の整数変数を加え，equality_variables の整数変数には等号リテラルも作る
+    fn make_engine(domains: &[(i64, i64)], equality_variables: &[u32]) -> TestEngine<IntegerTheory> {
+        let mut engine = crate::theory_test::make_engine(IntegerTheory::default(), 0);
+        for (variable, &(lower, upper)) in domains.iter().enumerate() {
+            engine.theory_mut().add_integer_variable(lower, upper);
+            if equality_variables.contains(&(variable as u32)) {
+                let equality_literals = Array::from_iter((lower..=upper).map(|_| add_literal(&mut engine)));
+                engine.theory_mut().set_equality_literals(variable as u32, equality_literals);
+            }
+        }
+        return engine;
+    }
+
+    /// [variable <= value] を返し，なければ作る
+    fn order_literal(engine: &mut TestEngine<IntegerTheory>, variable: u32, value: i64) -> Literal {
+        if let Some(literal) = engine.theory().order_literal(variable, value) {
+            return literal;
+        }
+        let literal = add_literal(engine);
+        engine.theory_mut().insert_order_literal(variable, value, literal);
+        return literal;
+    }
+
+    fn equality_literal(engine: &TestEngine<IntegerTheory>, variable: u32, value: i64) -> Literal {
+        return engine.theory().variables[variable].equality_literal(value);
+    }
+
+    fn reason_clause(engine: &mut TestEngine<IntegerTheory>, literal: Literal) -> Vec<Literal> {
+        return sorted(crate::theory_test::reason_clause(engine, literal).unwrap());
+    }
+
+    fn is_satisfied(constraint: &IntegerConstraint, values: &[i64]) -> bool {
+        return match constraint {
+            IntegerConstraint::Linear { terms, bound } => {
+                terms.iter().map(|&(coefficient, variable)| coefficient * values[variable as usize]).sum::<i64>()
+                    <= *bound
+            }
+            IntegerConstraint::AllDifferent { variables } => variables.iter().enumerate().all(|(k, &variable)| {
+                variables.iter().skip(k + 1).all(|&other| values[variable as usize] != values[other as usize])
+            }),
+            IntegerConstraint::Element { index, result, values: element_values } => {
+                let index = values[*index as usize];
+                0 <= index
+                    && index < element_values.len() as i64
+                    && element_values[index as u32] == values[*result as usize]
+            }
+        };
+    }
+
+    /// 全ての制約を充足する整数変数の値の組
+    fn enumerate_solutions(domains: &[(i64, i64)], constraints: &[IntegerConstraint]) -> Vec<Vec<i64>> {
+        let mut solutions = Vec::new();
+        let mut values = Vec::from_iter(domains.iter().map(|&(lower, _)| lower));
+        loop {
+            if constraints.iter().all(|constraint| is_satisfied(constraint, &values)) {
+                solutions.push(values.clone());
+            }
+            let Some(k) = (0..domains.len()).find(|&k| values[k] < domains[k].1) else {
+                return solutions;
+            };
+            values[k] += 1;
+            for j in 0..k {
+                values[j] = domains[j].0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_linear_propagates_bounds_with_lazily_created_order_literals() {
+        // x + y <= 3, 0 <= x, y <= 5
+        let domains = [(0, 5), (0, 5)];
+        let constraint = IntegerConstraint::Linear { terms: Array::from_iter([(1, 0), (1, 1)].into_iter()), bound: 3 };
+        let solutions = enumerate_solutions(&domains, std::slice::from_ref(&constraint));
+        let mut engine = make_engine(&domains, &[]);
+        assert_eq!(engine.number_of_variables(), 0);
+        assert!(add_constraint(&mut engine, constraint).is_no_conflict());
+        // 根で [x <= 3], [y <= 3] だけを作って伝播する
+        assert_eq!(engine.number_of_variables(), 2);
+        let x3 = engine.theory().order_literal(0, 3).unwrap();
+        let y3 = engine.theory().order_literal(1, 3).unwrap();
+        assert!(engine.is_true(x3) && engine.is_true(y3));
+        assert!(reason_clause(&mut engine, x3) == vec![x3]);
+        // x >= 2 から [y <= 1] を作り，[x <= 1] が偽であることで説明する
+        let x1 = order_literal(&mut engine, 0, 1);
+        assert!(engine.assign(!x1, Reason::Decision).is_no_conflict());
+        let y1 = engine.theory().order_literal(1, 1).unwrap();
+        assert!(engine.is_true(y1));
+        assert!(reason_clause(&mut engine, y1) == sorted(vec![x1, y1]));
+        // [y <= 1] から連鎖の節で [y <= 3] が従う
+        assert!(engine.theory().variables[1].order_literals.iter().all(|&(_, literal)| engine.is_true(literal)));
+        assert_explanations_are_valid(&mut engine, &solutions);
+    }
+
+    #[test]
+    fn test_all_different_removes_value_and_fixes_remaining_value() {
+        // all_different(x, y), 0 <= x, y <= 2
+        let domains = [(0, 2), (0, 2)];
+        let constraint = IntegerConstraint::AllDifferent { variables: Array::from_iter([0, 1].into_iter()) };
+        let solutions = enumerate_solutions(&domains, std::slice::from_ref(&constraint));
+        let mut engine = make_engine(&domains, &[0, 1]);
+        assert!(add_constraint(&mut engine, constraint).is_no_conflict());
+        let x1 = equality_literal(&engine, 0, 1);
+        let y1 = equality_literal(&engine, 1, 1);
+        assert!(engine.assign(x1, Reason::Decision).is_no_conflict());
+        assert!(engine.is_false(y1));
+        assert!(reason_clause(&mut engine, !y1) == sorted(vec![!x1, !y1]));
+        // y != 0 と y != 1 から下限が 2 に上がり y = 2 が従う
+        let y0 = equality_literal(&engine, 1, 0);
+        assert!(engine.assign(!y0, Reason::Decision).is_no_conflict());
+        assert!(engine.is_true(equality_literal(&engine, 1, 2)));
+        assert_eq!(engine.theory().value(1, &engine), 2);
+        assert_explanations_are_valid(&mut engine, &solutions);
+    }
+
+    #[test]
+    fn test_element_keeps_supported_values() {
+        // r = [4, 1, 4][i], 0 <= i <= 2, 0 <= r <= 5
+        let domains = [(0, 2), (0, 5)];
+        let constraint =
+            IntegerConstraint::Element { index: 0, result: 1, values: Array::from_iter([4, 1, 4].into_iter()) };
+        let solutions = enumerate_solutions(&domains, std::slice::from_ref(&constraint));
+        let mut engine = make_engine(&domains, &[0, 1]);
+        assert!(add_constraint(&mut engine, constraint).is_no_conflict());
+        // どの i からも得られない値は根で除く
+        for v in [0, 2, 3, 5] {
+            let literal = equality_literal(&engine, 1, v);
+            assert!(engine.is_false(literal));
+        }
+        let r0 = equality_literal(&engine, 1, 0);
+        assert!(reason_clause(&mut engine, !r0) == vec![!r0]);
+        // r != 4 なら values[i] = 4 となる i を除き，残る i = 1 から r = 1 が従う
+        let r4 = equality_literal(&engine, 1, 4);
+        let i0 = equality_literal(&engine, 0, 0);
+        assert!(engine.assign(!r4, Reason::Decision).is_no_conflict());
+        assert!(engine.is_false(i0));
+        assert!(reason_clause(&mut engine, !i0) == sorted(vec![!i0, r4]));
+        assert!(engine.is_true(equality_literal(&engine, 0, 1)));
+        assert!(engine.is_true(equality_literal(&engine, 1, 1)));
+        assert_eq!(engine.theory().value(0, &engine), 1);
+        assert_eq!(engine.theory().value(1, &engine), 1);
+        assert_explanations_are_valid(&mut engine, &solutions);
+    }
+
+    fn random_constraint(rng: &mut SmallRng, number_of_integer_variables: u32) -> IntegerConstraint {
+        return match rng.gen_range(0..3) {
+            0 => IntegerConstraint::Linear {
+                terms: Array::from_iter((0..rng.gen_range(1..4)).map(|_| {
+                    (
+                        rng.gen_range(1..4) * if rng.gen_bool(0.5) { 1 } else { -1 },
+                        rng.gen_range(0..number_of_integer_variables),
+                    )
+                })),
+                bound: rng.gen_range(-4..6),
+            },
+            1 => {
+                let mut variables = Array::from_iter(0..number_of_integer_variables);
+                for k in 0..variables.len() {
+                    variables.swap(k, rng.gen_range(k..variables.len()));
+                }
+                variables.truncate(rng.gen_range(2..(number_of_integer_variables + 1)));
+                IntegerConstraint::AllDifferent { variables: variables }
+            }
+            _ => IntegerConstraint::Element {
+                index: rng.gen_range(0..number_of_integer_variables),
+                result: rng.gen_range(0..number_of_integer_variables),
+                values: Array::from_iter((0..rng.gen_range(1..4)).map(|_| rng.gen_range(-1..4))),
+            },
+        };
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(13);
+        for _ in 0..500 {
+            let number_of_integer_variables = rng.gen_range(2..4);
+            let domains = Vec::from_iter((0..number_of_integer_variables).map(|_| {
+                let lower = rng.gen_range(-1..2);
+                (lower, lower + rng.gen_range(0..4))
+            }));
+            let constraints = Vec::from_iter(
+                (0..rng.gen_range(1..4)).map(|_| random_constraint(&mut rng, number_of_integer_variables)),
+            );
+            let solutions = enumerate_solutions(&domains, &constraints);
+            let mut equality_variables = Vec::new();
+            for constraint in constraints.iter() {
+                match constraint {
+                    IntegerConstraint::Linear { .. } => {}
+                    IntegerConstraint::AllDifferent { variables } => {
+                        equality_variables.extend(variables.iter().cloned())
+                    }
+                    IntegerConstraint::Element { index, result, .. } => equality_variables.extend([*index, *result]),
+                }
+            }
+            let mut engine = make_engine(&domains, &equality_variables);
+            let mut result = PropagationResult::Noconflict;
+            for constraint in constraints.iter() {
+                result = add_constraint(&mut engine, constraint.clone());
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
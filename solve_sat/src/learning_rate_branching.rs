@@ -0,0 +1,115 @@
+use cdcl_engine::EngineTrait;
+use utility::{Array, HeapedMap};
+
+use crate::decision_heuristic::{CompareValues, DecisionHeuristic};
+
+/// LRB (learning-rate branching)
+///
+/// 変数が割り当てられてから取り消されるまでの矛盾の数を interval とし，その間に矛盾の解析に現れた回数(participation)と
+/// 学習節のリテラルの割り当て理由に現れた回数(reason side)を interval で割った値を報酬として，
+/// 取り消されるたびに活性度を報酬の指数移動平均に近づける．
+/// 割り当てた時刻は，矛盾のたびに割り当て列の末尾からまだ記録していない変数に記録する．
+#[derive(Clone)]
+pub struct LearningRateBranching {
+    step_size: f64,
+    min_step_size: f64,
+    step_size_decrease_value: f64,
+    activities: Array<u32, f64>,
+    /// 変数 -> 割り当てられたときの矛盾の数(記録していなければ NULL)
+    assigned_conflict_counts: Array<u32, u64>,
+    participated_counts: Array<u32, u32>,
+    reasoned_counts: Array<u32, u32>,
+    conflict_count: u64,
+    unassigned_variable_queue: HeapedMap<u32, f64, CompareValues>,
+}
+
+impl LearningRateBranching {
+    const NULL: u64 = u64::MAX;
+
+    pub fn new(step_size: f64, min_step_size: f64, step_size_decrease_value: f64) -> Self {
+        Self {
+            step_size: step_size,
+            min_step_size: min_step_size,
+            step_size_decrease_value: step_size_decrease_value,
+            activities: Array::default(),
+            assigned_conflict_counts: Array::default(),
+            participated_counts: Array::default(),
+            reasoned_counts: Array::default(),
+            conflict_count: 0,
+            unassigned_variable_queue: HeapedMap::default(),
+        }
+    }
+}
+
+impl DecisionHeuristic for LearningRateBranching {
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
+        let index = self.activities.len();
+        self.activities.push(initial_activity);
+        self.assigned_conflict_counts.push(Self::NULL);
+        self.participated_counts.push(0);
+        self.reasoned_counts.push(0);
+        if !is_assigned {
+            self.unassigned_variable_queue.insert(index, initial_activity);
+        }
+    }
+
+    fn set_to_unassigned(&mut self, index: u32) {
+        let assigned_conflict_count = self.assigned_conflict_counts[index];
+        if assigned_conflict_count != Self::NULL {
+            // 割り当てられていた間の報酬で活性度を更新する
+            let interval = self.conflict_count - assigned_conflict_count;
+            if interval > 0 {
+                let reward = (self.participated_counts[index] + self.reasoned_counts[index]) as f64 / interval as f64;
+                self.activities[index] = (1.0 - self.step_size) * self.activities[index] + self.step_size * reward;
+            }
+            self.assigned_conflict_counts[index] = Self::NULL;
+            self.participated_counts[index] = 0;
+            self.reasoned_counts[index] = 0;
+        }
+        if !self.unassigned_variable_queue.contains_key(index) {
+            let activity = self.activities[index];
+            self.unassigned_variable_queue.insert(index, activity);
+        }
+    }
+
+    fn peek(&self) -> Option<u32> {
+        return self.unassigned_variable_queue.first().map(|(&index, _)| index);
+    }
+
+    fn activity(&self, index: u32) -> f64 {
+        return self.activities[index];
+    }
+
+    fn set_to_assigned(&mut self, index: u32) {
+        self.unassigned_variable_queue.remove(index);
+    }
+
+    fn increase_price(
+        &mut self,
+        related_variables: impl Iterator<Item = u32>,
+        reason_side_variables: impl Iterator<Item = u32>,
+        engine: &impl EngineTrait,
+        _backjump_level: u32,
+    ) {
+        // 前回の矛盾の後に割り当てられた変数は，割り当て列の末尾に並んでいる
+        for assignment_order in (0..engine.number_of_assigneds()).rev() {
+            let index = engine.get_assignment(assignment_order).index();
+            if self.assigned_conflict_counts[index] != Self::NULL {
+                break;
+            }
+            self.assigned_conflict_counts[index] = self.conflict_count;
+        }
+        self.conflict_count += 1;
+        for index in related_variables {
+            self.participated_counts[index] += 1;
+        }
+        for index in reason_side_variables {
+            self.reasoned_counts[index] += 1;
+        }
+        self.step_size = f64::max(self.min_step_size, self.step_size - self.step_size_decrease_value);
+    }
+
+    fn is_reason_side_rate_used(&self) -> bool {
+        return true;
+    }
+}
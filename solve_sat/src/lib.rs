@@ -0,0 +1,56 @@
+#![feature(unboxed_closures)]
+#![feature(fn_traits)]
+#![feature(impl_trait_in_assoc_type)]
+
+mod acyclicity_theory;
+mod analyze;
+mod binary_clause_theory;
+mod blocked_clause_elimination;
+mod calculate_lbd;
+mod cardinality_theory;
+mod clause_database;
+mod clause_theory;
+mod cnf;
+mod decision_heuristic;
+mod difference_logic_theory;
+mod engine;
+mod external_propagator;
+mod gate_extraction;
+mod integer_theory;
+mod learning_rate_branching;
+mod mode_switching;
+mod model;
+mod options;
+mod phase;
+mod plbd_watcher;
+mod plbd_watcher2;
+mod preprocess;
+mod pricer;
+mod pricer2;
+mod probe;
+mod pseudo_boolean_analyze;
+mod pseudo_boolean_theory;
+mod read_cnf;
+mod reconstruction_stack;
+mod restart_policy;
+mod simplify;
+mod solve;
+mod symmetry;
+#[cfg(test)]
+mod theory_test;
+mod walk;
+mod write_cnf;
+mod xor_theory;
+
+pub use {
+    cnf::CNF,
+    external_propagator::ExternalPropagator,
+    gate_extraction::{Gate, GateKind},
+    model::{print_model, read_model},
+    options::{Mode, Options, PropagationOptions, SearchOptions},
+    preprocess::{Preprocess, PreprocessResult},
+    read_cnf::read_cnf,
+    reconstruction_stack::ReconstructionStack,
+    solve::{SolveResult, solve},
+    write_cnf::{format_literal, write_cnf},
+};
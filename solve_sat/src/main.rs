@@ -1,24 +1,118 @@
-#![feature(unboxed_closures)]
-#![feature(fn_traits)]
-#![feature(impl_trait_in_assoc_type)]
+use std::{fs, io};
 
-mod analyze;
-mod binary_clause_theory;
-mod calculate_lbd;
-mod clause_theory;
-mod cnf;
-mod engine;
-// mod plbd_watcher;
-mod plbd_watcher2;
-// mod pricer;
-mod pricer2;
-mod read_cnf;
-mod simplify;
-mod solve;
-use read_cnf::read_cnf;
-use solve::solve;
+use solve_sat::{
+    CNF, Gate, GateKind, Mode, Options, Preprocess, PreprocessResult, ReconstructionStack, format_literal, print_model,
+    read_cnf, read_model, solve, write_cnf,
+};
+use utility::Array;
+
+fn preprocess_only(
+    cnf: &CNF,
+    output_path: &str,
+    reconstruction_path: &str,
+    is_gate_printing_enabled: bool,
+    is_xor_detection_enabled: bool,
+) -> io::Result<()> {
+    let mut preprocess = Preprocess::new(100000000, is_xor_detection_enabled);
+    let (residual_cnf, reconstruction_stack) = match preprocess.preprocess(cnf) {
+        PreprocessResult::Unsatisfiable => {
+            // 空節だけからなる論理式を書き出す
+            let mut clauses = Array::default();
+            clauses.push(Array::default());
+            (
+                CNF {
+                    number_of_variables: cnf.number_of_variables,
+                    clauses: clauses,
+                    cardinality_constraints: Array::default(),
+                    pseudo_boolean_constraints: Array::default(),
+                    xor_constraints: Array::default(),
+                    acyclicity_edges: Array::default(),
+                    difference_atoms: Array::default(),
+                    integer_variables: Array::default(),
+                    order_literals: Array::default(),
+                    integer_constraints: Array::default(),
+                },
+                ReconstructionStack::default(),
+            )
+        }
+        PreprocessResult::Simplified { cnf, reconstruction_stack } => (*cnf, reconstruction_stack),
+    };
+    preprocess.print_summary();
+    if is_gate_printing_enabled {
+        print_gates(preprocess.gates());
+    }
+    println!("c residual clauses: {}", residual_cnf.clauses.len());
+    println!("c residual cardinality constraints: {}", residual_cnf.cardinality_constraints.len());
+    println!("c residual pseudo-Boolean constraints: {}", residual_cnf.pseudo_boolean_constraints.len());
+    println!("c residual xor constraints: {}", residual_cnf.xor_constraints.len());
+    println!("c residual acyclicity edges: {}", residual_cnf.acyclicity_edges.len());
+    println!("c residual difference atoms: {}", residual_cnf.difference_atoms.len());
+    println!("c residual integer constraints: {}", residual_cnf.integer_constraints.len());
+    write_cnf(&mut io::BufWriter::new(fs::File::create(output_path)?), &residual_cnf)?;
+    reconstruction_stack.write(&mut io::BufWriter::new(fs::File::create(reconstruction_path)?))?;
+    return Ok(());
+}
+
+/// 1 行に 1 つずつ "c gate <種類> <出力> = <入力> 0" の形式で出力する
+fn print_gates(gates: &[Gate]) {
+    for gate in gates.iter() {
+        let kind = match gate.kind {
+            GateKind::And => "and",
+            GateKind::Xor => "xor",
+            GateKind::IfThenElse => "ite",
+            GateKind::Equivalence => "equivalence",
+        };
+        let mut line = format!("c gate {} {} =", kind, format_literal(gate.output));
+        for &input in gate.inputs.iter() {
+            line += &format!(" {}", format_literal(input));
+        }
+        println!("{} 0", line);
+    }
+}
+
+fn reconstruct(reconstruction_path: &str) -> io::Result<()> {
+    let reconstruction_stack = ReconstructionStack::read(io::BufReader::new(fs::File::open(reconstruction_path)?));
+    let mut model = read_model(io::BufReader::new(io::stdin()), reconstruction_stack.number_of_variables());
+    reconstruction_stack.extend_model(&mut model);
+    print_model(&model);
+    return Ok(());
+}
 
 fn main() {
-    let cnf = read_cnf(std::io::BufReader::new(std::io::stdin()));
-    solve(&cnf);
+    let options = match Options::parse(std::env::args()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let result = match &options.mode {
+        Mode::Solve => {
+            let cnf = read_cnf(io::BufReader::new(io::stdin()));
+            solve(
+                &cnf,
+                options.is_xor_detection_enabled,
+                options.symmetry_breaking_effort,
+                &options.propagation,
+                &options.search,
+                None,
+            );
+            Ok(())
+        }
+        Mode::PreprocessOnly { output_path, reconstruction_path } => {
+            let cnf = read_cnf(io::BufReader::new(io::stdin()));
+            preprocess_only(
+                &cnf,
+                output_path,
+                reconstruction_path,
+                options.is_gate_printing_enabled,
+                options.is_xor_detection_enabled,
+            )
+        }
+        Mode::Reconstruct { reconstruction_path } => reconstruct(reconstruction_path),
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,253 @@
+use cdcl_engine::EngineTrait;
+
+use crate::{
+    decision_heuristic::DecisionHeuristic,
+    restart_policy::{RestartPolicy, RestartPolicySummary},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// 頻繁に再始動し，優先度を速く減衰させる
+    Focused,
+    /// 再始動を控え，優先度をゆっくり減衰させる
+    Stable,
+}
+
+impl SearchMode {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Focused => "focused",
+            Self::Stable => "stable",
+        };
+    }
+}
+
+/// focused モードと stable モードを矛盾の数の等比数列の間隔で交互に切り替える
+///
+/// モードごとに DecisionHeuristic と RestartPolicy を持ち，現在のモードのものだけで優先度の更新と再始動の判断を行う．
+/// 未割り当てになった変数は両方の DecisionHeuristic に知らせ，切り替えた後もそのまま決定に使えるようにする．
+/// モードは再始動するときに切り替える．切り替えを無効にすると focused モードのものだけを使う．
+#[derive(Clone)]
+pub struct ModeSwitching<DecisionHeuristicT, RestartPolicyT> {
+    decision_heuristics: [DecisionHeuristicT; 2],
+    restart_policies: [RestartPolicyT; 2],
+    is_enabled: bool,
+    mode: SearchMode,
+    interval: f64,
+    factor: f64,
+    number_of_conflicts: u64,
+    number_of_conflicts_at_next_switch: u64,
+    number_of_restarts: u32,
+}
+
+impl<DecisionHeuristicT, RestartPolicyT> ModeSwitching<DecisionHeuristicT, RestartPolicyT>
+where
+    DecisionHeuristicT: DecisionHeuristic,
+    RestartPolicyT: RestartPolicy,
+{
+    /// 最初の focused モードを initial_interval 回の矛盾だけ続け，切り替えるたびに間隔を factor 倍にする
+    pub fn new(
+        focused: (DecisionHeuristicT, RestartPolicyT),
+        stable: (DecisionHeuristicT, RestartPolicyT),
+        is_enabled: bool,
+        initial_interval: f64,
+        factor: f64,
+    ) -> Self {
+        Self {
+            decision_heuristics: [focused.0, stable.0],
+            restart_policies: [focused.1, stable.1],
+            is_enabled: is_enabled,
+            mode: SearchMode::Focused,
+            interval: initial_interval,
+            factor: factor,
+            number_of_conflicts: 0,
+            number_of_conflicts_at_next_switch: if is_enabled { initial_interval.ceil() as u64 } else { u64::MAX },
+            number_of_restarts: 0,
+        }
+    }
+
+    pub fn mode(&self) -> SearchMode {
+        return self.mode;
+    }
+
+    fn should_switch(&self) -> bool {
+        return self.number_of_conflicts >= self.number_of_conflicts_at_next_switch;
+    }
+
+    fn switch(&mut self) {
+        self.mode = match self.mode {
+            SearchMode::Focused => SearchMode::Stable,
+            SearchMode::Stable => SearchMode::Focused,
+        };
+        self.interval *= self.factor;
+        self.number_of_conflicts_at_next_switch = self.number_of_conflicts + self.interval.ceil() as u64;
+    }
+
+    fn decision_heuristic(&self) -> &DecisionHeuristicT {
+        return &self.decision_heuristics[self.mode as usize];
+    }
+
+    fn decision_heuristic_mut(&mut self) -> &mut DecisionHeuristicT {
+        return &mut self.decision_heuristics[self.mode as usize];
+    }
+
+    fn restart_policy(&self) -> &RestartPolicyT {
+        return &self.restart_policies[self.mode as usize];
+    }
+
+    fn restart_policy_mut(&mut self) -> &mut RestartPolicyT {
+        return &mut self.restart_policies[self.mode as usize];
+    }
+}
+
+impl<DecisionHeuristicT, RestartPolicyT> DecisionHeuristic for ModeSwitching<DecisionHeuristicT, RestartPolicyT>
+where
+    DecisionHeuristicT: DecisionHeuristic,
+    RestartPolicyT: RestartPolicy,
+{
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
+        if !self.is_enabled {
+            self.decision_heuristic_mut().add_variable(initial_activity, is_assigned);
+            return;
+        }
+        for decision_heuristic in self.decision_heuristics.iter_mut() {
+            decision_heuristic.add_variable(initial_activity, is_assigned);
+        }
+    }
+
+    fn set_to_unassigned(&mut self, index: u32) {
+        if !self.is_enabled {
+            self.decision_heuristic_mut().set_to_unassigned(index);
+            return;
+        }
+        for decision_heuristic in self.decision_heuristics.iter_mut() {
+            decision_heuristic.set_to_unassigned(index);
+        }
+    }
+
+    fn peek(&self) -> Option<u32> {
+        return self.decision_heuristic().peek();
+    }
+
+    fn activity(&self, index: u32) -> f64 {
+        return self.decision_heuristic().activity(index);
+    }
+
+    fn set_to_assigned(&mut self, index: u32) {
+        // 他方のモードの DecisionHeuristic に残った割り当て済みの変数は，そのモードの peek で取り除かれる
+        self.decision_heuristic_mut().set_to_assigned(index);
+    }
+
+    fn increase_price(
+        &mut self,
+        related_variables: impl Iterator<Item = u32>,
+        reason_side_variables: impl Iterator<Item = u32>,
+        engine: &impl EngineTrait,
+        backjump_level: u32,
+    ) {
+        self.decision_heuristic_mut()
+            .increase_price(related_variables, reason_side_variables, engine, backjump_level);
+    }
+
+    fn is_reason_side_rate_used(&self) -> bool {
+        return self
+            .decision_heuristics
+            .iter()
+            .any(|decision_heuristic| decision_heuristic.is_reason_side_rate_used());
+    }
+}
+
+impl<DecisionHeuristicT, RestartPolicyT> RestartPolicy for ModeSwitching<DecisionHeuristicT, RestartPolicyT>
+where
+    DecisionHeuristicT: DecisionHeuristic,
+    RestartPolicyT: RestartPolicy,
+{
+    fn add_conflict(&mut self, plbd: u32, engine: &impl EngineTrait) {
+        self.number_of_conflicts += 1;
+        self.restart_policy_mut().add_conflict(plbd, engine);
+    }
+
+    fn should_restart(&self) -> bool {
+        return self.should_switch() || self.restart_policy().should_restart();
+    }
+
+    fn restart(&mut self) {
+        self.restart_policy_mut().restart();
+        self.number_of_restarts += 1;
+        if self.should_switch() {
+            self.switch();
+        }
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return RestartPolicySummary {
+            number_of_restarts: self.number_of_restarts,
+            number_of_blocked_restarts: self
+                .restart_policies
+                .iter()
+                .map(|restart_policy| restart_policy.summary().number_of_blocked_restarts)
+                .sum(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{CoreEngine, CoreEngineExplainKey};
+
+    use super::{ModeSwitching, SearchMode};
+    use crate::{
+        pricer2::Pricer,
+        restart_policy::{GeometricRestart, RestartPolicy},
+    };
+
+    type TestModeSwitching = ModeSwitching<Pricer, GeometricRestart>;
+
+    /// focused モードでは interval 回の矛盾ごとに再始動し，stable モードでは自ら再始動しない
+    fn make_mode_switching(interval: f64, is_enabled: bool) -> TestModeSwitching {
+        return ModeSwitching::new(
+            (Pricer::new(1e2), GeometricRestart::new(interval, 1.0)),
+            (Pricer::new(1e3), GeometricRestart::new(f64::INFINITY, 1.0)),
+            is_enabled,
+            10.0,
+            2.0,
+        );
+    }
+
+    /// 再始動するまでの矛盾の数を数えて再始動する
+    fn conflicts_until_restart(mode_switching: &mut TestModeSwitching) -> usize {
+        let engine = CoreEngine::<CoreEngineExplainKey>::default();
+        let mut number_of_conflicts = 0;
+        while !mode_switching.should_restart() {
+            mode_switching.add_conflict(1, &engine);
+            number_of_conflicts += 1;
+        }
+        mode_switching.restart();
+        return number_of_conflicts;
+    }
+
+    /// 現在のモードの再始動の方針に従い，矛盾の数が等比数列で増える間隔ごとの再始動でモードを切り替えることを確かめる
+    #[test]
+    fn test_switches_mode_at_geometric_intervals() {
+        let mut mode_switching = make_mode_switching(3.0, true);
+        for expected in [3, 3, 3] {
+            assert_eq!(conflicts_until_restart(&mut mode_switching), expected);
+            assert!(mode_switching.mode() == SearchMode::Focused);
+        }
+        assert_eq!(conflicts_until_restart(&mut mode_switching), 1);
+        assert!(mode_switching.mode() == SearchMode::Stable);
+        assert_eq!(conflicts_until_restart(&mut mode_switching), 20);
+        assert!(mode_switching.mode() == SearchMode::Focused);
+        assert_eq!(mode_switching.summary().number_of_restarts, 5);
+    }
+
+    /// 切り替えを無効にすると focused モードのまま再始動することを確かめる
+    #[test]
+    fn test_disabled_mode_switching_stays_focused() {
+        let mut mode_switching = make_mode_switching(30.0, false);
+        for _ in 0..3 {
+            assert_eq!(conflicts_until_restart(&mut mode_switching), 30);
+            assert!(mode_switching.mode() == SearchMode::Focused);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use cdcl_engine::{Boolean, EngineTrait, Literal};
+use utility::Array;
+
+use crate::{difference_logic_theory::DifferenceLogicTheory, integer_theory::IntegerTheory, write_cnf::format_literal};
+
+pub fn print_model(model: &Array<u32, Boolean>) {
+    let mut line = String::from("v");
+    for index in 0..model.len() {
+        line += &format!(" {}", format_literal(Literal::new(index, model[index])));
+        if line.len() >= 80 {
+            println!("{}", line);
+            line = String::from("v");
+        }
+    }
+    println!("{} 0", line);
+}
+
+/// 差分論理の整数変数の値を "i <整数変数> <値>" の形で出力する
+pub fn print_integer_model(difference_logic_theory: &DifferenceLogicTheory) {
+    for integer_variable in 0..difference_logic_theory.number_of_integer_variables() {
+        println!("i {} {}", integer_variable, difference_logic_theory.value(integer_variable));
+    }
+}
+
+/// lazy clause generation の整数変数の値を "z <整数変数> <値>" の形で出力する
+pub fn print_integer_variable_model(integer_theory: &IntegerTheory, engine: &impl EngineTrait) {
+    for integer_variable in 0..integer_theory.number_of_integer_variables() {
+        println!("z {} {}", integer_variable, integer_theory.value(integer_variable, engine));
+    }
+}
+
+/// "v" で始まる行から解を読み込む．現れない変数は偽とする
+pub fn read_model(reader: impl std::io::BufRead, number_of_variables: u32) -> Array<u32, Boolean> {
+    let mut model = Array::default();
+    model.resize(number_of_variables, Boolean::FALSE);
+    for read_result in reader.lines() {
+        let line = read_result.unwrap();
+        let Some(values) = line.strip_prefix('v') else {
+            continue;
+        };
+        for i in values.split_whitespace().map(|field| field.parse::<i64>().unwrap()).filter(|&i| i != 0) {
+            let index = (i.abs() - 1) as u32;
+            if index >= model.len() {
+                model.resize(index + 1, Boolean::FALSE);
+            }
+            model[index] = Boolean::from(i > 0);
+        }
+    }
+    return model;
+}
@@ -0,0 +1,181 @@
+use utility::Array;
+
+use crate::engine::TheoryKind;
+
+pub enum Mode {
+    /// 標準入力の CNF を解く
+    Solve,
+    /// 前処理だけを行い，残った論理式と ReconstructionStack を書き出す
+    PreprocessOnly { output_path: String, reconstruction_path: String },
+    /// 標準入力から残った論理式の解を読み込み，ReconstructionStack で元の論理式の解に修復する
+    Reconstruct { reconstruction_path: String },
+}
+
+/// 次に決定する変数を選ぶ方法
+#[derive(Clone, Copy, Default)]
+pub enum DecisionHeuristicKind {
+    /// EVSIDS
+    Vsids,
+    /// 解析に現れたか否かを目標値とする Adam 風の学習
+    #[default]
+    Adam,
+    /// learning-rate branching
+    LearningRateBranching,
+}
+
+impl DecisionHeuristicKind {
+    /// コマンドラインで指定する名前
+    pub const NAMES: [(&str, Self); 3] =
+        [("vsids", Self::Vsids), ("adam", Self::Adam), ("lrb", Self::LearningRateBranching)];
+
+    fn from_name(name: &str) -> Option<Self> {
+        return Self::NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, kind)| kind);
+    }
+}
+
+/// 再始動する時期の決め方
+#[derive(Clone, Copy, Default)]
+pub enum RestartPolicyKind {
+    /// PLBD の対数正規分布の累積分布関数で再始動までの矛盾の数を決める
+    #[default]
+    LogNormal,
+    /// PLBD の短期と長期の平均の比
+    AverageRatio,
+    /// Glucose 風の PLBD の指数移動平均と割り当ての列の長さによる見送り
+    GlucoseEma,
+    Luby,
+    Geometric,
+}
+
+impl RestartPolicyKind {
+    /// コマンドラインで指定する名前
+    pub const NAMES: [(&str, Self); 5] = [
+        ("log-normal", Self::LogNormal),
+        ("average-ratio", Self::AverageRatio),
+        ("glucose-ema", Self::GlucoseEma),
+        ("luby", Self::Luby),
+        ("geometric", Self::Geometric),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        return Self::NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, kind)| kind);
+    }
+}
+
+#[derive(Default)]
+pub struct SearchOptions {
+    pub decision_heuristic: DecisionHeuristicKind,
+    pub restart_policy: RestartPolicyKind,
+    /// focused モードと stable モードを交互に切り替えるか
+    pub is_mode_switching_enabled: bool,
+    /// 一定の間隔で saved phase と target phase を再設定するか
+    pub is_rephasing_enabled: bool,
+    /// 学習節で戻る決定レベルの差がこれを超えるときは，矛盾した決定レベルの一つ前までしか戻らない
+    ///
+    /// None であれば常に学習節の決定レベルまで戻る
+    pub chronological_backtracking_threshold: Option<u32>,
+    /// 再始動で優先度の高い変数を決定した決定レベルを取り消さずに残すか
+    pub is_trail_reuse_enabled: bool,
+}
+
+#[derive(Default)]
+pub struct PropagationOptions {
+    /// 他の理論が伝播し終えてから伝播させる理論
+    pub deferred_theories: Array<u32, TheoryKind>,
+    /// 理論ごとの伝播の時間を計って出力するか
+    pub is_statistics_printing_enabled: bool,
+}
+
+pub struct Options {
+    pub mode: Mode,
+    /// 前処理だけを行うときに認識したゲートを出力するか
+    pub is_gate_printing_enabled: bool,
+    /// 前処理で節として書かれた XOR 制約を認識し，XOR 制約の理論に移すか
+    pub is_xor_detection_enabled: bool,
+    /// 対称性の検出に費やす手間の上限．0 であれば対称性を検出せず lex-leader 制約も加えない
+    pub symmetry_breaking_effort: usize,
+    pub propagation: PropagationOptions,
+    pub search: SearchOptions,
+}
+
+impl Options {
+    const USAGE: &str = "usage: solve_sat [--preprocess-only <output.cnf> [--reconstruction-file <output.rec>] [--print-gates]] [--reconstruct <input.rec>] [--detect-xor] [--symmetry-breaking-effort <effort>] [--defer <theory>]... [--propagation-statistics] [--decision-heuristic <vsids|adam|lrb>] [--restart-policy <log-normal|average-ratio|glucose-ema|luby|geometric>] [--mode-switching] [--rephase] [--chronological-backtracking <threshold>] [--reuse-trail] < input";
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut output_path = None;
+        let mut reconstruction_path = None;
+        let mut is_reconstruct = false;
+        let mut is_gate_printing_enabled = false;
+        let mut is_xor_detection_enabled = false;
+        let mut symmetry_breaking_effort = 10000000;
+        let mut propagation = PropagationOptions::default();
+        let mut search = SearchOptions::default();
+        args.next();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--preprocess-only" => output_path = Some(Self::value_of(&arg, args.next())?),
+                "--reconstruction-file" => reconstruction_path = Some(Self::value_of(&arg, args.next())?),
+                "--print-gates" => is_gate_printing_enabled = true,
+                "--detect-xor" => is_xor_detection_enabled = true,
+                "--symmetry-breaking-effort" => {
+                    let effort = args.next().and_then(|value| value.parse::<usize>().ok());
+                    symmetry_breaking_effort =
+                        effort.ok_or_else(|| format!("{} requires a number\n{}", arg, Self::USAGE))?;
+                }
+                "--defer" => {
+                    let name = args.next().ok_or_else(|| format!("{} requires a theory\n{}", arg, Self::USAGE))?;
+                    let kind = TheoryKind::from_name(&name).ok_or_else(|| format!("unknown theory: {}", name))?;
+                    propagation.deferred_theories.push(kind);
+                }
+                "--propagation-statistics" => propagation.is_statistics_printing_enabled = true,
+                "--decision-heuristic" => {
+                    let name = args.next().ok_or_else(|| format!("{} requires a heuristic\n{}", arg, Self::USAGE))?;
+                    search.decision_heuristic = DecisionHeuristicKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown decision heuristic: {}", name))?;
+                }
+                "--mode-switching" => search.is_mode_switching_enabled = true,
+                "--rephase" => search.is_rephasing_enabled = true,
+                "--chronological-backtracking" => {
+                    let threshold = args.next().and_then(|value| value.parse::<u32>().ok());
+                    search.chronological_backtracking_threshold =
+                        Some(threshold.ok_or_else(|| format!("{} requires a number\n{}", arg, Self::USAGE))?);
+                }
+                "--reuse-trail" => search.is_trail_reuse_enabled = true,
+                "--restart-policy" => {
+                    let name = args.next().ok_or_else(|| format!("{} requires a policy\n{}", arg, Self::USAGE))?;
+                    search.restart_policy = RestartPolicyKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown restart policy: {}", name))?;
+                }
+                "--reconstruct" => {
+                    is_reconstruct = true;
+                    reconstruction_path = Some(Self::value_of(&arg, args.next())?);
+                }
+                _ => return Err(format!("unknown option: {}\n{}", arg, Self::USAGE)),
+            }
+        }
+        if is_gate_printing_enabled && output_path.is_none() {
+            return Err(String::from(Self::USAGE));
+        }
+        let mode = match (output_path, reconstruction_path, is_reconstruct) {
+            (None, None, false) => Mode::Solve,
+            (Some(output_path), reconstruction_path, false) => {
+                let reconstruction_path = reconstruction_path.unwrap_or_else(|| format!("{}.rec", output_path));
+                Mode::PreprocessOnly { output_path: output_path, reconstruction_path: reconstruction_path }
+            }
+            (None, Some(reconstruction_path), true) => Mode::Reconstruct { reconstruction_path: reconstruction_path },
+            _ => return Err(String::from(Self::USAGE)),
+        };
+        return Ok(Self {
+            mode: mode,
+            is_gate_printing_enabled: is_gate_printing_enabled,
+            is_xor_detection_enabled: is_xor_detection_enabled,
+            symmetry_breaking_effort: symmetry_breaking_effort,
+            propagation: propagation,
+            search: search,
+        });
+    }
+
+    fn value_of(option: &str, value: Option<String>) -> Result<String, String> {
+        return value.ok_or_else(|| format!("{} requires a path\n{}", option, Self::USAGE));
+    }
+}
@@ -0,0 +1,299 @@
+use cdcl_engine::{Boolean, EngineTrait, Literal};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use utility::Array;
+
+use crate::{mode_switching::SearchMode, walk::Walk};
+
+/// 位相を再設定するときの位相の取り出し元
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PhaseSource {
+    /// 変数を作ったときの値
+    Original,
+    /// 変数を作ったときの値の否定
+    Inverted,
+    Random,
+    /// best phase
+    Best,
+    /// saved phase から始めた局所探索で得た割り当て
+    Walk,
+}
+
+impl PhaseSource {
+    pub const ALL: [Self; 5] = [Self::Original, Self::Inverted, Self::Random, Self::Best, Self::Walk];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Original => "original",
+            Self::Inverted => "inverted",
+            Self::Random => "random",
+            Self::Best => "best",
+            Self::Walk => "walk",
+        };
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct RephaseStatistics {
+    pub number_of_rephases: u32,
+    /// この取り出し元で再設定した後，次に再設定するまでに最長の矛盾のない割り当ての列を更新した回数
+    pub number_of_improvements: u32,
+}
+
+/// 決定した変数に割り当てる値(位相)
+///
+/// saved phase は最後に割り当てられていた値，target phase は再始動してから最も長い矛盾のない割り当ての列での値，
+/// best phase は位相を再設定してから最も長い矛盾のない割り当ての列での値である．
+/// focused モードでは saved phase を，stable モードでは target phase を用いる．
+/// 再設定を有効にすると，矛盾の数が等差数列で増える間隔ごとに saved phase と target phase を SCHEDULE の順の取り出し元で置き換える．
+pub struct Phases {
+    initial: Array<u32, Boolean>,
+    saved: Array<u32, Boolean>,
+    target: Array<u32, Boolean>,
+    best: Array<u32, Boolean>,
+    target_length: u32,
+    best_length: u32,
+    /// これまでで最も長い矛盾のない割り当ての列の長さ
+    max_length: u32,
+    is_rephasing_enabled: bool,
+    interval: u64,
+    number_of_conflicts: u64,
+    number_of_conflicts_at_next_rephase: u64,
+    number_of_rephases: u32,
+    last_source: Option<PhaseSource>,
+    statistics: [RephaseStatistics; 5],
+    walk: Walk,
+    rng: SmallRng,
+}
+
+impl Phases {
+    const SCHEDULE: [PhaseSource; 9] = [
+        PhaseSource::Original,
+        PhaseSource::Best,
+        PhaseSource::Walk,
+        PhaseSource::Inverted,
+        PhaseSource::Best,
+        PhaseSource::Walk,
+        PhaseSource::Random,
+        PhaseSource::Best,
+        PhaseSource::Walk,
+    ];
+
+    /// 最初は interval 回の矛盾の後に再設定し，k 回目の再設定の後は k + 1 倍の間隔を空ける
+    pub fn new(is_rephasing_enabled: bool, interval: u64) -> Self {
+        Self {
+            initial: Array::default(),
+            saved: Array::default(),
+            target: Array::default(),
+            best: Array::default(),
+            target_length: 0,
+            best_length: 0,
+            max_length: 0,
+            is_rephasing_enabled: is_rephasing_enabled,
+            interval: interval,
+            number_of_conflicts: 0,
+            number_of_conflicts_at_next_rephase: interval,
+            number_of_rephases: 0,
+            last_source: None,
+            statistics: [RephaseStatistics::default(); 5],
+            walk: Walk::default(),
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    pub fn add_variable(&mut self, initial_value: Boolean) {
+        self.initial.push(initial_value);
+        self.saved.push(initial_value);
+        self.target.push(initial_value);
+        self.best.push(initial_value);
+    }
+
+    pub fn set_to_unassigned(&mut self, literal: Literal) {
+        self.saved[literal.index()] = literal.value();
+    }
+
+    pub fn get(&self, index: u32, mode: SearchMode) -> Boolean {
+        return match mode {
+            SearchMode::Focused => self.saved[index],
+            SearchMode::Stable => self.target[index],
+        };
+    }
+
+    /// 矛盾の解析の後，バックジャンプの前に呼び出す
+    ///
+    /// 矛盾した決定レベルより前の割り当ては矛盾していないので，target phase と best phase の候補にする．
+    pub fn add_conflict(&mut self, engine: &impl EngineTrait) {
+        self.number_of_conflicts += 1;
+        let length = engine.get_assignment_order_range(engine.current_decision_level()).start;
+        if length > self.target_length {
+            Self::copy_trail(&mut self.target, length, engine);
+            self.target_length = length;
+        }
+        if length > self.best_length {
+            Self::copy_trail(&mut self.best, length, engine);
+            self.best_length = length;
+        }
+        if length > self.max_length {
+            self.max_length = length;
+            if let Some(source) = self.last_source {
+                self.statistics[source as usize].number_of_improvements += 1;
+            }
+        }
+    }
+
+    fn copy_trail(phases: &mut Array<u32, Boolean>, length: u32, engine: &impl EngineTrait) {
+        for assignment_order in 0..length {
+            let literal = engine.get_assignment(assignment_order);
+            phases[literal.index()] = literal.value();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.target_length = 0;
+    }
+
+    pub fn should_rephase(&self) -> bool {
+        return self.is_rephasing_enabled && self.number_of_conflicts >= self.number_of_conflicts_at_next_rephase;
+    }
+
+    /// 決定レベル 0 で saved phase と target phase を置き換える
+    ///
+    /// 局所探索は clauses だけを対象とする
+    pub fn rephase(&mut self, clauses: &Array<u32, Array<u32, Literal>>, engine: &impl EngineTrait) {
+        debug_assert!(engine.current_decision_level() == 0);
+        let source = Self::SCHEDULE[self.number_of_rephases as usize % Self::SCHEDULE.len()];
+        match source {
+            PhaseSource::Original => {
+                self.saved.clone_from(&self.initial);
+            }
+            PhaseSource::Inverted => {
+                self.saved.clear();
+                self.saved.extend(self.initial.iter().map(|&value| !value));
+            }
+            PhaseSource::Random => {
+                for value in self.saved.iter_mut() {
+                    *value = Boolean::from(self.rng.gen_bool(0.5));
+                }
+            }
+            PhaseSource::Best => {
+                self.saved.clone_from(&self.best);
+            }
+            PhaseSource::Walk => {
+                self.walk.walk(clauses, engine, &mut self.saved, 0.5, 10000000, &mut self.rng);
+            }
+        }
+        self.target.clone_from(&self.saved);
+        self.target_length = 0;
+        self.best_length = 0;
+        self.number_of_rephases += 1;
+        self.number_of_conflicts_at_next_rephase =
+            self.number_of_conflicts + self.interval * (self.number_of_rephases as u64 + 1);
+        self.statistics[source as usize].number_of_rephases += 1;
+        self.last_source = Some(source);
+    }
+
+    pub fn last_source(&self) -> Option<PhaseSource> {
+        return self.last_source;
+    }
+
+    pub fn statistics(&self, source: PhaseSource) -> RephaseStatistics {
+        return self.statistics[source as usize];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, CoreEngine, CoreEngineExplainKey, EngineTrait, Literal, Reason};
+    use utility::Array;
+
+    use super::{PhaseSource, Phases};
+    use crate::mode_switching::SearchMode;
+
+    type Engine = CoreEngine<CoreEngineExplainKey>;
+
+    /// number_of_variables 個の変数を初期値偽で作ったエンジンと位相
+    fn make_phases(number_of_variables: u32, interval: u64) -> (Engine, Phases) {
+        let mut engine = Engine::default();
+        let mut phases = Phases::new(true, interval);
+        for _ in 0..number_of_variables {
+            engine.add_variable(Boolean::FALSE);
+            phases.add_variable(Boolean::FALSE);
+        }
+        return (engine, phases);
+    }
+
+    /// 決定レベル 0 に戻ってから decisions を順に決定し，最後の決定レベルで矛盾したことを知らせる
+    fn add_conflict(engine: &mut Engine, phases: &mut Phases, decisions: &[Literal]) {
+        for literal in engine.backjump(0) {
+            phases.set_to_unassigned(literal);
+        }
+        for &literal in decisions {
+            let _ = engine.assign(literal, Reason::Decision);
+        }
+        phases.add_conflict(engine);
+    }
+
+    fn literal(index: u32, value: bool) -> Literal {
+        return Literal::new(index, Boolean::from(value));
+    }
+
+    /// target phase と best phase は矛盾のない割り当ての列が長くなったときだけ更新し，
+    /// target phase は再始動で，best phase は位相の再設定で長さを忘れることを確かめる
+    #[test]
+    fn test_target_and_best_phases_update_only_on_improvement() {
+        let (mut engine, mut phases) = make_phases(4, 100);
+        add_conflict(&mut engine, &mut phases, &[literal(0, true), literal(1, true), literal(2, true)]);
+        assert!(phases.get(0, SearchMode::Stable) == Boolean::TRUE);
+        assert!(phases.get(1, SearchMode::Stable) == Boolean::TRUE);
+        assert!(phases.get(2, SearchMode::Stable) == Boolean::FALSE);
+
+        // 割り当ての列が短いので更新しない
+        add_conflict(&mut engine, &mut phases, &[literal(0, false), literal(3, true)]);
+        assert!(phases.get(0, SearchMode::Stable) == Boolean::TRUE);
+        assert!(phases.best[0] == Boolean::TRUE);
+
+        // 再始動の後は target phase だけを更新する
+        phases.restart();
+        add_conflict(&mut engine, &mut phases, &[literal(0, false), literal(3, true)]);
+        assert!(phases.get(0, SearchMode::Stable) == Boolean::FALSE);
+        assert!(phases.get(1, SearchMode::Stable) == Boolean::TRUE);
+        assert!(phases.best[0] == Boolean::TRUE);
+
+        // focused モードでは最後に割り当てられていた値を用いる
+        for literal in engine.backjump(0) {
+            phases.set_to_unassigned(literal);
+        }
+        assert!(phases.get(0, SearchMode::Focused) == Boolean::FALSE);
+        assert!(phases.get(1, SearchMode::Focused) == Boolean::TRUE);
+        assert!(phases.get(3, SearchMode::Focused) == Boolean::TRUE);
+    }
+
+    /// 再設定の間隔が等差数列で増え，SCHEDULE の順に取り出し元を切り替えることを確かめる
+    #[test]
+    fn test_rephase_schedule() {
+        let (mut engine, mut phases) = make_phases(2, 2);
+        let clauses = Array::default();
+        add_conflict(&mut engine, &mut phases, &[literal(0, true), literal(1, true)]);
+        assert!(!phases.should_rephase());
+        add_conflict(&mut engine, &mut phases, &[literal(1, true), literal(0, true)]);
+        assert!(phases.should_rephase());
+        let _ = engine.backjump(0).count();
+        phases.rephase(&clauses, &engine);
+        assert!(phases.last_source() == Some(PhaseSource::Original));
+        assert!(phases.get(0, SearchMode::Focused) == Boolean::FALSE);
+        assert!(phases.get(0, SearchMode::Stable) == Boolean::FALSE);
+
+        for _ in 0..3 {
+            add_conflict(&mut engine, &mut phases, &[literal(1, false), literal(0, false)]);
+            assert!(!phases.should_rephase());
+        }
+        add_conflict(&mut engine, &mut phases, &[literal(1, false), literal(0, false)]);
+        assert!(phases.should_rephase());
+        let _ = engine.backjump(0).count();
+        phases.rephase(&clauses, &engine);
+        assert!(phases.last_source() == Some(PhaseSource::Best));
+        assert!(phases.get(0, SearchMode::Focused) == Boolean::TRUE);
+        assert!(phases.get(1, SearchMode::Stable) == Boolean::FALSE);
+        assert_eq!(phases.statistics(PhaseSource::Original).number_of_rephases, 1);
+        assert_eq!(phases.statistics(PhaseSource::Best).number_of_rephases, 1);
+    }
+}
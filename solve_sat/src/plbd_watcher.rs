@@ -21,10 +21,15 @@ impl PLBDWatcher {
     //     self.short_term_average.count
     // }
 
-    pub fn long_term_average(&self) -> f64 {
-        self.long_term_average.value()
+    /// 短期の平均の窓が埋まっているか
+    pub fn is_short_term_average_full(&self) -> bool {
+        self.short_term_average.count == self.short_term_average.ring_buffer.len()
     }
 
+    // pub fn long_term_average(&self) -> f64 {
+    //     self.long_term_average.value()
+    // }
+
     // pub fn short_term_average(&self) -> f64 {
     //     self.short_term_average.value()
     // }
@@ -78,7 +83,7 @@ impl Current {
     fn new(time_constant: usize) -> Self {
         assert!(time_constant > 0);
         Self {
-            ring_buffer: Vec::from_iter(std::iter::repeat(f64::NAN).take(time_constant)),
+            ring_buffer: Vec::from_iter(std::iter::repeat_n(f64::NAN, time_constant)),
             first: 0,
             count: 0,
             sum: 0.0,
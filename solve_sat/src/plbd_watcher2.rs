@@ -35,26 +35,27 @@ impl PLBDWatcher2 {
     }
 }
 
+/// 最初の time_constant 個までは算術平均，それ以降は平滑化係数 1 / time_constant の指数移動平均
 #[derive(Clone, Debug)]
-struct Average {
+pub struct Average {
     time_constant: f64,
     count: f64,
     mean: f64,
 }
 
 impl Average {
-    fn new(time_constant: f64) -> Self {
+    pub fn new(time_constant: f64) -> Self {
         assert!(time_constant > 0.0);
         Self { time_constant: time_constant, count: 0.0, mean: 0.0 }
     }
 
-    fn add(&mut self, value: f64) {
+    pub fn add(&mut self, value: f64) {
         self.count += 1.0;
         let t = self.time_constant.min(self.count);
         self.mean = ((t - 1.0) * self.mean + value) / t;
     }
 
-    fn value(&self) -> f64 {
+    pub fn value(&self) -> f64 {
         if self.count != 0.0 { self.mean } else { f64::NAN }
     }
 }
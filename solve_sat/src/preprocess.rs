@@ -0,0 +1,844 @@
+use cdcl_engine::{Boolean, EngineAddConstraintTrait, EngineGetTheoryTrait, EngineTrait, Literal};
+use utility::{Array, CalculateGCD, Set};
+
+use crate::{
+    acyclicity_theory::{AcyclicityEdge, AcyclicityTheory},
+    binary_clause_theory::BinaryClauseTheory,
+    cardinality_theory::{CardinalityConstraint, CardinalityTheory},
+    clause_database::ClauseDatabase,
+    clause_theory::{ClauseTheory, ClauseView},
+    cnf::CNF,
+    difference_logic_theory::{DifferenceAtom, DifferenceLogicTheory},
+    engine::{EngineConstraint, SATEngine, make_constraint, new_sat_engine},
+    external_propagator::ExternalPropagatorTheory,
+    gate_extraction::{Gate, GateExtraction, GateSummary},
+    integer_theory::{IntegerConstraint, IntegerTheory},
+    pseudo_boolean_theory::{PseudoBooleanConstraint, PseudoBooleanTheory},
+    reconstruction_stack::ReconstructionStack,
+    xor_theory::{XorConstraint, XorTheory},
+};
+
+pub enum PreprocessResult {
+    Unsatisfiable,
+    Simplified { cnf: Box<CNF>, reconstruction_stack: ReconstructionStack },
+}
+
+#[derive(Default, Clone)]
+pub struct PreprocessSummary {
+    pub number_of_fixed_variables: u32,
+    pub number_of_substituted_variables: u32,
+    pub number_of_subsumed_clauses: u32,
+    pub number_of_strengthened_clauses: u32,
+    pub number_of_eliminated_variables: u32,
+    /// ゲートの定義を用いて除去した変数の数
+    pub number_of_gate_eliminations: u32,
+    /// 節から認識した XOR 制約の数
+    pub number_of_detected_xor_constraints: u32,
+}
+
+/// 決定レベル 0 で節を追加する
+///
+/// 充足済みの節は追加せず，偽のリテラルは取り除いてから追加する．矛盾が生じた場合は false を返す．
+pub fn add_clause_at_root(engine: &mut SATEngine, clause: &[Literal]) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    if clause.iter().any(|&literal| engine.is_true(literal)) {
+        return true;
+    }
+    let literals =
+        Array::<u32, Literal>::from_iter(clause.iter().cloned().filter(|&literal| !engine.is_false(literal)));
+    if literals.is_empty() {
+        return false;
+    }
+    return engine.add_constraint(make_constraint(literals.iter().cloned()).into(), false).is_no_conflict();
+}
+
+/// 決定レベル 0 で基数制約を追加する
+///
+/// 割り当て済みのリテラルは取り除き，真のリテラルの数だけ bound を減らしてから追加する．
+/// 矛盾が生じた場合は false を返す．
+pub fn add_cardinality_constraint_at_root(engine: &mut SATEngine, constraint: &CardinalityConstraint) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    let Some(constraint) = simplify_cardinality_constraint_at_root(engine, constraint) else {
+        return true;
+    };
+    if (constraint.literals.len()) < constraint.bound {
+        return false;
+    }
+    if constraint.bound == 1 {
+        return add_clause_at_root(engine, &constraint.literals[..]);
+    }
+    let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = constraint.into();
+    return engine.add_constraint(constraint, false).is_no_conflict();
+}
+
+/// 決定レベル 0 で擬似ブール制約を追加する
+///
+/// 割り当て済みのリテラルを取り除いて正規化し，全ての係数が 1 になれば基数制約として追加する．
+/// 矛盾が生じた場合は false を返す．
+pub fn add_pseudo_boolean_constraint_at_root(engine: &mut SATEngine, constraint: &PseudoBooleanConstraint) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    let Some(constraint) = simplify_pseudo_boolean_constraint_at_root(engine, constraint) else {
+        return true;
+    };
+    if constraint.terms.iter().map(|&(c, _)| c).sum::<u64>() < constraint.degree {
+        return false;
+    }
+    if constraint.is_cardinality() {
+        let literals = Array::from_iter(constraint.terms.iter().map(|&(_, l)| l));
+        return add_cardinality_constraint_at_root(
+            engine,
+            &CardinalityConstraint { literals: literals, bound: constraint.degree as u32 },
+        );
+    }
+    let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = constraint.into();
+    return engine.add_constraint(constraint, false).is_no_conflict();
+}
+
+/// 決定レベル 0 で XOR 制約を追加する
+///
+/// 割り当て済みの変数を取り除き，変数が 2 個以下になれば節として追加する．矛盾が生じた場合は false を返す．
+pub fn add_xor_constraint_at_root(engine: &mut SATEngine, constraint: &XorConstraint) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    let constraint = simplify_xor_constraint_at_root(engine, constraint);
+    if constraint.variables.len() <= 2 {
+        return constraint.clauses().iter().all(|clause| add_clause_at_root(engine, &clause[..]));
+    }
+    let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = constraint.into();
+    return engine.add_constraint(constraint, false).is_no_conflict();
+}
+
+/// 決定レベル 0 で非巡回性の辺を宣言する
+///
+/// 偽に固定された辺は存在しないので加えない．矛盾が生じた場合は false を返す．
+pub fn add_acyclicity_edge_at_root(engine: &mut SATEngine, edge: &AcyclicityEdge) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    if engine.is_false(edge.literal) {
+        return true;
+    }
+    let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = (*edge).into();
+    return engine.add_constraint(constraint, false).is_no_conflict();
+}
+
+/// 決定レベル 0 で差分論理の原子を宣言する．矛盾が生じた場合は false を返す
+pub fn add_difference_atom_at_root(engine: &mut SATEngine, atom: &DifferenceAtom) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = (*atom).into();
+    return engine.add_constraint(constraint, false).is_no_conflict();
+}
+
+/// 決定レベル 0 で整数変数とその制約を加える
+///
+/// all_different・element に現れる整数変数の等号リテラルと，宣言された順序リテラルに対応する順序リテラルを
+/// add_variable で作り，宣言された順序リテラルとの同値性を節として加える．他の順序リテラルは理論が必要になったときに作る．
+/// 矛盾が生じた場合は false を返す．
+pub fn add_integer_model_at_root(engine: &mut SATEngine, cnf: &CNF) -> bool {
+    debug_assert!(engine.current_decision_level() == 0);
+    let mut has_equality_literals = Array::<u32, bool>::default();
+    has_equality_literals.resize(cnf.integer_variables.len(), false);
+    for constraint in cnf.integer_constraints.iter() {
+        match constraint {
+            IntegerConstraint::Linear { .. } => {}
+            IntegerConstraint::AllDifferent { variables } => {
+                for &variable in variables.iter() {
+                    has_equality_literals[variable] = true;
+                }
+            }
+            IntegerConstraint::Element { index, result, .. } => {
+                has_equality_literals[*index] = true;
+                has_equality_literals[*result] = true;
+            }
+        }
+    }
+    let add_literal = |engine: &mut SATEngine| {
+        engine.add_variable(Boolean::FALSE);
+        return Literal::new(engine.number_of_variables() - 1, Boolean::TRUE);
+    };
+    for (variable, &(lower, upper)) in cnf.integer_variables.iter().enumerate() {
+        if lower > upper {
+            return false;
+        }
+        // 順序リテラル [x <= v] は理論が必要になったときに作る
+        engine.get_theory_mut::<IntegerTheory>().add_integer_variable(lower, upper);
+        if !has_equality_literals[variable as u32] {
+            continue;
+        }
+        // [x = v] と上下限の関係は理論が伝播する
+        let equality_literals = Array::<u32, Literal>::from_iter((lower..=upper).map(|_| add_literal(engine)));
+        engine.get_theory_mut::<IntegerTheory>().set_equality_literals(variable as u32, equality_literals);
+    }
+    for order_literal in cnf.order_literals.iter() {
+        let (lower, upper) = engine.get_theory::<IntegerTheory>().domain(order_literal.variable);
+        let is_consistent = if order_literal.value < lower {
+            add_clause_at_root(engine, &[!order_literal.literal])
+        } else if order_literal.value >= upper {
+            add_clause_at_root(engine, &[order_literal.literal])
+        } else {
+            let literal =
+                match engine.get_theory::<IntegerTheory>().order_literal(order_literal.variable, order_literal.value) {
+                    Some(literal) => literal,
+                    None => {
+                        let literal = add_literal(engine);
+                        engine.get_theory_mut::<IntegerTheory>().insert_order_literal(
+                            order_literal.variable,
+                            order_literal.value,
+                            literal,
+                        );
+                        literal
+                    }
+                };
+            add_clause_at_root(engine, &[!order_literal.literal, literal])
+                && add_clause_at_root(engine, &[order_literal.literal, !literal])
+        };
+        if !is_consistent {
+            return false;
+        }
+        engine.get_theory_mut::<IntegerTheory>().add_order_literal(order_literal);
+    }
+    for constraint in cnf.integer_constraints.iter() {
+        let constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> = constraint.clone().into();
+        if !engine.add_constraint(constraint, false).is_no_conflict() {
+            return false;
+        }
+    }
+    return true;
+}
+
+/// 割り当て済みの変数を取り除いた XOR 制約を返す
+fn simplify_xor_constraint_at_root(engine: &SATEngine, constraint: &XorConstraint) -> XorConstraint {
+    let number_of_trues = constraint
+        .variables
+        .iter()
+        .filter(|&&index| engine.is_true(Literal::new(index, Boolean::TRUE)))
+        .count();
+    return XorConstraint {
+        variables: Array::from_iter(constraint.variables.iter().cloned().filter(|&index| !engine.is_assigned(index))),
+        parity: constraint.parity ^ (number_of_trues % 2 == 1),
+    };
+}
+
+/// 割り当て済みのリテラルを取り除いて正規化した擬似ブール制約を返す．充足済みであれば None を返す
+fn simplify_pseudo_boolean_constraint_at_root(
+    engine: &SATEngine,
+    constraint: &PseudoBooleanConstraint,
+) -> Option<PseudoBooleanConstraint> {
+    let sum_of_trues = constraint.terms.iter().filter(|&&(_, l)| engine.is_true(l)).map(|&(c, _)| c).sum::<u64>();
+    if sum_of_trues >= constraint.degree {
+        return None;
+    }
+    let mut simplified_constraint = PseudoBooleanConstraint {
+        terms: Array::from_iter(constraint.terms.iter().cloned().filter(|&(_, l)| !engine.is_assigned(l.index()))),
+        degree: constraint.degree - sum_of_trues,
+    };
+    simplified_constraint.normalize(&CalculateGCD::default());
+    return Some(simplified_constraint);
+}
+
+/// 割り当て済みのリテラルを取り除いた基数制約を返す．充足済みであれば None を返す
+fn simplify_cardinality_constraint_at_root(
+    engine: &SATEngine,
+    constraint: &CardinalityConstraint,
+) -> Option<CardinalityConstraint> {
+    let number_of_trues = constraint.literals.iter().filter(|&&literal| engine.is_true(literal)).count() as u32;
+    if number_of_trues >= constraint.bound {
+        return None;
+    }
+    return Some(CardinalityConstraint {
+        literals: Array::from_iter(
+            constraint.literals.iter().cloned().filter(|&literal| !engine.is_assigned(literal.index())),
+        ),
+        bound: constraint.bound - number_of_trues,
+    });
+}
+
+/// 読み込み時の簡約
+///
+/// 正規化・ SATEngine による決定レベル 0 での伝播・等価リテラルの置換・包含除去・変数除去を順に行い，
+/// 残った論理式と解の修復に用いる ReconstructionStack を返す．
+/// 基数制約・擬似ブール制約・ XOR 制約は節に書き換えずにそのまま残すので，それらに現れる変数は置換・除去の対象にしない．
+/// is_xor_detection_enabled であれば，節として書かれた XOR 制約を認識して XOR 制約に書き換える．
+pub struct Preprocess {
+    effort: usize,
+    is_xor_detection_enabled: bool,
+    database: ClauseDatabase,
+    /// 変数 -> 基数制約・擬似ブール制約・ XOR 制約に現れるか否か
+    is_frozen: Array<u32, bool>,
+    /// 外部の伝播器が観測するので全ての変数を置換・除去の対象にしないか否か
+    is_every_variable_frozen: bool,
+    literal_marks: Set<u32>,
+    clause_ids: Array<u32, u32>,
+    resolvents: Array<u32, Array<u32, Literal>>,
+    gate_extraction: GateExtraction,
+    /// 決定レベル 0 での伝播の直後に認識したゲート
+    gates: Array<u32, Gate>,
+    gate_summary: GateSummary,
+    summary: PreprocessSummary,
+}
+
+impl Preprocess {
+    /// 変数除去を行う変数の出現数(正・負の積)の上限
+    const MAX_NUMBER_OF_RESOLUTION_PAIRS: usize = 400;
+    /// 変数除去で生成する導出節の長さの上限
+    const MAX_RESOLVENT_LENGTH: u32 = 32;
+    /// 認識する XOR 制約の長さの上限
+    const MAX_XOR_LENGTH: u32 = 6;
+
+    pub fn new(effort: usize, is_xor_detection_enabled: bool) -> Self {
+        Self {
+            effort: effort,
+            is_xor_detection_enabled: is_xor_detection_enabled,
+            database: ClauseDatabase::default(),
+            is_frozen: Array::default(),
+            is_every_variable_frozen: false,
+            literal_marks: Set::default(),
+            clause_ids: Array::default(),
+            resolvents: Array::default(),
+            gate_extraction: GateExtraction::default(),
+            gates: Array::default(),
+            gate_summary: GateSummary::default(),
+            summary: PreprocessSummary::default(),
+        }
+    }
+
+    /// 全ての変数を置換・除去の対象にせず，固定された変数も単位節とともに残す
+    pub fn freeze_every_variable(&mut self) {
+        self.is_every_variable_frozen = true;
+    }
+
+    /// 認識したゲート(定義の節番号は認識した時点のもの)
+    pub fn gates(&self) -> &[Gate] {
+        return &self.gates[..];
+    }
+
+    /// 統計を "c" で始まる行として出力する
+    pub fn print_summary(&self) {
+        println!("c fixed variables: {}", self.summary.number_of_fixed_variables);
+        println!("c substituted variables: {}", self.summary.number_of_substituted_variables);
+        println!("c subsumed clauses: {}", self.summary.number_of_subsumed_clauses);
+        println!("c strengthened clauses: {}", self.summary.number_of_strengthened_clauses);
+        println!("c eliminated variables: {}", self.summary.number_of_eliminated_variables);
+        println!("c gate eliminations: {}", self.summary.number_of_gate_eliminations);
+        println!("c detected xor constraints: {}", self.summary.number_of_detected_xor_constraints);
+        println!("c and gates: {}", self.gate_summary.number_of_and_gates);
+        println!("c xor gates: {}", self.gate_summary.number_of_xor_gates);
+        println!("c ite gates: {}", self.gate_summary.number_of_if_then_else_gates);
+        println!("c equivalence gates: {}", self.gate_summary.number_of_equivalence_gates);
+    }
+
+    pub fn preprocess(&mut self, cnf: &CNF) -> PreprocessResult {
+        let mut reconstruction_stack = ReconstructionStack::default();
+
+        // 正規化
+        let mut clauses = Array::<u32, Array<u32, Literal>>::default();
+        for clause in cnf.clauses.iter() {
+            if let Some(normalized_clause) = ClauseDatabase::normalize(clause.iter().cloned()) {
+                if normalized_clause.is_empty() {
+                    return PreprocessResult::Unsatisfiable;
+                }
+                clauses.push(normalized_clause);
+            }
+        }
+
+        // 決定レベル 0 での伝播
+        let mut engine = new_sat_engine(
+            ExternalPropagatorTheory::default(),
+            IntegerTheory::default(),
+            DifferenceLogicTheory::default(),
+            AcyclicityTheory::default(),
+            XorTheory::default(),
+            PseudoBooleanTheory::new(0),
+            CardinalityTheory::default(),
+            ClauseTheory::new(1e4),
+            BinaryClauseTheory::new(0),
+        );
+        for _ in 0..cnf.number_of_variables {
+            engine.add_variable(Boolean::FALSE);
+        }
+        for clause in clauses.iter() {
+            if !add_clause_at_root(&mut engine, &clause[..]) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        for constraint in cnf.cardinality_constraints.iter() {
+            if !add_cardinality_constraint_at_root(&mut engine, constraint) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        for constraint in cnf.pseudo_boolean_constraints.iter() {
+            if !add_pseudo_boolean_constraint_at_root(&mut engine, constraint) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        for constraint in cnf.xor_constraints.iter() {
+            if !add_xor_constraint_at_root(&mut engine, constraint) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        for edge in cnf.acyclicity_edges.iter() {
+            if !add_acyclicity_edge_at_root(&mut engine, edge) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        for atom in cnf.difference_atoms.iter() {
+            if !add_difference_atom_at_root(&mut engine, atom) {
+                return PreprocessResult::Unsatisfiable;
+            }
+        }
+        self.database = ClauseDatabase::new(cnf.number_of_variables);
+        for index in 0..cnf.number_of_variables {
+            if engine.is_assigned(index) {
+                let literal = Literal::new(index, engine.get_value(index));
+                reconstruction_stack.push(literal, [literal].into_iter());
+                self.summary.number_of_fixed_variables += 1;
+            }
+        }
+        for clause in clauses.iter() {
+            if clause.iter().any(|&literal| engine.is_true(literal)) {
+                continue;
+            }
+            self.database.add_clause(clause.iter().cloned().filter(|&literal| !engine.is_false(literal)));
+        }
+        let mut cardinality_constraints = Array::<u32, CardinalityConstraint>::default();
+        let mut pseudo_boolean_constraints = Array::<u32, PseudoBooleanConstraint>::default();
+        self.is_frozen.clear();
+        self.is_frozen.resize(cnf.number_of_variables, self.is_every_variable_frozen);
+        // 理論が観測する変数は固定し，決定レベル 0 で割り当てられたものは単位節とともに残す
+        // (全ての変数を固定する場合は後でまとめて残す)
+        for index in cnf.theory_variables() {
+            if self.is_frozen[index] {
+                continue;
+            }
+            self.is_frozen[index] = true;
+            if engine.is_assigned(index) {
+                self.database.add_clause([Literal::new(index, engine.get_value(index))].into_iter());
+            }
+        }
+        for constraint in cnf.pseudo_boolean_constraints.iter() {
+            if let Some(constraint) = simplify_pseudo_boolean_constraint_at_root(&engine, constraint) {
+                for &(_, literal) in constraint.terms.iter() {
+                    self.is_frozen[literal.index()] = true;
+                }
+                pseudo_boolean_constraints.push(constraint);
+            }
+        }
+        for constraint in cnf.cardinality_constraints.iter() {
+            if let Some(constraint) = simplify_cardinality_constraint_at_root(&engine, constraint) {
+                debug_assert!(constraint.literals.len() > constraint.bound);
+                if constraint.bound == 1 {
+                    self.database.add_clause(constraint.literals.into_iter());
+                    continue;
+                }
+                for literal in constraint.literals.iter() {
+                    self.is_frozen[literal.index()] = true;
+                }
+                cardinality_constraints.push(constraint);
+            }
+        }
+        let mut xor_constraints = Array::<u32, XorConstraint>::default();
+        for constraint in cnf.xor_constraints.iter() {
+            let constraint = simplify_xor_constraint_at_root(&engine, constraint);
+            if constraint.variables.len() <= 2 {
+                for clause in constraint.clauses().iter() {
+                    self.database.add_clause(clause.iter().cloned());
+                }
+                continue;
+            }
+            xor_constraints.push(constraint);
+        }
+        if self.is_xor_detection_enabled {
+            self.extract_xor_constraints(&mut xor_constraints);
+        }
+        for constraint in xor_constraints.iter() {
+            for &index in constraint.variables.iter() {
+                self.is_frozen[index] = true;
+            }
+        }
+        // 偽に固定された辺は存在しないので取り除く
+        let acyclicity_edges = Array::<u32, AcyclicityEdge>::from_iter(
+            cnf.acyclicity_edges.iter().filter(|edge| !engine.is_false(edge.literal)).cloned(),
+        );
+        // 外部の伝播器は全ての変数の割り当てを観測するので，固定された変数も単位節とともに残す
+        if self.is_every_variable_frozen {
+            for index in 0..cnf.number_of_variables {
+                if engine.is_assigned(index) {
+                    self.database.add_clause([Literal::new(index, engine.get_value(index))].into_iter());
+                }
+            }
+        }
+
+        // ゲートの認識
+        self.gates = self.gate_extraction.extract_gates(&self.database);
+        for gate in self.gates.iter() {
+            self.gate_summary.add(gate);
+        }
+
+        if !self.substitute_equivalences(&mut reconstruction_stack) {
+            return PreprocessResult::Unsatisfiable;
+        }
+        if !self.subsume() {
+            return PreprocessResult::Unsatisfiable;
+        }
+        if !self.eliminate_variables(&mut reconstruction_stack) {
+            return PreprocessResult::Unsatisfiable;
+        }
+
+        let mut residual_cnf = CNF {
+            number_of_variables: cnf.number_of_variables,
+            clauses: Array::default(),
+            cardinality_constraints: cardinality_constraints,
+            pseudo_boolean_constraints: pseudo_boolean_constraints,
+            xor_constraints: xor_constraints,
+            acyclicity_edges: acyclicity_edges,
+            difference_atoms: cnf.difference_atoms.clone(),
+            integer_variables: cnf.integer_variables.clone(),
+            order_literals: cnf.order_literals.clone(),
+            integer_constraints: cnf.integer_constraints.clone(),
+        };
+        for clause_id in self.database.clause_ids() {
+            residual_cnf.clauses.push(Array::from_iter(self.database.clause(clause_id).iter().cloned()));
+        }
+        return PreprocessResult::Simplified {
+            cnf: Box::new(residual_cnf),
+            reconstruction_stack: reconstruction_stack,
+        };
+    }
+
+    /// 変数の集合が等しく否定リテラルの数の偶奇が等しい 2^(k - 1) 個の k 変数の節を XOR 制約として認識し，
+    /// 節を取り除いて xor_constraints に加える
+    fn extract_xor_constraints(&mut self, xor_constraints: &mut Array<u32, XorConstraint>) {
+        // (変数の並び, 否定リテラルの位置のマスク, 節の番号) を変数の並びで整列してまとめる
+        let mut candidates = Array::<u32, (Array<u32, u32>, u32, u32)>::default();
+        for clause_id in self.database.clause_ids() {
+            let clause = self.database.clause(clause_id);
+            let len = clause.len() as u32;
+            if !(3..=Self::MAX_XOR_LENGTH).contains(&len) {
+                continue;
+            }
+            let mut literals = Array::<u32, Literal>::from_iter(clause.iter().cloned());
+            literals.sort_unstable_by_key(|literal| literal.index());
+            let mask = (0..len).filter(|&k| literals[k].value() == Boolean::FALSE).fold(0, |mask, k| mask | (1 << k));
+            candidates.push((Array::from_iter(literals.iter().map(|literal| literal.index())), mask, clause_id));
+        }
+        candidates.sort_unstable_by(|l, r| l.0.as_slice().cmp(r.0.as_slice()).then(l.1.cmp(&r.1)));
+        let mut start = 0;
+        while start < candidates.len() {
+            let mut end = start + 1;
+            while end < candidates.len() && candidates[end].0.as_slice() == candidates[start].0.as_slice() {
+                end += 1;
+            }
+            let variables = &candidates[start].0;
+            let number_of_clauses_per_parity = 1 << (variables.len() - 1);
+            for negated_parity in [0, 1] {
+                // 否定リテラルの数の偶奇が negated_parity である節は，偶奇が negated_parity の割り当てを全て禁止する
+                let mut number_of_masks = 0;
+                for k in start..end {
+                    if candidates[k].1.count_ones() % 2 == negated_parity
+                        && (k == start || candidates[k - 1].1 != candidates[k].1)
+                    {
+                        number_of_masks += 1;
+                    }
+                }
+                if number_of_masks != number_of_clauses_per_parity {
+                    continue;
+                }
+                for k in start..end {
+                    if candidates[k].1.count_ones() % 2 == negated_parity {
+                        self.database.remove_clause(candidates[k].2);
+                    }
+                }
+                xor_constraints.push(XorConstraint { variables: variables.clone(), parity: negated_parity == 0 });
+                self.summary.number_of_detected_xor_constraints += 1;
+            }
+            start = end;
+        }
+    }
+
+    /// 2 項節の含意グラフの強連結成分ごとに代表リテラルを定め，他のリテラルを代表リテラルで置き換える
+    ///
+    /// 基数制約に現れる変数は置き換えず，強連結成分に含まれていればそれを代表にする．
+    fn substitute_equivalences(&mut self, reconstruction_stack: &mut ReconstructionStack) -> bool {
+        const NULL: u32 = u32::MAX;
+        let number_of_literals = 2 * self.database.number_of_variables();
+        let mut implications = Array::<u32, Array<u32, u32>>::default();
+        implications.resize_with(number_of_literals, Array::default);
+        for clause_id in self.database.clause_ids() {
+            if let &[l0, l1] = self.database.clause(clause_id) {
+                implications[(!l0).bits()].push(l1.bits());
+                implications[(!l1).bits()].push(l0.bits());
+            }
+        }
+
+        // Tarjan のアルゴリズム(再帰を明示的なスタックで置き換えたもの)
+        let mut discovery_orders = Array::<u32, u32>::default();
+        discovery_orders.resize(number_of_literals, NULL);
+        let mut lowlinks = Array::<u32, u32>::default();
+        lowlinks.resize(number_of_literals, NULL);
+        let mut is_on_stack = Array::<u32, bool>::default();
+        is_on_stack.resize(number_of_literals, false);
+        let mut representatives = Array::<u32, u32>::default();
+        representatives.resize(number_of_literals, NULL);
+        let mut component_stack = Array::<u32, u32>::default();
+        let mut call_stack = Array::<u32, (u32, u32)>::default();
+        let mut counter = 0;
+        for root in 0..number_of_literals {
+            if discovery_orders[root] != NULL {
+                continue;
+            }
+            discovery_orders[root] = counter;
+            lowlinks[root] = counter;
+            counter += 1;
+            component_stack.push(root);
+            is_on_stack[root] = true;
+            call_stack.push((root, 0));
+            while let Some(&(node, position)) = call_stack.last() {
+                if position < implications[node].len() {
+                    call_stack.last_mut().unwrap().1 += 1;
+                    let next = implications[node][position];
+                    if discovery_orders[next] == NULL {
+                        discovery_orders[next] = counter;
+                        lowlinks[next] = counter;
+                        counter += 1;
+                        component_stack.push(next);
+                        is_on_stack[next] = true;
+                        call_stack.push((next, 0));
+                    } else if is_on_stack[next] {
+                        lowlinks[node] = u32::min(lowlinks[node], discovery_orders[next]);
+                    }
+                } else {
+                    call_stack.pop();
+                    if let Some(&(parent, _)) = call_stack.last() {
+                        lowlinks[parent] = u32::min(lowlinks[parent], lowlinks[node]);
+                    }
+                    if lowlinks[node] == discovery_orders[node] {
+                        // 強連結成分を取り出し，基数制約に現れる変数を優先して添字が最小のリテラルを代表とする
+                        let position = component_stack.iter().rposition(|&l| l == node).unwrap() as u32;
+                        let representative =
+                            *component_stack[position..].iter().min_by_key(|&&l| (!self.is_frozen[l / 2], l)).unwrap();
+                        for &member in component_stack[position..].iter() {
+                            is_on_stack[member] = false;
+                            representatives[member] = representative;
+                        }
+                        component_stack.truncate(position);
+                    }
+                }
+            }
+        }
+
+        let literal_of = |bits: u32| Literal::new(bits / 2, Boolean::from(bits % 2 == 1));
+        let mut substituted_indices = Array::<u32, u32>::default();
+        for index in 0..self.database.number_of_variables() {
+            let literal = Literal::new(index, Boolean::TRUE);
+            if representatives[literal.bits()] == representatives[(!literal).bits()] {
+                // x と !x が等価なので充足不能
+                return false;
+            }
+            if self.is_frozen[index] {
+                // 代表でなくても置き換えない
+                representatives[literal.bits()] = literal.bits();
+                representatives[(!literal).bits()] = (!literal).bits();
+                continue;
+            }
+            let representative = literal_of(representatives[literal.bits()]);
+            if representative.index() != index {
+                debug_assert!(literal_of(representatives[(!literal).bits()]) == !representative);
+                reconstruction_stack.push(literal, [literal, !representative].into_iter());
+                reconstruction_stack.push(!literal, [!literal, representative].into_iter());
+                substituted_indices.push(index);
+            }
+        }
+        self.summary.number_of_substituted_variables += substituted_indices.len();
+
+        // 置き換えの対象となる変数を含む節を書き換える
+        self.clause_ids.clear();
+        for &index in substituted_indices.iter() {
+            for value in [Boolean::FALSE, Boolean::TRUE] {
+                self.clause_ids.extend(self.database.occurrences(Literal::new(index, value)).iter().cloned());
+            }
+        }
+        self.clause_ids.sort_unstable_by_key(|&clause_id| clause_id);
+        for k in 0..self.clause_ids.len() {
+            let clause_id = self.clause_ids[k];
+            if k > 0 && self.clause_ids[k - 1] == clause_id {
+                continue;
+            }
+            let substituted_clause = ClauseDatabase::normalize(
+                self.database.clause(clause_id).iter().map(|literal| literal_of(representatives[literal.bits()])),
+            );
+            self.database.remove_clause(clause_id);
+            if let Some(substituted_clause) = substituted_clause {
+                debug_assert!(!substituted_clause.is_empty());
+                self.database.add_clause(substituted_clause.into_iter());
+            }
+        }
+        return true;
+    }
+
+    /// 包含除去と自己包含導出による節の縮小
+    fn subsume(&mut self) -> bool {
+        self.clause_ids.clear();
+        self.clause_ids.extend(self.database.clause_ids());
+        self.clause_ids.sort_by_cached_key(|&clause_id| self.database.clause(clause_id).len());
+        let mut ticks = 0;
+        let mut candidates = Array::<u32, u32>::default();
+        for k in 0..self.clause_ids.len() {
+            if ticks >= self.effort {
+                break;
+            }
+            let clause_id = self.clause_ids[k];
+            if self.database.is_removed(clause_id) {
+                continue;
+            }
+            let clause = self.database.clause(clause_id);
+            self.literal_marks.clear();
+            for literal in clause.iter() {
+                self.literal_marks.insert(literal.bits());
+            }
+            // 包含・自己包含される節は出現数最小の変数を必ず含む
+            let pivot = *clause
+                .iter()
+                .min_by_key(|&&l| self.database.occurrences(l).len() + self.database.occurrences(!l).len())
+                .unwrap();
+            candidates.clear();
+            candidates.extend(self.database.occurrences(pivot).iter().cloned());
+            candidates.extend(self.database.occurrences(!pivot).iter().cloned());
+            let clause_len = clause.len() as u32;
+            for &other_clause_id in candidates.iter() {
+                if other_clause_id == clause_id || self.database.is_removed(other_clause_id) {
+                    continue;
+                }
+                let other_clause = self.database.clause(other_clause_id);
+                if (other_clause.len() as u32) < clause_len {
+                    continue;
+                }
+                ticks += other_clause.len();
+                let mut number_of_matches = 0;
+                let mut negated_literal = None;
+                let mut number_of_negated_literals = 0;
+                for &literal in other_clause.iter() {
+                    if self.literal_marks.contains_key(literal.bits()) {
+                        number_of_matches += 1;
+                    } else if self.literal_marks.contains_key((!literal).bits()) {
+                        number_of_negated_literals += 1;
+                        negated_literal = Some(literal);
+                    }
+                }
+                if number_of_matches == clause_len {
+                    self.database.remove_clause(other_clause_id);
+                    self.summary.number_of_subsumed_clauses += 1;
+                } else if number_of_matches + 1 == clause_len && number_of_negated_literals == 1 {
+                    self.database.strengthen_clause(other_clause_id, negated_literal.unwrap());
+                    self.summary.number_of_strengthened_clauses += 1;
+                    if self.database.clause(other_clause_id).is_empty() {
+                        return false;
+                    }
+                }
+            }
+        }
+        return true;
+    }
+
+    /// 導出節の数が元の節の数を超えない変数を除去する(bounded variable elimination)
+    ///
+    /// 変数を出力とするゲートの定義が見つかれば，定義の節とそれ以外の節の組についてだけ導出する．
+    /// 定義の節同士の導出節は恒真，それ以外の節同士の導出節は他の導出節から導かれるので省いてよい．
+    fn eliminate_variables(&mut self, reconstruction_stack: &mut ReconstructionStack) -> bool {
+        let number_of_occurrences = |database: &ClauseDatabase, index: u32| {
+            let literal = Literal::new(index, Boolean::TRUE);
+            (database.occurrences(literal).len(), database.occurrences(!literal).len())
+        };
+        let mut candidates = Array::<u32, u32>::from_iter((0..self.database.number_of_variables()).filter(|&index| {
+            let (p, n) = number_of_occurrences(&self.database, index);
+            !self.is_frozen[index] && p + n > 0 && p * n <= Self::MAX_NUMBER_OF_RESOLUTION_PAIRS
+        }));
+        candidates.sort_by_cached_key(|&index| {
+            let (p, n) = number_of_occurrences(&self.database, index);
+            (p * n, index)
+        });
+        let mut ticks = 0;
+        for &index in candidates.iter() {
+            if ticks >= self.effort {
+                break;
+            }
+            let literal = Literal::new(index, Boolean::TRUE);
+            let (p, n) = number_of_occurrences(&self.database, index);
+            if p + n == 0 || p * n > Self::MAX_NUMBER_OF_RESOLUTION_PAIRS {
+                continue;
+            }
+            let gate = self.gate_extraction.find_gate(&self.database, index);
+            // 導出節を生成し，数が元の節の数を超えれば中止
+            self.resolvents.clear();
+            let mut is_eliminable = true;
+            'loop_for_pairs: for &positive_clause_id in self.database.occurrences(literal).iter() {
+                for &negative_clause_id in self.database.occurrences(!literal).iter() {
+                    if let Some(gate) = &gate
+                        && gate.definition.contains(&positive_clause_id)
+                            == gate.definition.contains(&negative_clause_id)
+                    {
+                        continue;
+                    }
+                    let positive_clause = self.database.clause(positive_clause_id);
+                    let negative_clause = self.database.clause(negative_clause_id);
+                    ticks += positive_clause.len() + negative_clause.len();
+                    if let Some(resolvent) =
+                        Self::resolve(&mut self.literal_marks, positive_clause, negative_clause, literal)
+                    {
+                        if resolvent.len() > Self::MAX_RESOLVENT_LENGTH || self.resolvents.len() as usize >= p + n {
+                            is_eliminable = false;
+                            break 'loop_for_pairs;
+                        }
+                        self.resolvents.push(resolvent);
+                    }
+                }
+            }
+            if !is_eliminable {
+                continue;
+            }
+            // 出現数の少ない側の節を witness とともに積み，最後に反対側の単位節を積む
+            let witness = if p <= n { literal } else { !literal };
+            for &clause_id in self.database.occurrences(witness).iter() {
+                reconstruction_stack.push(witness, self.database.clause(clause_id).iter().cloned());
+            }
+            reconstruction_stack.push(!witness, [!witness].into_iter());
+            self.clause_ids.clear();
+            self.clause_ids.extend(self.database.occurrences(literal).iter().cloned());
+            self.clause_ids.extend(self.database.occurrences(!literal).iter().cloned());
+            for &clause_id in self.clause_ids.iter() {
+                self.database.remove_clause(clause_id);
+            }
+            for resolvent in self.resolvents.iter() {
+                if resolvent.is_empty() {
+                    return false;
+                }
+                self.database.add_clause(resolvent.iter().cloned());
+            }
+            self.summary.number_of_eliminated_variables += 1;
+            if gate.is_some() {
+                self.summary.number_of_gate_eliminations += 1;
+            }
+        }
+        return true;
+    }
+
+    /// literal についての導出節を返す．恒真であれば None を返す
+    fn resolve(
+        literal_marks: &mut Set<u32>,
+        positive_clause: &[Literal],
+        negative_clause: &[Literal],
+        literal: Literal,
+    ) -> Option<Array<u32, Literal>> {
+        literal_marks.clear();
+        let mut resolvent = Array::default();
+        for &l in positive_clause.iter().filter(|&&l| l != literal) {
+            literal_marks.insert(l.bits());
+            resolvent.push(l);
+        }
+        for &l in negative_clause.iter().filter(|&&l| l != !literal) {
+            if literal_marks.contains_key((!l).bits()) {
+                return None;
+            }
+            if !literal_marks.contains_key(l.bits()) {
+                resolvent.push(l);
+            }
+        }
+        return Some(resolvent);
+    }
+}
@@ -1,42 +1,9 @@
+use cdcl_engine::EngineTrait;
 use utility::{Array, HeapedMap};
 
-#[derive(Default, Clone, Copy)]
-struct CompareValues;
-
-impl<IndexT, ValueT> FnOnce<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    type Output = std::cmp::Ordering;
-    extern "rust-call" fn call_once(
-        self,
-        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
-    ) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
-
-impl<IndexT, ValueT> FnMut<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    extern "rust-call" fn call_mut(
-        &mut self,
-        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
-    ) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
-
-impl<IndexT, ValueT> Fn<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    extern "rust-call" fn call(&self, ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT))) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
+use crate::decision_heuristic::{CompareValues, DecisionHeuristic};
 
+/// EVSIDS: 解析に現れた変数の活性度を，矛盾のたびに指数的に大きくなる値だけ増やす
 // NOTE: ネーミングが微妙な気もするので，実装が固まったら再検討する
 #[derive(Clone)]
 pub struct Pricer {
@@ -55,8 +22,10 @@ impl Pricer {
             activity_increase_value: 1.0,
         }
     }
+}
 
-    pub fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
+impl DecisionHeuristic for Pricer {
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
         let index = self.activities.len();
         self.activities.push(initial_activity);
         if !is_assigned {
@@ -64,22 +33,32 @@ impl Pricer {
         }
     }
 
-    pub fn set_to_unassigned(&mut self, index: u32) {
+    fn set_to_unassigned(&mut self, index: u32) {
         if !self.unassigned_variable_queue.contains_key(index) {
             let activity = self.activities[index];
             self.unassigned_variable_queue.insert(index, activity);
         }
     }
 
-    pub fn peek(&self) -> Option<u32> {
+    fn peek(&self) -> Option<u32> {
         return self.unassigned_variable_queue.first().map(|(&index, _)| index);
     }
 
-    pub fn set_to_assigned(&mut self, index: u32) {
+    fn activity(&self, index: u32) -> f64 {
+        return self.activities[index];
+    }
+
+    fn set_to_assigned(&mut self, index: u32) {
         self.unassigned_variable_queue.remove(index);
     }
 
-    pub fn increase_price(&mut self, indices: impl Iterator<Item = u32>) {
+    fn increase_price(
+        &mut self,
+        related_variables: impl Iterator<Item = u32>,
+        _reason_side_variables: impl Iterator<Item = u32>,
+        _engine: &impl EngineTrait,
+        _backjump_level: u32,
+    ) {
         self.activity_increase_value /= 1.0 - 1.0 / self.time_constant;
         if self.activity_increase_value > 1e4 {
             for index in 0..self.activities.len() {
@@ -90,7 +69,7 @@ impl Pricer {
             }
             self.activity_increase_value = 1.0;
         }
-        for index in indices {
+        for index in related_variables {
             self.activities[index] += self.activity_increase_value;
             if self.unassigned_variable_queue.contains_key(index) {
                 self.unassigned_variable_queue.insert(index, self.activities[index]);
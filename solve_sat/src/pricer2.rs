@@ -1,50 +1,22 @@
 use cdcl_engine::EngineTrait;
 use utility::{Array, HeapedMap, Set};
 
-#[derive(Default, Clone, Copy)]
-struct CompareValues;
-
-impl<IndexT, ValueT> FnOnce<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    type Output = std::cmp::Ordering;
-    extern "rust-call" fn call_once(
-        self,
-        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
-    ) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
-
-impl<IndexT, ValueT> FnMut<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    extern "rust-call" fn call_mut(
-        &mut self,
-        ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT)),
-    ) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
-
-impl<IndexT, ValueT> Fn<(&(IndexT, ValueT), &(IndexT, ValueT))> for CompareValues
-where
-    ValueT: std::cmp::PartialOrd,
-{
-    extern "rust-call" fn call(&self, ((_, lhs), (_, rhs)): (&(IndexT, ValueT), &(IndexT, ValueT))) -> Self::Output {
-        rhs.partial_cmp(lhs).unwrap()
-    }
-}
+use crate::decision_heuristic::{CompareValues, DecisionHeuristic};
 
+/// 矛盾の解析に現れたか否か(0 または 1)を目標値とし，活性度を目標値に近づける Adam 風の更新で優先度を学習する
+///
+/// 活性度の変化量は学習率 1 / time_constant 程度に正規化されるので，目標値との差の大きさによらず一定の速さで追従する．
 // NOTE: ネーミングが微妙な気もするので，実装が固まったら再検討する
 #[derive(Clone)]
 pub struct Pricer {
+    learning_rate: f64,
     activities: Array<u32, f64>,
+    /// 勾配の 1 次モーメントの指数移動平均
     m: Array<u32, f64>,
+    /// 勾配の 2 次モーメントの指数移動平均
     v: Array<u32, f64>,
-    time: usize,
+    /// 変数 -> 活性度を更新した回数(モーメントのバイアス補正に用いる)
+    number_of_updates: Array<u32, i32>,
     target_variables: Set<u32>,
     unassigned_variable_queue: HeapedMap<u32, f64, CompareValues>,
 }
@@ -52,82 +24,136 @@ pub struct Pricer {
 impl Pricer {
     pub fn new(time_constant: f64) -> Self {
         Self {
+            learning_rate: 1.0 / time_constant,
             activities: Array::default(),
             m: Array::default(),
             v: Array::default(),
-            time: 0,
+            number_of_updates: Array::default(),
             target_variables: Set::default(),
             unassigned_variable_queue: HeapedMap::default(),
         }
     }
 
-    pub fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
+    /// 活性度を target に近づける
+    fn update(&mut self, index: u32, target: f64) {
+        const BETA1: f64 = 0.9;
+        const BETA2: f64 = 0.999;
+        const EPSILON: f64 = 1e-8;
+        let g = target - self.activities[index];
+        self.m[index] = BETA1 * self.m[index] + (1.0 - BETA1) * g;
+        self.v[index] = BETA2 * self.v[index] + (1.0 - BETA2) * g * g;
+        self.number_of_updates[index] = self.number_of_updates[index].saturating_add(1);
+        let m_hat = self.m[index] / (1.0 - BETA1.powi(self.number_of_updates[index]));
+        let v_hat = self.v[index] / (1.0 - BETA2.powi(self.number_of_updates[index]));
+        self.activities[index] += self.learning_rate * m_hat / (v_hat.sqrt() + EPSILON);
+        if self.unassigned_variable_queue.contains_key(index) {
+            self.unassigned_variable_queue.insert(index, self.activities[index]);
+        }
+    }
+}
+
+impl DecisionHeuristic for Pricer {
+    fn add_variable(&mut self, initial_activity: f64, is_assigned: bool) {
         let index = self.activities.len();
         self.activities.push(initial_activity);
         self.m.push(0.0);
         self.v.push(0.0);
+        self.number_of_updates.push(0);
         if !is_assigned {
             self.unassigned_variable_queue.insert(index, initial_activity);
         }
     }
 
-    pub fn set_to_unassigned(&mut self, index: u32) {
+    fn set_to_unassigned(&mut self, index: u32) {
         if !self.unassigned_variable_queue.contains_key(index) {
             let activity = self.activities[index];
             self.unassigned_variable_queue.insert(index, activity);
         }
     }
 
-    pub fn peek(&self) -> Option<u32> {
+    fn peek(&self) -> Option<u32> {
         return self.unassigned_variable_queue.first().map(|(&index, _)| index);
     }
 
-    pub fn set_to_assigned(&mut self, index: u32) {
+    fn activity(&self, index: u32) -> f64 {
+        return self.activities[index];
+    }
+
+    fn set_to_assigned(&mut self, index: u32) {
         self.unassigned_variable_queue.remove(index);
     }
 
-    pub fn increase_price(
+    fn increase_price(
         &mut self,
-        indices: impl Iterator<Item = u32>,
+        related_variables: impl Iterator<Item = u32>,
+        _reason_side_variables: impl Iterator<Item = u32>,
         engine: &impl EngineTrait,
         backjump_level: u32,
     ) {
-        self.time += 1;
         self.target_variables.clear();
-        for index in indices {
+        for index in related_variables {
             if engine.get_decision_level(index) == engine.current_decision_level() {
                 self.target_variables.insert(index);
             } else {
-                self.activities[index] += 0.1 * (1.0 - self.activities[index]);
-                if self.unassigned_variable_queue.contains_key(index) {
-                    self.unassigned_variable_queue.insert(index, self.activities[index]);
-                }
+                self.update(index, 1.0);
             }
         }
+        // 取り消される決定レベルで割り当てた変数は，現在の決定レベルで解析に現れたか否かを目標値にする
         for decision_level in (backjump_level + 1)..(engine.current_decision_level() + 1) {
-            // for decision_level in engine.current_decision_level()..(engine.current_decision_level() + 1) {
             for assignment_level in engine.get_assignment_order_range(decision_level) {
                 let index = engine.get_assignment(assignment_level).index();
-                let g = (if self.target_variables.contains_key(index) { 1.0 } else { 0.0 }) - self.activities[index];
-                self.activities[index] += 0.1 * g;
-                if self.unassigned_variable_queue.contains_key(index) {
-                    self.unassigned_variable_queue.insert(index, self.activities[index]);
-                }
+                let target = if self.target_variables.contains_key(index) { 1.0 } else { 0.0 };
+                self.update(index, target);
             }
         }
-        // for index in indices {
-        //     self.target_variables.insert(index);
-        // }
-        // for decision_level in 1..(engine.current_decision_level() + 1) {
-        //     for assignment_level in engine.get_assignment_order_range(decision_level) {
-        //         let index = engine.get_assignment(assignment_level).index();
-        //         let g = (if self.target_variables.contains_key(index) { 1.0 } else {0.0}) - self.activities[index];
-        //         let a = if decision_level <= backjump_level {0.0001} else {0.1};
-        //         self.activities[index] += a * g;
-        //         if self.unassigned_variable_queue.contains_key(index) {
-        //             self.unassigned_variable_queue.insert(index, self.activities[index]);
-        //         }
-        //     }
-        // }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pricer;
+    use crate::decision_heuristic::DecisionHeuristic;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    /// バイアス補正により，最初の更新は目標値との差の大きさによらず学習率だけ目標値に近づくことを確かめる
+    #[test]
+    fn test_first_update_moves_by_learning_rate() {
+        let mut pricer = Pricer::new(10.0);
+        pricer.add_variable(0.0, false);
+        pricer.add_variable(0.9, false);
+        pricer.add_variable(0.5, false);
+        pricer.update(0, 1.0);
+        pricer.update(1, 1.0);
+        pricer.update(2, 0.0);
+        assert_close(pricer.activity(0), 0.1);
+        assert_close(pricer.activity(1), 1.0);
+        assert_close(pricer.activity(2), 0.4);
+    }
+
+    /// 2 回目の更新がバイアス補正したモーメントの比で進むことを確かめる
+    #[test]
+    fn test_second_update_uses_bias_corrected_moments() {
+        let mut pricer = Pricer::new(10.0);
+        pricer.add_variable(0.0, false);
+        pricer.update(0, 1.0);
+        pricer.update(0, 1.0);
+        // m_hat = 0.18 / (1 - 0.9^2), v_hat = 0.001809 / (1 - 0.999^2) で 0.1 + 0.1 * m_hat / sqrt(v_hat)
+        assert_close(pricer.activity(0), 0.19958777);
+    }
+
+    /// 更新した活性度が未割り当ての変数の順序に反映されることを確かめる
+    #[test]
+    fn test_update_reorders_unassigned_variables() {
+        let mut pricer = Pricer::new(10.0);
+        pricer.add_variable(0.0, false);
+        pricer.add_variable(0.05, false);
+        assert_eq!(pricer.peek(), Some(1));
+        pricer.update(0, 1.0);
+        assert_eq!(pricer.peek(), Some(0));
+        pricer.set_to_assigned(0);
+        assert_eq!(pricer.peek(), Some(1));
     }
 }
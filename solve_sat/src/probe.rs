@@ -0,0 +1,164 @@
+use cdcl_engine::{Boolean, EngineAddConstraintTrait, EngineGetTheoryTrait, EngineTrait, Literal, Reason};
+use utility::{Array, Map};
+
+use crate::{
+    binary_clause_theory::BinaryClauseTheory,
+    clause_theory::ClauseTrait,
+    engine::{Constraint, SATEngine, make_constraint},
+};
+
+pub enum ProbeResult {
+    Unsatisfiable,
+    Completed,
+}
+
+#[derive(Default, Clone)]
+pub struct ProbeSummary {
+    pub number_of_probes: u32,
+    pub number_of_failed_literals: u32,
+    pub number_of_hyper_binary_resolvents: u32,
+}
+
+/// 決定レベル 0 での失敗リテラル探索(probing)
+///
+/// 2 項節の含意グラフの根となるリテラルを仮に割り当てて伝播させ，矛盾すれば否定を単位節として追加する．
+/// 矛盾しなければ，長い節によって伝播したリテラルについて hyper-binary resolution を行い，
+/// 含意グラフ上の支配リテラルからの近道となる 2 項節を学習する．
+#[derive(Default)]
+pub struct Probe {
+    cursor: u32,
+    /// 決定レベル 1 で割り当てられた変数 -> 含意グラフ上の親リテラル
+    parents: Map<u32, Literal>,
+    hyper_binary_resolvents: Array<u32, [Literal; 2]>,
+    summary: ProbeSummary,
+}
+
+impl Probe {
+    pub fn summary(&self) -> &ProbeSummary {
+        return &self.summary;
+    }
+
+    pub fn probe(&mut self, engine: &mut SATEngine, effort: usize) -> ProbeResult {
+        debug_assert!(engine.current_decision_level() == 0);
+        let number_of_variables = engine.number_of_variables();
+        if number_of_variables == 0 {
+            return ProbeResult::Completed;
+        }
+        let mut ticks = 0;
+        for _ in 0..number_of_variables {
+            if ticks >= effort {
+                break;
+            }
+            self.cursor %= number_of_variables;
+            let index = self.cursor;
+            self.cursor += 1;
+            for value in [Boolean::TRUE, Boolean::FALSE] {
+                if engine.is_assigned(index) {
+                    break;
+                }
+                let literal = Literal::new(index, value);
+                if !Self::is_root(literal, engine) {
+                    continue;
+                }
+                if Self::probe_literal(&mut self.parents, &mut self.hyper_binary_resolvents, literal, engine, &mut ticks)
+                {
+                    // 失敗リテラル: 否定を単位節として追加
+                    self.summary.number_of_failed_literals += 1;
+                    let result = engine.add_constraint(make_constraint([!literal].into_iter()).into(), true);
+                    if result.is_conflict() {
+                        return ProbeResult::Unsatisfiable;
+                    }
+                } else {
+                    for k in 0..self.hyper_binary_resolvents.len() {
+                        let hyper_binary_resolvent = self.hyper_binary_resolvents[k];
+                        let result =
+                            engine.add_constraint(make_constraint(hyper_binary_resolvent.into_iter()).into(), true);
+                        if result.is_conflict() {
+                            return ProbeResult::Unsatisfiable;
+                        }
+                    }
+                    self.summary.number_of_hyper_binary_resolvents += self.hyper_binary_resolvents.len();
+                }
+                self.summary.number_of_probes += 1;
+            }
+        }
+        return ProbeResult::Completed;
+    }
+
+    /// 含意グラフに入次数 0 かつ出次数 1 以上のリテラルであるかを判定
+    fn is_root(literal: Literal, engine: &SATEngine) -> bool {
+        let binary_clause_theory = engine.get_theory::<BinaryClauseTheory>();
+        return binary_clause_theory.implications(!literal).next().is_none()
+            && binary_clause_theory.implications(literal).next().is_some();
+    }
+
+    /// literal を仮に割り当てて伝播させ，矛盾したか否かを返す
+    fn probe_literal(
+        parents: &mut Map<u32, Literal>,
+        hyper_binary_resolvents: &mut Array<u32, [Literal; 2]>,
+        literal: Literal,
+        engine: &mut SATEngine,
+        ticks: &mut usize,
+    ) -> bool {
+        parents.clear();
+        hyper_binary_resolvents.clear();
+        let propagation_result = engine.assign(literal, Reason::Decision);
+        *ticks += engine.get_assignment_order_range(1).len();
+        if propagation_result.is_conflict() {
+            engine.backjump(0).for_each(drop);
+            return true;
+        }
+        for assignment_order in engine.get_assignment_order_range(1) {
+            let propagated_literal = engine.get_assignment(assignment_order);
+            let Some(Reason::Propagation { explain_key }) = engine.get_explained_reason(propagated_literal.index())
+            else {
+                debug_assert!(propagated_literal == literal);
+                continue;
+            };
+            let reason_constraint: Constraint<_> = engine.explain_propagation(explain_key.into()).into();
+            let parent = match reason_constraint {
+                Constraint::MonadicClause(_) => unreachable!(),
+                Constraint::BinaryClause(binary_clause) => {
+                    let antecedent = binary_clause.iter().find(|l| l.index() != propagated_literal.index()).unwrap();
+                    if engine.get_decision_level(antecedent.index()) == 0 { literal } else { !antecedent }
+                }
+                Constraint::Clause(clause) => {
+                    // 決定レベル 1 の原因リテラル全ての支配リテラルを求める
+                    let mut dominator = None;
+                    let mut number_of_antecedents = 0;
+                    for antecedent in clause.iter_literals() {
+                        if antecedent.index() == propagated_literal.index()
+                            || engine.get_decision_level(antecedent.index()) == 0
+                        {
+                            continue;
+                        }
+                        number_of_antecedents += 1;
+                        dominator = Some(match dominator {
+                            None => !antecedent,
+                            Some(d) => Self::find_dominator(parents, d, !antecedent, engine),
+                        });
+                    }
+                    let dominator = dominator.unwrap_or(literal);
+                    if number_of_antecedents >= 2 {
+                        hyper_binary_resolvents.push([!dominator, propagated_literal]);
+                    }
+                    dominator
+                }
+            };
+            parents.insert(propagated_literal.index(), parent);
+        }
+        engine.backjump(0).for_each(drop);
+        return false;
+    }
+
+    fn find_dominator(parents: &Map<u32, Literal>, mut l0: Literal, mut l1: Literal, engine: &SATEngine) -> Literal {
+        while l0 != l1 {
+            if engine.get_assignment_order(l0.index()) > engine.get_assignment_order(l1.index()) {
+                l0 = *parents.get(l0.index()).unwrap();
+            } else {
+                l1 = *parents.get(l1.index()).unwrap();
+            }
+        }
+        return l0;
+    }
+}
@@ -0,0 +1,268 @@
+use cdcl_engine::{EngineGetTheoryTrait, EngineTrait, Literal, Reason};
+use utility::{Array, CalculateGCD, Map, Set};
+
+use crate::{
+    engine::{Constraint, ExplainKey, SATEngine},
+    pseudo_boolean_theory::{PseudoBooleanConstraint, PseudoBooleanTheory},
+};
+
+pub enum PseudoBooleanAnalyzeResult<IteratorT: Iterator<Item = u32> + Clone> {
+    Unsatisfiable,
+    Backjumpable {
+        backjump_decision_level: u32,
+        learnt_constraint: PseudoBooleanConstraint,
+        related_variables: IteratorT,
+    },
+    /// 解析を打ち切ったので，節を学習する解析に任せる
+    Aborted,
+}
+
+/// cutting planes (generalized resolution) による矛盾の解析
+///
+/// 矛盾した擬似ブール制約から始めて，現在の決定レベルで割り当てられたリテラルを割り当ての逆順にたどり，
+/// 割り当て理由の制約との線形結合でそのリテラルを消去することを，制約がより低い決定レベルで伝播するようになるまで繰り返す．
+/// 割り当て理由の制約は，伝播したリテラルの係数で割り切れない偽でないリテラルを弱化してから係数で割る(切り上げ)ことで，
+/// 結合した制約も矛盾したままになるようにする．擬似ブール制約以外の割り当て理由は節として扱う．
+#[derive(Default)]
+pub struct PseudoBooleanAnalyze {
+    calculate_gcd: CalculateGCD,
+    /// 変数 -> (係数, リテラル)
+    terms: Map<u32, (u64, Literal)>,
+    degree: u64,
+    reason_terms: Array<u32, (u64, Literal)>,
+    /// (決定レベル, 係数)
+    falsified_terms: Array<u32, (u32, u64)>,
+    related_variables: Set<u32>,
+}
+
+impl PseudoBooleanAnalyze {
+    /// 係数の桁あふれを避けるため，degree がこれを超えれば解析を打ち切る
+    const MAX_DEGREE: u64 = 1 << 40;
+
+    /// 擬似ブール制約の理論で生じた矛盾を解析する
+    pub fn analyze<'a>(
+        &'a mut self,
+        explain_key: ExplainKey,
+        engine: &mut SATEngine,
+    ) -> PseudoBooleanAnalyzeResult<impl Iterator<Item = u32> + Clone + use<'a>> {
+        let ExplainKey::PseudoBooleanTheory(conflict_key) = explain_key else { unreachable!() };
+        self.terms.clear();
+        self.related_variables.clear();
+        engine.get_theory_mut::<PseudoBooleanTheory>().bump_activity(conflict_key);
+        let conflicting_constraint = engine.get_theory::<PseudoBooleanTheory>().constraint(conflict_key);
+        self.degree = conflicting_constraint.degree;
+        for &(coefficient, literal) in conflicting_constraint.terms.iter() {
+            if engine.get_decision_level(literal.index()) == 0 {
+                if engine.is_true(literal) {
+                    self.degree = self.degree.saturating_sub(coefficient);
+                }
+                continue;
+            }
+            self.terms.insert(literal.index(), (coefficient, literal));
+        }
+
+        let current_decision_level = engine.current_decision_level();
+        if !self.terms.iter().any(|(_, &(_, literal))| {
+            engine.is_false(literal) && engine.get_decision_level(literal.index()) == current_decision_level
+        }) {
+            // chronological backtracking の後に現在の決定レベルより低い決定レベルで矛盾した
+            return PseudoBooleanAnalyzeResult::Aborted;
+        }
+        let assignment_order_range = engine.get_assignment_order_range(current_decision_level);
+        let mut assignment_order = assignment_order_range.end;
+        loop {
+            if self.terms.is_empty() {
+                // 0 >= degree (> 0) が導かれたので充足不可能
+                return PseudoBooleanAnalyzeResult::Unsatisfiable;
+            }
+            if self.is_asserting(engine) {
+                break;
+            }
+            // 現在の決定レベルで最後に偽になったリテラルを探す
+            let literal = loop {
+                if assignment_order == assignment_order_range.start {
+                    return PseudoBooleanAnalyzeResult::Aborted;
+                }
+                assignment_order -= 1;
+                let literal = engine.get_assignment(assignment_order);
+                if self.terms.get(literal.index()).is_some_and(|&(_, l)| l == !literal) {
+                    break literal;
+                }
+            };
+            let explain_key = match engine.get_reason(literal.index()) {
+                // 擬似ブール制約は制約そのものを用いるので説明を作らない
+                Some(Reason::LazyPropagation { explain_key: explain_key @ ExplainKey::PseudoBooleanTheory(_) }) => {
+                    explain_key
+                }
+                Some(Reason::Propagation { .. } | Reason::LazyPropagation { .. }) => {
+                    let Some(Reason::Propagation { explain_key }) = engine.get_explained_reason(literal.index()) else {
+                        unreachable!()
+                    };
+                    explain_key
+                }
+                _ => {
+                    // 決定リテラルまで戻っても伝播しない
+                    return PseudoBooleanAnalyzeResult::Aborted;
+                }
+            };
+            let reason_degree = self.load_reason(literal, explain_key, engine);
+            let multiplier = self.terms.get(literal.index()).unwrap().0;
+            if !self.add_reason(multiplier, reason_degree) {
+                return PseudoBooleanAnalyzeResult::Aborted;
+            }
+            debug_assert!(!self.terms.contains_key(literal.index()));
+            self.related_variables.insert(literal.index());
+        }
+
+        let mut learnt_constraint = PseudoBooleanConstraint {
+            terms: Array::from_iter(self.terms.iter().map(|(_, &term)| term)),
+            degree: self.degree,
+        };
+        learnt_constraint.normalize(&self.calculate_gcd);
+        let Some(backjump_decision_level) = self.calculate_backjump_decision_level(&learnt_constraint, engine) else {
+            return PseudoBooleanAnalyzeResult::Aborted;
+        };
+        for &(_, literal) in learnt_constraint.terms.iter() {
+            self.related_variables.insert(literal.index());
+        }
+        return PseudoBooleanAnalyzeResult::Backjumpable {
+            backjump_decision_level: backjump_decision_level,
+            learnt_constraint: learnt_constraint,
+            related_variables: self.related_variables.iter().cloned(),
+        };
+    }
+
+    /// 現在の決定レベルの割り当てを取り消すと伝播するか
+    fn is_asserting(&self, engine: &SATEngine) -> bool {
+        let current_decision_level = engine.current_decision_level();
+        let mut sum = 0;
+        let mut max_coefficient = 0;
+        for (_, &(coefficient, literal)) in self.terms.iter() {
+            if !engine.is_false(literal) || engine.get_decision_level(literal.index()) == current_decision_level {
+                sum += coefficient;
+            }
+            if engine.is_false(literal) && engine.get_decision_level(literal.index()) == current_decision_level {
+                max_coefficient = u64::max(max_coefficient, coefficient);
+            }
+        }
+        return sum >= self.degree && max_coefficient > sum - self.degree;
+    }
+
+    /// 割り当て理由の制約を，literal の係数が 1 で，literal が割り当てられる直前に slack が 0 以下になるように
+    /// 弱化・除算して reason_terms に格納し，degree を返す
+    fn load_reason(&mut self, literal: Literal, explain_key: ExplainKey, engine: &mut SATEngine) -> u64 {
+        self.reason_terms.clear();
+        let ExplainKey::PseudoBooleanTheory(pseudo_boolean_explain_key) = explain_key else {
+            // 節: literal + (偽のリテラル) >= 1
+            let reason_constraint: Constraint<_> = engine.explain_propagation(explain_key.into()).into();
+            for l in reason_constraint.iter() {
+                if engine.get_decision_level(l.index()) != 0 {
+                    self.reason_terms.push((1, l));
+                }
+            }
+            return 1;
+        };
+        engine.get_theory_mut::<PseudoBooleanTheory>().bump_activity(pseudo_boolean_explain_key);
+        let reason_constraint = engine.get_theory::<PseudoBooleanTheory>().constraint(pseudo_boolean_explain_key);
+        let assignment_order = engine.get_assignment_order(literal.index());
+        let divisor = reason_constraint.terms.iter().find(|&&(_, l)| l == literal).unwrap().0;
+        let mut degree = reason_constraint.degree;
+        for &(coefficient, l) in reason_constraint.terms.iter() {
+            if engine.is_assigned(l.index()) && engine.get_decision_level(l.index()) == 0 {
+                if engine.is_true(l) {
+                    degree = degree.saturating_sub(coefficient);
+                }
+                continue;
+            }
+            let is_falsified_before = engine.is_false(l) && engine.get_assignment_order(l.index()) < assignment_order;
+            if l != literal && !is_falsified_before && coefficient % divisor != 0 {
+                // 弱化
+                degree = degree.saturating_sub(coefficient);
+                continue;
+            }
+            self.reason_terms.push((coefficient.div_ceil(divisor), l));
+        }
+        return degree.div_ceil(divisor);
+    }
+
+    /// terms に multiplier * reason_terms を加えて飽和させる．係数が大きくなりすぎれば false を返す
+    fn add_reason(&mut self, multiplier: u64, reason_degree: u64) -> bool {
+        let Some(degree) = multiplier.checked_mul(reason_degree).and_then(|d| d.checked_add(self.degree)) else {
+            return false;
+        };
+        self.degree = degree;
+        for &(coefficient, literal) in self.reason_terms.iter() {
+            let coefficient = multiplier * coefficient;
+            match self.terms.get(literal.index()).cloned() {
+                None => self.terms.insert(literal.index(), (coefficient, literal)),
+                Some((other_coefficient, other_literal)) if other_literal == literal => {
+                    self.terms.insert(literal.index(), (other_coefficient + coefficient, literal))
+                }
+                Some((other_coefficient, other_literal)) => {
+                    // a * l + b * !l = min(a, b) + |a - b| * (係数の大きい方)
+                    self.degree -= u64::min(coefficient, other_coefficient);
+                    if coefficient > other_coefficient {
+                        self.terms.insert(literal.index(), (coefficient - other_coefficient, literal));
+                    } else if coefficient < other_coefficient {
+                        self.terms.insert(literal.index(), (other_coefficient - coefficient, other_literal));
+                    } else {
+                        self.terms.remove(literal.index());
+                    }
+                }
+            }
+        }
+        if self.degree > Self::MAX_DEGREE {
+            return false;
+        }
+        // 飽和 (reason_terms はもう使わないので作業領域にする)
+        let degree = self.degree;
+        self.reason_terms.clear();
+        self.reason_terms
+            .extend(self.terms.iter().filter(|&(_, &(c, _))| c > degree).map(|(_, &term)| term));
+        for &(_, literal) in self.reason_terms.iter() {
+            self.terms.insert(literal.index(), (degree, literal));
+        }
+        return true;
+    }
+
+    /// 学習した制約が伝播する最小の決定レベルを求める
+    fn calculate_backjump_decision_level(
+        &mut self,
+        learnt_constraint: &PseudoBooleanConstraint,
+        engine: &SATEngine,
+    ) -> Option<u32> {
+        let current_decision_level = engine.current_decision_level();
+        // 決定レベル d の時点で偽でないリテラルの係数の和は，sum から d 以下で偽になったリテラルの係数を引いたもの
+        let sum = learnt_constraint.terms.iter().map(|&(c, _)| c).sum::<u64>();
+        self.falsified_terms.clear();
+        for &(coefficient, literal) in learnt_constraint.terms.iter() {
+            if engine.is_false(literal) {
+                self.falsified_terms.push((engine.get_decision_level(literal.index()), coefficient));
+            }
+        }
+        self.falsified_terms.sort_unstable_by_key(|&(decision_level, _)| decision_level);
+        // 後ろから見た係数の最大値
+        let mut suffix_max_coefficients = Array::<u32, u64>::default();
+        suffix_max_coefficients.resize(self.falsified_terms.len() + 1, 0);
+        for k in (0..self.falsified_terms.len()).rev() {
+            suffix_max_coefficients[k] = u64::max(suffix_max_coefficients[k + 1], self.falsified_terms[k].1);
+        }
+        let mut falsified_sum = 0;
+        let mut k = 0;
+        for decision_level in 0..current_decision_level {
+            while k < self.falsified_terms.len() && self.falsified_terms[k].0 <= decision_level {
+                falsified_sum += self.falsified_terms[k].1;
+                k += 1;
+            }
+            if sum < falsified_sum + learnt_constraint.degree {
+                // この決定レベルで既に矛盾している
+                return None;
+            }
+            let slack = sum - falsified_sum - learnt_constraint.degree;
+            if suffix_max_coefficients[k] > slack {
+                return Some(decision_level);
+            }
+        }
+        return None;
+    }
+}
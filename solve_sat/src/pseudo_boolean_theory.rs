@@ -0,0 +1,682 @@
+use std::{array, iter::Chain, iter::Cloned, slice};
+
+use cdcl_engine::{
+    DerivedClauses, EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait,
+    TheoryTrait, UndoLog,
+};
+use either::Either;
+use utility::{Array, CalculateGCD, Map};
+
+use crate::clause_theory::ClauseView;
+
+/// 擬似ブール制約 sum(coefficient * literal) >= degree
+///
+/// 係数は正で，同じ変数は高々一度しか現れない．
+#[derive(Clone)]
+pub struct PseudoBooleanConstraint {
+    pub terms: Array<u32, (u64, Literal)>,
+    pub degree: u64,
+}
+
+impl PseudoBooleanConstraint {
+    /// 負の係数の項は否定リテラルの項に，同じ変数の項はまとめて一つの項にして正規化する
+    pub fn new(terms: impl Iterator<Item = (i64, Literal)>, mut degree: i64) -> Self {
+        let mut coefficients = Map::<u32, (i64, Literal)>::default();
+        for (coefficient, literal) in terms {
+            // a * l = a - a * !l
+            let (coefficient, literal) = if coefficient < 0 {
+                degree -= coefficient;
+                (-coefficient, !literal)
+            } else {
+                (coefficient, literal)
+            };
+            if coefficient == 0 {
+                continue;
+            }
+            if let Some(&(other_coefficient, other_literal)) = coefficients.get(literal.index()) {
+                if other_literal == literal {
+                    coefficients.insert(literal.index(), (other_coefficient + coefficient, literal));
+                } else {
+                    // a * l + b * !l = min(a, b) + |a - b| * (係数の大きい方)
+                    degree -= i64::min(coefficient, other_coefficient);
+                    if coefficient > other_coefficient {
+                        coefficients.insert(literal.index(), (coefficient - other_coefficient, literal));
+                    } else {
+                        coefficients.insert(literal.index(), (other_coefficient - coefficient, other_literal));
+                    }
+                }
+            } else {
+                coefficients.insert(literal.index(), (coefficient, literal));
+            }
+        }
+        let mut constraint = Self {
+            terms: Array::from_iter(
+                coefficients.iter().filter(|(_, (c, _))| *c != 0).map(|(_, &(c, l))| (c as u64, l)),
+            ),
+            degree: i64::max(degree, 0) as u64,
+        };
+        constraint.normalize(&CalculateGCD::default());
+        return constraint;
+    }
+
+    /// 係数を degree で飽和させ，係数の最大公約数で割る(degree は切り上げる)
+    pub fn normalize(&mut self, calculate_gcd: &CalculateGCD) {
+        if self.degree == 0 {
+            self.terms.clear();
+            return;
+        }
+        for term in self.terms.iter_mut() {
+            term.0 = u64::min(term.0, self.degree);
+        }
+        let gcd = calculate_gcd(self.terms.iter().map(|&(c, _)| c as f64)).round();
+        if gcd > 1.0 {
+            let gcd = gcd as u64;
+            if self.terms.iter().all(|&(c, _)| c % gcd == 0) {
+                for term in self.terms.iter_mut() {
+                    term.0 /= gcd;
+                }
+                self.degree = self.degree.div_ceil(gcd);
+            }
+        }
+    }
+
+    /// 全ての係数が 1 であるか(節または基数制約であるか)
+    pub fn is_cardinality(&self) -> bool {
+        return self.terms.iter().all(|&(c, _)| c == 1);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum PseudoBooleanExplainKey {
+    /// literal と explanations[literal の変数] (偽のリテラル)からなる節
+    Propagation { row_id: u32, literal: Literal },
+    /// 説明をまだ作っていない伝播
+    LazyPropagation { row_id: u32 },
+    /// conflicting_literals からなる節
+    Conflict { row_id: u32 },
+}
+
+impl PseudoBooleanExplainKey {
+    pub fn row_id(&self) -> u32 {
+        return match *self {
+            Self::Propagation { row_id, .. } => row_id,
+            Self::LazyPropagation { row_id } => row_id,
+            Self::Conflict { row_id } => row_id,
+        };
+    }
+}
+
+/// バックジャンプで戻す状態の変更
+#[derive(Clone, Copy)]
+pub enum PseudoBooleanUndoEntry {
+    /// literal が偽になり，それを監視している制約の watched_sum を減らした
+    Falsified { literal: Literal },
+}
+
+#[derive(Default, Clone)]
+pub struct PseudoBooleanTheorySummary {
+    pub number_of_pseudo_boolean_constraints: u32,
+    pub number_of_learnt_pseudo_boolean_constraints: u32,
+    pub number_of_pseudo_boolean_propagations: u32,
+}
+
+impl ProgressColumnsTrait for PseudoBooleanTheorySummary {
+    const HEADERS: &'static [&'static str] = &["PSEUDO_BOOLEAN_PROPAGATION", "LEARNT_PSEUDO_BOOLEAN"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_pseudo_boolean_propagations, self.number_of_learnt_pseudo_boolean_constraints]
+            .into_iter();
+    }
+}
+
+#[derive(Clone)]
+struct Row {
+    /// 項は係数の降順に並べる
+    constraint: PseudoBooleanConstraint,
+    /// 項 -> 監視しているか否か
+    is_watched: Array<u32, bool>,
+    /// 偽でない監視リテラルの係数の和
+    watched_sum: u64,
+    is_learnt: bool,
+    is_deleted: bool,
+    activity: f64,
+}
+
+impl Row {
+    /// 監視の条件 (偽でない監視リテラルの係数の和) >= degree + (係数の最大値)
+    fn watch_threshold(&self) -> u64 {
+        return self.constraint.degree + self.constraint.terms.first().map_or(0, |&(c, _)| c);
+    }
+}
+
+type PropagationLiterals<'a> = Chain<array::IntoIter<Literal, 1>, Cloned<slice::Iter<'a, Literal>>>;
+
+/// 擬似ブール制約の理論
+///
+/// 制約ごとに，偽でない監視リテラルの係数の和が degree + (係数の最大値) 以上になるようにリテラルを監視する．
+/// 監視リテラルが偽になったときに監視を補えなければ，偽でないリテラルは全て監視されているので，
+/// その係数の和から slack を求め，slack が負であれば矛盾とし，係数が slack を超える未割り当てのリテラルを真にする．
+/// 偽か否かはこの理論が処理済みの割り当てで判断するので，監視の条件はバックジャンプしても保たれる．
+/// 伝播の説明は解析で必要になったときに伝播した変数ごとに，矛盾の説明は矛盾時に節として記録し，
+/// 制約そのものは cutting planes による矛盾の解析のために constraint から参照できるようにする．
+#[derive(Clone)]
+pub struct PseudoBooleanTheory {
+    rows: Array<u32, Row>,
+    /// リテラル(bits) -> (そのリテラルを監視している制約の番号, 項の位置)
+    watches: Array<u32, Array<u32, (u32, u32)>>,
+    /// 変数 -> 変数が現れる(削除されていない)制約の数
+    number_of_occurrences: Array<u32, u32>,
+    /// リテラル(bits) -> この理論が偽になったことを処理済みか否か
+    is_falsified: Array<u32, bool>,
+    /// 変数 -> その変数を伝播した説明に用いる偽のリテラル
+    ///
+    /// 説明は割り当てより後に作るので，割り当ての処理とともに記録して戻すことはできない．
+    /// 次に同じ変数の伝播を説明するまで残す
+    explanations: Array<u32, Array<u32, Literal>>,
+    conflicting_literals: Array<u32, Literal>,
+    activity_increase_value: f64,
+    max_number_of_learnt_constraints: u32,
+    /// simplify_at_root を次に始める行番号
+    simplification_row_id: u32,
+    summary: PseudoBooleanTheorySummary,
+}
+
+impl PseudoBooleanTheory {
+    pub fn new(max_number_of_learnt_constraints: u32) -> Self {
+        Self {
+            rows: Array::default(),
+            watches: Array::default(),
+            number_of_occurrences: Array::default(),
+            is_falsified: Array::default(),
+            explanations: Array::default(),
+            conflicting_literals: Array::default(),
+            activity_increase_value: 1.0,
+            max_number_of_learnt_constraints: max_number_of_learnt_constraints,
+            simplification_row_id: 0,
+            summary: PseudoBooleanTheorySummary::default(),
+        }
+    }
+
+    /// 伝播・矛盾の原因となった制約
+    pub fn constraint(&self, explain_key: PseudoBooleanExplainKey) -> &PseudoBooleanConstraint {
+        return &self.rows[explain_key.row_id()].constraint;
+    }
+
+    /// 矛盾の解析に用いた制約の活性度を上げる
+    pub fn bump_activity(&mut self, explain_key: PseudoBooleanExplainKey) {
+        let row = &mut self.rows[explain_key.row_id()];
+        if row.is_learnt {
+            row.activity += self.activity_increase_value;
+            self.activity_increase_value *= 1.001;
+        }
+    }
+
+    /// 制約を削除する．監視は remove_deleted_watches で取り除く
+    fn delete_row(&mut self, row_id: u32) {
+        let row = &mut self.rows[row_id];
+        debug_assert!(!row.is_deleted);
+        row.is_deleted = true;
+        for &(_, literal) in row.constraint.terms.iter() {
+            self.number_of_occurrences[literal.index()] -= 1;
+        }
+        self.summary.number_of_pseudo_boolean_constraints -= 1;
+        if row.is_learnt {
+            self.summary.number_of_learnt_pseudo_boolean_constraints -= 1;
+        }
+    }
+
+    /// 削除した制約の監視を取り除く
+    fn remove_deleted_watches(&mut self) {
+        let rows = &self.rows;
+        for watch in self.watches.iter_mut() {
+            let mut k = 0;
+            while k < watch.len() {
+                if rows[watch[k].0].is_deleted {
+                    watch.swap_remove(k);
+                } else {
+                    k += 1;
+                }
+            }
+        }
+    }
+
+    /// 監視の条件を満たすまで，偽でない監視していないリテラルを係数の大きい順に監視に加える
+    fn extend_watches(&mut self, row_id: u32) {
+        let row = &mut self.rows[row_id];
+        let threshold = row.watch_threshold();
+        for k in 0..row.constraint.terms.len() {
+            if row.watched_sum >= threshold {
+                break;
+            }
+            let (coefficient, literal) = row.constraint.terms[k];
+            if !row.is_watched[k] && !self.is_falsified[literal.bits()] {
+                row.is_watched[k] = true;
+                row.watched_sum += coefficient;
+                self.watches[literal.bits()].push((row_id, k));
+            }
+        }
+    }
+
+    /// 偽でないリテラルが全て監視されている制約について，矛盾の検出と伝播を行う
+    fn propagate<EngineT>(
+        &mut self,
+        row_id: u32,
+        engine: &mut EngineT,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<PseudoBooleanExplainKey>,
+    {
+        let row = &self.rows[row_id];
+        let is_falsified = &self.is_falsified;
+        let sum = row.watched_sum;
+        if sum < row.constraint.degree {
+            self.conflicting_literals.clear();
+            self.conflicting_literals
+                .extend(row.constraint.terms.iter().map(|&(_, l)| l).filter(|&l| is_falsified[l.bits()]));
+            return PropagationResult::Conflict {
+                explain_key: PseudoBooleanExplainKey::Conflict { row_id: row_id }.into(),
+            };
+        }
+        let slack = sum - row.constraint.degree;
+        // 係数が slack を超えるリテラルは真でなければならない
+        for k in 0..self.rows[row_id].constraint.terms.len() {
+            let (coefficient, literal) = self.rows[row_id].constraint.terms[k];
+            if coefficient <= slack {
+                break;
+            }
+            if engine.is_assigned(literal.index()) {
+                // 伝播の途中で偽になったリテラルは，後で assign されたときに処理する
+                continue;
+            }
+            self.summary.number_of_pseudo_boolean_propagations += 1;
+            let explain_key = PseudoBooleanExplainKey::LazyPropagation { row_id: row_id };
+            let result = engine.assign(literal, Reason::LazyPropagation { explain_key: explain_key.into() });
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+impl TheoryTrait for PseudoBooleanTheory {
+    type ExplainKey = PseudoBooleanExplainKey;
+    type ExplanationConstraint<'a> = ClauseView<Either<PropagationLiterals<'a>, Cloned<slice::Iter<'a, Literal>>>>;
+    type Summary = PseudoBooleanTheorySummary;
+    type UndoEntry = PseudoBooleanUndoEntry;
+
+    fn add_variable(&mut self) {
+        self.watches.push(Array::default());
+        self.watches.push(Array::default());
+        self.number_of_occurrences.push(0);
+        self.is_falsified.push(false);
+        self.is_falsified.push(false);
+        self.explanations.push(Array::default());
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        let falsified_literal = !assigned_literal;
+        self.is_falsified[falsified_literal.bits()] = true;
+        undo_log.push(PseudoBooleanUndoEntry::Falsified { literal: falsified_literal });
+        // 矛盾で途中で戻っても undo と整合するように，先に全ての watched_sum を更新する
+        for &(row_id, position) in self.watches[falsified_literal.bits()].iter() {
+            let row = &mut self.rows[row_id];
+            row.watched_sum -= row.constraint.terms[position].0;
+        }
+        let mut k = 0;
+        while k < self.watches[falsified_literal.bits()].len() {
+            let (row_id, position) = self.watches[falsified_literal.bits()][k];
+            let row = &self.rows[row_id];
+            if row.watched_sum < row.watch_threshold() {
+                self.extend_watches(row_id);
+            }
+            let row = &mut self.rows[row_id];
+            if row.watched_sum >= row.watch_threshold() {
+                // 監視を補えたので falsified_literal の監視をやめる
+                row.is_watched[position] = false;
+                self.watches[falsified_literal.bits()].swap_remove(k);
+                continue;
+            }
+            k += 1;
+            let result = self.propagate(row_id, engine);
+            if result.is_conflict() {
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+
+    fn explain_propagation(&self, explain_key: PseudoBooleanExplainKey) -> Self::ExplanationConstraint<'_> {
+        let PseudoBooleanExplainKey::Propagation { literal, .. } = explain_key else {
+            unreachable!()
+        };
+        return ClauseView::new(Either::Left(
+            [literal].into_iter().chain(self.explanations[literal.index()].iter().cloned()),
+        ));
+    }
+
+    fn explain_lazily<EngineT: EngineTrait>(
+        &mut self,
+        literal: Literal,
+        explain_key: PseudoBooleanExplainKey,
+        engine: &EngineT,
+    ) -> PseudoBooleanExplainKey {
+        let PseudoBooleanExplainKey::LazyPropagation { row_id } = explain_key else { unreachable!() };
+        // literal より先に偽になったリテラルだけで literal は伝播する
+        let assignment_order = engine.get_assignment_order(literal.index());
+        let explanation = &mut self.explanations[literal.index()];
+        explanation.clear();
+        explanation.extend(
+            self.rows[row_id]
+                .constraint
+                .terms
+                .iter()
+                .map(|&(_, l)| l)
+                .filter(|&l| engine.is_false(l) && engine.get_assignment_order(l.index()) < assignment_order),
+        );
+        return PseudoBooleanExplainKey::Propagation { row_id: row_id, literal: literal };
+    }
+
+    fn explain_conflict(&self, explain_key: PseudoBooleanExplainKey) -> Self::ExplanationConstraint<'_> {
+        debug_assert!(matches!(explain_key, PseudoBooleanExplainKey::Conflict { .. }));
+        return ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned()));
+    }
+
+    fn undo(&mut self, entry: PseudoBooleanUndoEntry) {
+        let PseudoBooleanUndoEntry::Falsified { literal } = entry;
+        self.is_falsified[literal.bits()] = false;
+        for &(row_id, position) in self.watches[literal.bits()].iter() {
+            let row = &mut self.rows[row_id];
+            row.watched_sum += row.constraint.terms[position].0;
+        }
+    }
+
+    fn simplify_at_root<EngineT: EngineTrait>(
+        &mut self,
+        effort: usize,
+        engine: &EngineT,
+        _derived_clauses: &mut DerivedClauses,
+    ) -> u32 {
+        let mut number_of_removed_constraints = 0;
+        let mut ticks = 0;
+        // 前回打ち切った行から順に一巡する
+        for _ in 0..self.rows.len() {
+            if ticks >= effort {
+                break;
+            }
+            if self.simplification_row_id >= self.rows.len() {
+                self.simplification_row_id = 0;
+            }
+            let row_id = self.simplification_row_id;
+            self.simplification_row_id += 1;
+            let row = &self.rows[row_id];
+            if row.is_deleted {
+                continue;
+            }
+            ticks += row.constraint.terms.len() as usize;
+            // 根で真のリテラルの係数の和が degree 以上であれば充足されている
+            let true_sum: u64 = row.constraint.terms.iter().filter(|&&(_, l)| engine.is_true(l)).map(|&(c, _)| c).sum();
+            if true_sum >= row.constraint.degree {
+                self.delete_row(row_id);
+                number_of_removed_constraints += 1;
+            }
+        }
+        if number_of_removed_constraints != 0 {
+            self.remove_deleted_watches();
+        }
+        return number_of_removed_constraints;
+    }
+
+    /// 変数が擬似ブール制約に現れるか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return self.number_of_occurrences[index] != 0;
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習した制約が上限を超えていれば活性度の低い半分を削除する
+        if self.summary.number_of_learnt_pseudo_boolean_constraints <= self.max_number_of_learnt_constraints {
+            return;
+        }
+        let mut learnt_row_ids = Array::<u32, u32>::from_iter(
+            (0..self.rows.len()).filter(|&row_id| self.rows[row_id].is_learnt && !self.rows[row_id].is_deleted),
+        );
+        learnt_row_ids.sort_unstable_by(|&l, &r| self.rows[l].activity.total_cmp(&self.rows[r].activity));
+        for &row_id in learnt_row_ids[..learnt_row_ids.len() / 2].iter() {
+            self.delete_row(row_id);
+        }
+        self.remove_deleted_watches();
+        for row in self.rows.iter_mut() {
+            row.activity /= self.activity_increase_value;
+        }
+        self.activity_increase_value = 1.0;
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<PseudoBooleanConstraint> for PseudoBooleanTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        mut constraint: PseudoBooleanConstraint,
+        is_learnt: bool,
+        engine: &mut EngineT,
+        _undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        if constraint.degree == 0 {
+            // 常に充足される
+            return PropagationResult::Noconflict;
+        }
+        // 伝播するリテラルを係数の大きい順に調べられるように並べ替える
+        constraint.terms.sort_unstable_by_key(|&(c, _)| std::cmp::Reverse(c));
+        for &(_, literal) in constraint.terms.iter() {
+            self.number_of_occurrences[literal.index()] += 1;
+        }
+        let row_id = self.rows.len();
+        let mut is_watched = Array::default();
+        is_watched.resize(constraint.terms.len(), false);
+        self.rows.push(Row {
+            constraint: constraint,
+            is_watched: is_watched,
+            watched_sum: 0,
+            is_learnt: is_learnt,
+            is_deleted: false,
+            activity: if is_learnt { self.activity_increase_value } else { 0.0 },
+        });
+        self.summary.number_of_pseudo_boolean_constraints += 1;
+        if is_learnt {
+            self.summary.number_of_learnt_pseudo_boolean_constraints += 1;
+        }
+        self.extend_watches(row_id);
+        let row = &mut self.rows[row_id];
+        let threshold = row.watch_threshold();
+        if row.watched_sum >= threshold {
+            return PropagationResult::Noconflict;
+        }
+        // バックジャンプで偽でなくなったリテラルを監視していなくても条件が保たれるように，
+        // 偽のリテラルも後に割り当てられたものから順に監視に加える
+        let mut falsified_positions = Array::<u32, u32>::from_iter(
+            (0..row.constraint.terms.len()).filter(|&k| self.is_falsified[row.constraint.terms[k].1.bits()]),
+        );
+        falsified_positions.sort_unstable_by_key(|&k| {
+            std::cmp::Reverse(engine.get_assignment_order(row.constraint.terms[k].1.index()))
+        });
+        let mut sum = row.watched_sum;
+        for &k in falsified_positions.iter() {
+            if sum >= threshold {
+                break;
+            }
+            row.is_watched[k] = true;
+            self.watches[row.constraint.terms[k].1.bits()].push((row_id, k));
+            sum += row.constraint.terms[k].0;
+        }
+        return self.propagate(row_id, engine);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::{PseudoBooleanConstraint, PseudoBooleanExplainKey, PseudoBooleanTheory};
+    use crate::theory_test::{
+        TestEngine, TestExplainKey, TestedExplainKey, TestedTheory, assert_conflict_is_valid,
+        assert_explanations_are_valid, assignment_bits, check_random_search, conflict_clause, enumerate_bits,
+        holds_in_bits, literal, make_engine, reason_clause, sorted,
+    };
+
+    impl TestedExplainKey for PseudoBooleanExplainKey {}
+
+    impl TestedTheory for PseudoBooleanTheory {
+        type Solution = u32;
+
+        fn holds(&self, literal: Literal, bits: &u32) -> bool {
+            return holds_in_bits(literal, *bits);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> u32 {
+            return assignment_bits(engine);
+        }
+
+        /// 偽になったことを処理済みのリテラルの数
+        fn checkpoint(&self) -> u32 {
+            return self.is_falsified.iter().filter(|&&is_falsified| is_falsified).count() as u32;
+        }
+
+        /// 監視の状態がエンジンの割り当てと一致し，監視の条件が保たれている
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for index in 0..engine.number_of_variables() {
+                for literal in [literal(index, true), literal(index, false)] {
+                    assert_eq!(self.is_falsified[literal.bits()], engine.is_false(literal));
+                }
+            }
+            for (row_id, row) in self.rows.iter().enumerate() {
+                let mut watched_sum = 0;
+                for (k, &(coefficient, literal)) in row.constraint.terms.iter().enumerate() {
+                    let is_watching = self.watches[literal.bits()].contains(&(row_id as u32, k as u32));
+                    assert_eq!(row.is_watched[k as u32], is_watching);
+                    if is_watching && !engine.is_false(literal) {
+                        watched_sum += coefficient;
+                    }
+                }
+                assert_eq!(row.watched_sum, watched_sum);
+                // 監視の条件を満たさなければ，偽でないリテラルは全て監視されている
+                if row.watched_sum < row.watch_threshold() {
+                    assert!(
+                        (0..row.constraint.terms.len())
+                            .all(|k| row.is_watched[k] || engine.is_false(row.constraint.terms[k].1))
+                    );
+                }
+            }
+        }
+    }
+
+    /// 正規化する前の制約 sum(coefficient * literal) >= degree
+    type Terms = Vec<(i64, Literal)>;
+
+    fn add_constraint(
+        engine: &mut TestEngine<PseudoBooleanTheory>,
+        terms: &Terms,
+        degree: i64,
+    ) -> PropagationResult<TestExplainKey<PseudoBooleanExplainKey>> {
+        let constraint = PseudoBooleanConstraint::new(terms.iter().cloned(), degree);
+        return crate::theory_test::add_constraint(engine, constraint);
+    }
+
+    fn enumerate_solutions(number_of_variables: u32, constraints: &[(Terms, i64)]) -> Vec<u32> {
+        return enumerate_bits(number_of_variables, |bits| {
+            constraints.iter().all(|(terms, degree)| {
+                terms.iter().filter(|&&(_, literal)| holds_in_bits(literal, bits)).map(|&(c, _)| c).sum::<i64>()
+                    >= *degree
+            })
+        });
+    }
+
+    #[test]
+    fn test_large_coefficient_is_propagated_and_explained_lazily() {
+        // 3 x0 + 2 x1 + x2 + x3 >= 4
+        let terms = vec![(3, literal(0, true)), (2, literal(1, true)), (1, literal(2, true)), (1, literal(3, true))];
+        let solutions = enumerate_solutions(4, &[(terms.clone(), 4)]);
+        let mut engine = make_engine(PseudoBooleanTheory::new(100), 4);
+        assert!(add_constraint(&mut engine, &terms, 4).is_no_conflict());
+        assert_eq!(engine.number_of_assigneds(), 0);
+        // x1 = 0 なら残りの和は 5 で slack は 1 なので，係数 3 の x0 だけが真になる
+        assert!(engine.assign(literal(1, false), Reason::Decision).is_no_conflict());
+        assert!(engine.is_true(literal(0, true)));
+        assert!(!engine.is_assigned(2) && !engine.is_assigned(3));
+        assert!(matches!(engine.get_reason(0), Some(Reason::LazyPropagation { .. })));
+        let clause = sorted(reason_clause(&mut engine, literal(0, true)).unwrap());
+        assert!(clause == vec![literal(0, true), literal(1, true)]);
+        // さらに x2 = 0 なら x3 が真になる
+        assert!(engine.assign(literal(2, false), Reason::Decision).is_no_conflict());
+        assert!(engine.is_true(literal(3, true)));
+        assert_explanations_are_valid(&mut engine, &solutions);
+        let _ = engine.backjump(1).count();
+        assert!(!engine.is_assigned(2) && !engine.is_assigned(3));
+        engine.theory().assert_consistent(&engine);
+        let _ = engine.backjump(0).count();
+        assert_eq!(engine.number_of_assigneds(), 0);
+        engine.theory().assert_consistent(&engine);
+    }
+
+    #[test]
+    fn test_conflict_contains_falsified_literals() {
+        // 2 x0 + x1 + x2 >= 3 に x0 = 0 を加えると矛盾する
+        let terms = vec![(2, literal(0, true)), (1, literal(1, true)), (1, literal(2, true))];
+        let solutions = enumerate_solutions(3, &[(terms.clone(), 3)]);
+        let mut engine = make_engine(PseudoBooleanTheory::new(100), 3);
+        assert!(engine.assign(literal(0, false), Reason::Decision).is_no_conflict());
+        let PropagationResult::Conflict { explain_key } = add_constraint(&mut engine, &terms, 3) else {
+            panic!("expected a conflict");
+        };
+        assert_conflict_is_valid(&engine, explain_key, &solutions);
+        assert!(conflict_clause(&engine, explain_key) == vec![literal(0, true)]);
+    }
+
+    fn random_constraint(rng: &mut SmallRng, number_of_variables: u32) -> (Terms, i64) {
+        let terms = Vec::from_iter((0..rng.gen_range(1..5)).map(|_| {
+            (
+                rng.gen_range(1..5) * if rng.gen_bool(0.8) { 1 } else { -1 },
+                literal(rng.gen_range(0..number_of_variables), rng.gen_bool(0.5)),
+            )
+        }));
+        let degree = rng.gen_range(0..8);
+        return (terms, degree);
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(19);
+        for _ in 0..500 {
+            let number_of_variables = rng.gen_range(2..8);
+            let constraints =
+                Vec::from_iter((0..rng.gen_range(1..5)).map(|_| random_constraint(&mut rng, number_of_variables)));
+            let solutions = enumerate_solutions(number_of_variables, &constraints);
+            let mut engine = make_engine(PseudoBooleanTheory::new(100), number_of_variables);
+            let mut result = PropagationResult::Noconflict;
+            for (terms, degree) in constraints.iter() {
+                result = add_constraint(&mut engine, terms, *degree);
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
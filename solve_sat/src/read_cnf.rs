@@ -1,7 +1,21 @@
 use cdcl_engine::{Boolean, Literal};
 use utility::Array;
 
-use crate::cnf::CNF;
+use crate::{
+    acyclicity_theory::AcyclicityEdge,
+    cardinality_theory::CardinalityConstraint,
+    cnf::CNF,
+    difference_logic_theory::DifferenceAtom,
+    integer_theory::{IntegerConstraint, OrderLiteral},
+    pseudo_boolean_theory::PseudoBooleanConstraint,
+    xor_theory::XorConstraint,
+};
+
+fn parse_literal(i: i64) -> Literal {
+    let index = (i.abs() - 1) as u32;
+    let value = if i > 0 { Boolean::TRUE } else { Boolean::FALSE };
+    return Literal::new(index, value);
+}
 
 fn parse_line(line: &str) -> Array<u32, Literal> {
     let mut clause = Array::default();
@@ -10,22 +24,176 @@ fn parse_line(line: &str) -> Array<u32, Literal> {
         if i == 0 {
             break;
         }
-        let index = (i.abs() - 1) as u32;
-        let value = if i > 0 { Boolean::TRUE } else { Boolean::FALSE };
-        clause.push(Literal::new(index, value));
+        clause.push(parse_literal(i));
     }
-    assert!(!clause.is_empty());
     return clause;
 }
 
+/// <係数> <リテラル> ... 0 を読む
+fn parse_terms(line: &str) -> Array<u32, (i64, Literal)> {
+    let mut terms = Array::default();
+    let mut fields = line.split_whitespace().map(|field| field.parse::<i64>().unwrap());
+    while let Some(coefficient) = fields.next() {
+        if coefficient == 0 {
+            break;
+        }
+        terms.push((coefficient, parse_literal(fields.next().unwrap())));
+    }
+    return terms;
+}
+
+/// <整数>... 0 を読む．整数変数は 0 から数えるので，行末の 0 だけを終端とみなす
+fn parse_integers(line: &str) -> Array<u32, i64> {
+    let mut integers = Array::from_iter(line.split_whitespace().map(|field| field.parse::<i64>().unwrap()));
+    assert!(integers.pop() == Some(0));
+    return integers;
+}
+
 pub fn read_cnf(reader: impl std::io::BufRead) -> CNF {
     let mut cnf = CNF::default();
     for read_result in reader.lines() {
         let line = read_result.unwrap();
-        if line.is_empty() || line.starts_with('c') || line.starts_with('p') {
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        if line.starts_with('p') {
+            // p cnf <変数の数> <節の数> (基数制約・擬似ブール制約を含む場合は p knf)
+            if let Some(field) = line.split_whitespace().nth(2) {
+                cnf.number_of_variables = u32::max(cnf.number_of_variables, field.parse::<u32>().unwrap());
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('k') {
+            // k <bound> <リテラル>... 0 は sum(リテラル) >= bound を表す
+            let rest = rest.trim_start();
+            let (bound, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let literals = parse_line(rest);
+            if let Some(max_index) = literals.iter().map(|l| l.index()).max() {
+                cnf.number_of_variables = u32::max(cnf.number_of_variables, max_index + 1);
+            }
+            let constraint = CardinalityConstraint::new(literals.into_iter(), bound.parse::<u32>().unwrap());
+            match constraint.bound {
+                0 => {}
+                1 => {
+                    cnf.clauses.push(constraint.literals);
+                }
+                _ => {
+                    cnf.cardinality_constraints.push(constraint);
+                }
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('b') {
+            // b <degree> <係数> <リテラル> ... 0 は sum(係数 * リテラル) >= degree を表す(係数・ degree は負でもよい)
+            let rest = rest.trim_start();
+            let (degree, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let terms = parse_terms(rest);
+            if let Some(max_index) = terms.iter().map(|(_, l)| l.index()).max() {
+                cnf.number_of_variables = u32::max(cnf.number_of_variables, max_index + 1);
+            }
+            let constraint = PseudoBooleanConstraint::new(terms.into_iter(), degree.parse::<i64>().unwrap());
+            if constraint.degree == 0 {
+                continue;
+            }
+            if !constraint.is_cardinality() {
+                cnf.pseudo_boolean_constraints.push(constraint);
+                continue;
+            }
+            let literals = Array::from_iter(constraint.terms.iter().map(|&(_, l)| l));
+            if constraint.degree == 1 {
+                cnf.clauses.push(literals);
+            } else {
+                cnf.cardinality_constraints
+                    .push(CardinalityConstraint { literals: literals, bound: constraint.degree as u32 });
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('x') {
+            // x <リテラル>... 0 はリテラルの排他的論理和が真であることを表す
+            let literals = parse_line(rest);
+            if let Some(max_index) = literals.iter().map(|l| l.index()).max() {
+                cnf.number_of_variables = u32::max(cnf.number_of_variables, max_index + 1);
+            }
+            let constraint = XorConstraint::new(literals.into_iter());
+            if constraint.variables.len() <= 2 {
+                cnf.clauses.extend(constraint.clauses().into_iter());
+            } else {
+                cnf.xor_constraints.push(constraint);
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('e') {
+            // e <始点> <終点> <リテラル> 0 はリテラルが真であれば辺が存在することを表す．存在する辺は閉路を含まない
+            let mut fields = rest.split_whitespace().map(|field| field.parse::<i64>().unwrap());
+            let from = fields.next().unwrap() as u32;
+            let to = fields.next().unwrap() as u32;
+            let literal = parse_literal(fields.next().unwrap());
+            cnf.number_of_variables = u32::max(cnf.number_of_variables, literal.index() + 1);
+            cnf.acyclicity_edges.push(AcyclicityEdge { from: from, to: to, literal: literal });
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('d') {
+            // d <x> <y> <bound> <リテラル> 0 はリテラルが真であることと整数変数について x - y <= bound であることが同値であることを表す
+            let mut fields = rest.split_whitespace().map(|field| field.parse::<i64>().unwrap());
+            let x = fields.next().unwrap() as u32;
+            let y = fields.next().unwrap() as u32;
+            let bound = fields.next().unwrap();
+            let literal = parse_literal(fields.next().unwrap());
+            cnf.number_of_variables = u32::max(cnf.number_of_variables, literal.index() + 1);
+            cnf.difference_atoms.push(DifferenceAtom { x: x, y: y, bound: bound, literal: literal });
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('z') {
+            // z <整数変数> <下限> <上限> 0 は整数変数の定義域を宣言する
+            let mut fields = rest.split_whitespace().map(|field| field.parse::<i64>().unwrap());
+            let variable = fields.next().unwrap() as u32;
+            let lower = fields.next().unwrap();
+            let upper = fields.next().unwrap();
+            if variable >= cnf.integer_variables.len() {
+                cnf.integer_variables.resize(variable + 1, (0, 0));
+            }
+            cnf.integer_variables[variable] = (lower, upper);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('o') {
+            // o <リテラル> <整数変数> <値> 0 はリテラルが真であることと整数変数が値以下であることが同値であることを表す
+            let mut fields = rest.split_whitespace().map(|field| field.parse::<i64>().unwrap());
+            let literal = parse_literal(fields.next().unwrap());
+            let variable = fields.next().unwrap() as u32;
+            let value = fields.next().unwrap();
+            cnf.number_of_variables = u32::max(cnf.number_of_variables, literal.index() + 1);
+            cnf.order_literals.push(OrderLiteral { literal: literal, variable: variable, value: value });
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('l') {
+            // l <bound> <係数> <整数変数> ... 0 は sum(係数 * 整数変数) <= bound を表す
+            let integers = parse_integers(rest);
+            let terms = Array::from_iter(integers[1..integers.len()].chunks(2).map(|term| (term[0], term[1] as u32)));
+            cnf.integer_constraints.push(IntegerConstraint::Linear { terms: terms, bound: integers[0] });
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('a') {
+            // a <整数変数>... 0 は整数変数の値が互いに異なることを表す
+            let variables = Array::from_iter(parse_integers(rest).iter().map(|&i| i as u32));
+            cnf.integer_constraints.push(IntegerConstraint::AllDifferent { variables: variables });
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('m') {
+            // m <index> <result> <値>... 0 は result = 値[index] (index は 0 始まり)を表す
+            let integers = parse_integers(rest);
+            let values = Array::from_iter(integers[2..integers.len()].iter().cloned());
+            cnf.integer_constraints.push(IntegerConstraint::Element {
+                index: integers[0] as u32,
+                result: integers[1] as u32,
+                values: values,
+            });
             continue;
         }
-        cnf.clauses.push(parse_line(&line));
+        let clause = parse_line(&line);
+        if let Some(max_index) = clause.iter().map(|l| l.index()).max() {
+            cnf.number_of_variables = u32::max(cnf.number_of_variables, max_index + 1);
+        }
+        cnf.clauses.push(clause);
     }
     return cnf;
 }
@@ -0,0 +1,70 @@
+use cdcl_engine::{Boolean, Literal};
+use utility::Array;
+
+use crate::write_cnf::format_literal;
+
+#[derive(Clone)]
+struct Entry {
+    witness: Literal,
+    clause: Array<u32, Literal>,
+}
+
+/// 削除した節と，解の修復時に真にするリテラル(witness)を積むスタック
+#[derive(Default, Clone)]
+pub struct ReconstructionStack {
+    entries: Array<u32, Entry>,
+}
+
+impl ReconstructionStack {
+    pub fn push(&mut self, witness: Literal, clause: impl Iterator<Item = Literal>) {
+        let clause = Array::from_iter(clause);
+        debug_assert!(clause.contains(&witness));
+        self.entries.push(Entry { witness: witness, clause: clause });
+    }
+
+    /// 現れる変数の添字の最大値 + 1 を返す
+    pub fn number_of_variables(&self) -> u32 {
+        return self.entries.iter().flat_map(|entry| entry.clause.iter()).map(|l| l.index() + 1).max().unwrap_or(0);
+    }
+
+    /// 削除後の論理式の解を，削除前の論理式の解に修復する
+    pub fn extend_model(&self, model: &mut Array<u32, Boolean>) {
+        for entry in self.entries.iter().rev() {
+            if entry.clause.iter().all(|literal| model[literal.index()] != literal.value()) {
+                // 節が充足されていなければ witness を真に
+                model[entry.witness.index()] = entry.witness.value();
+            }
+        }
+    }
+
+    /// 1 行に 1 要素ずつ，witness に続けて節のリテラルを 0 終端で書き出す
+    pub fn write(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        writeln!(writer, "c reconstruction stack: <witness> <clause> 0")?;
+        for entry in self.entries.iter() {
+            write!(writer, "{}", format_literal(entry.witness))?;
+            for &literal in entry.clause.iter() {
+                write!(writer, " {}", format_literal(literal))?;
+            }
+            writeln!(writer, " 0")?;
+        }
+        return Ok(());
+    }
+
+    pub fn read(reader: impl std::io::BufRead) -> Self {
+        let mut reconstruction_stack = Self::default();
+        for read_result in reader.lines() {
+            let line = read_result.unwrap();
+            if line.is_empty() || line.starts_with('c') {
+                continue;
+            }
+            let mut literals = line
+                .split_whitespace()
+                .map(|field| field.parse::<i64>().unwrap())
+                .take_while(|&i| i != 0)
+                .map(|i| Literal::new((i.abs() - 1) as u32, Boolean::from(i > 0)));
+            let witness = literals.next().unwrap();
+            reconstruction_stack.push(witness, literals);
+        }
+        return reconstruction_stack;
+    }
+}
@@ -0,0 +1,416 @@
+use cdcl_engine::EngineTrait;
+
+use crate::{
+    plbd_watcher::PLBDWatcher,
+    plbd_watcher2::{Average, PLBDWatcher2},
+};
+
+#[derive(Default, Clone)]
+pub struct RestartPolicySummary {
+    pub number_of_restarts: u32,
+    /// 割り当ての列が長いために見送った再始動の数
+    pub number_of_blocked_restarts: u32,
+}
+
+/// 再始動する時期の決め方
+///
+/// 実行時に切り替えられるように SearchRestartPolicy にまとめる．
+pub trait RestartPolicy {
+    /// 矛盾の解析で学習した制約の PLBD を，バックジャンプの前に知らせる
+    fn add_conflict(&mut self, plbd: u32, engine: &impl EngineTrait);
+
+    /// 直前の矛盾で再始動するか
+    fn should_restart(&self) -> bool;
+
+    /// 再始動したことを知らせる
+    fn restart(&mut self);
+
+    fn summary(&self) -> RestartPolicySummary;
+}
+
+/// 実行時に選んだ再始動の方針
+#[derive(Clone)]
+pub enum SearchRestartPolicy {
+    LogNormal(LogNormalRestart),
+    AverageRatio(AverageRatioRestart),
+    GlucoseEma(GlucoseEmaRestart),
+    Luby(LubyRestart),
+    Geometric(GeometricRestart),
+}
+
+impl RestartPolicy for SearchRestartPolicy {
+    fn add_conflict(&mut self, plbd: u32, engine: &impl EngineTrait) {
+        match self {
+            Self::LogNormal(policy) => policy.add_conflict(plbd, engine),
+            Self::AverageRatio(policy) => policy.add_conflict(plbd, engine),
+            Self::GlucoseEma(policy) => policy.add_conflict(plbd, engine),
+            Self::Luby(policy) => policy.add_conflict(plbd, engine),
+            Self::Geometric(policy) => policy.add_conflict(plbd, engine),
+        }
+    }
+
+    fn should_restart(&self) -> bool {
+        return match self {
+            Self::LogNormal(policy) => policy.should_restart(),
+            Self::AverageRatio(policy) => policy.should_restart(),
+            Self::GlucoseEma(policy) => policy.should_restart(),
+            Self::Luby(policy) => policy.should_restart(),
+            Self::Geometric(policy) => policy.should_restart(),
+        };
+    }
+
+    fn restart(&mut self) {
+        match self {
+            Self::LogNormal(policy) => policy.restart(),
+            Self::AverageRatio(policy) => policy.restart(),
+            Self::GlucoseEma(policy) => policy.restart(),
+            Self::Luby(policy) => policy.restart(),
+            Self::Geometric(policy) => policy.restart(),
+        }
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return match self {
+            Self::LogNormal(policy) => policy.summary(),
+            Self::AverageRatio(policy) => policy.summary(),
+            Self::GlucoseEma(policy) => policy.summary(),
+            Self::Luby(policy) => policy.summary(),
+            Self::Geometric(policy) => policy.summary(),
+        };
+    }
+}
+
+/// PLBD の対数が正規分布に従うとみなし，PLBD が小さい(累積分布関数の値が小さい)ほど長く再始動を待つ
+#[derive(Clone)]
+pub struct LogNormalRestart {
+    plbd_watcher: PLBDWatcher2,
+    /// 累積分布関数の値が 0 のときに待つ矛盾の数
+    max_interval: f64,
+    /// 最初に再始動するまでの矛盾の数
+    min_conflicts: usize,
+    last_plbd: u32,
+    number_of_conflicts: usize,
+    number_of_conflicts_at_previous_restart: usize,
+    summary: RestartPolicySummary,
+}
+
+impl LogNormalRestart {
+    pub fn new(time_constant: f64, max_interval: f64, min_conflicts: usize) -> Self {
+        Self {
+            plbd_watcher: PLBDWatcher2::new(time_constant),
+            max_interval: max_interval,
+            min_conflicts: min_conflicts,
+            last_plbd: 0,
+            number_of_conflicts: 0,
+            number_of_conflicts_at_previous_restart: 0,
+            summary: RestartPolicySummary::default(),
+        }
+    }
+}
+
+impl RestartPolicy for LogNormalRestart {
+    fn add_conflict(&mut self, plbd: u32, _engine: &impl EngineTrait) {
+        self.plbd_watcher.add(plbd);
+        self.last_plbd = plbd;
+        self.number_of_conflicts += 1;
+    }
+
+    fn should_restart(&self) -> bool {
+        let interval = f64::ceil(self.max_interval * (1.0 - self.plbd_watcher.cfd(self.last_plbd as f64))) as usize;
+        return self.number_of_conflicts >= self.min_conflicts
+            && self.number_of_conflicts >= self.number_of_conflicts_at_previous_restart + interval;
+    }
+
+    fn restart(&mut self) {
+        self.number_of_conflicts_at_previous_restart = self.number_of_conflicts;
+        self.summary.number_of_restarts += 1;
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return self.summary.clone();
+    }
+}
+
+/// 直近の PLBD の平均が全体の平均に比べて margin 倍を超えたら再始動する
+#[derive(Clone)]
+pub struct AverageRatioRestart {
+    plbd_watcher: PLBDWatcher,
+    margin: f64,
+    summary: RestartPolicySummary,
+}
+
+impl AverageRatioRestart {
+    pub fn new(long_term_time_constant: f64, short_term_time_constant: f64, margin: f64) -> Self {
+        Self {
+            plbd_watcher: PLBDWatcher::new(long_term_time_constant, short_term_time_constant),
+            margin: margin,
+            summary: RestartPolicySummary::default(),
+        }
+    }
+}
+
+impl RestartPolicy for AverageRatioRestart {
+    fn add_conflict(&mut self, plbd: u32, _engine: &impl EngineTrait) {
+        self.plbd_watcher.add(plbd);
+    }
+
+    fn should_restart(&self) -> bool {
+        return self.plbd_watcher.is_short_term_average_full() && self.plbd_watcher.ratio() > self.margin;
+    }
+
+    fn restart(&mut self) {
+        self.plbd_watcher.clear_short_term_average();
+        self.summary.number_of_restarts += 1;
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return self.summary.clone();
+    }
+}
+
+/// Glucose 風の再始動
+///
+/// PLBD の短期の指数移動平均が長期の指数移動平均の margin 倍を超えたら再始動する．
+/// 矛盾したときの割り当ての列が平均の blocking_factor 倍より長ければ，解に近づいているとみなして再始動を見送る．
+#[derive(Clone)]
+pub struct GlucoseEmaRestart {
+    fast_plbd_average: Average,
+    slow_plbd_average: Average,
+    trail_average: Average,
+    margin: f64,
+    blocking_factor: f64,
+    /// 再始動を見送り始めるまでの矛盾の数
+    min_conflicts_for_blocking: usize,
+    /// 再始動の後，次に再始動するまでの最小の矛盾の数
+    min_interval: usize,
+    number_of_conflicts: usize,
+    number_of_conflicts_since_restart: usize,
+    summary: RestartPolicySummary,
+}
+
+impl GlucoseEmaRestart {
+    pub fn new(
+        fast_time_constant: f64,
+        slow_time_constant: f64,
+        trail_time_constant: f64,
+        margin: f64,
+        blocking_factor: f64,
+    ) -> Self {
+        Self {
+            fast_plbd_average: Average::new(fast_time_constant),
+            slow_plbd_average: Average::new(slow_time_constant),
+            trail_average: Average::new(trail_time_constant),
+            margin: margin,
+            blocking_factor: blocking_factor,
+            min_conflicts_for_blocking: 10000,
+            min_interval: fast_time_constant.ceil() as usize,
+            number_of_conflicts: 0,
+            number_of_conflicts_since_restart: 0,
+            summary: RestartPolicySummary::default(),
+        }
+    }
+}
+
+impl RestartPolicy for GlucoseEmaRestart {
+    fn add_conflict(&mut self, plbd: u32, engine: &impl EngineTrait) {
+        self.number_of_conflicts += 1;
+        self.number_of_conflicts_since_restart += 1;
+        let trail_length = engine.number_of_assigneds() as f64;
+        if self.number_of_conflicts > self.min_conflicts_for_blocking
+            && self.number_of_conflicts_since_restart >= self.min_interval
+            && trail_length > self.blocking_factor * self.trail_average.value()
+        {
+            // 再始動を見送り，待ち直す
+            self.number_of_conflicts_since_restart = 0;
+            self.summary.number_of_blocked_restarts += 1;
+        }
+        self.trail_average.add(trail_length);
+        self.fast_plbd_average.add(plbd as f64);
+        self.slow_plbd_average.add(plbd as f64);
+    }
+
+    fn should_restart(&self) -> bool {
+        return self.number_of_conflicts_since_restart >= self.min_interval
+            && self.fast_plbd_average.value() > self.margin * self.slow_plbd_average.value();
+    }
+
+    fn restart(&mut self) {
+        self.number_of_conflicts_since_restart = 0;
+        self.summary.number_of_restarts += 1;
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return self.summary.clone();
+    }
+}
+
+/// unit * (Luby 数列の項) 回の矛盾ごとに再始動する
+#[derive(Clone)]
+pub struct LubyRestart {
+    unit: usize,
+    number_of_conflicts_since_restart: usize,
+    summary: RestartPolicySummary,
+}
+
+impl LubyRestart {
+    pub fn new(unit: usize) -> Self {
+        Self { unit: unit, number_of_conflicts_since_restart: 0, summary: RestartPolicySummary::default() }
+    }
+
+    /// Luby 数列 1, 1, 2, 1, 1, 2, 4, ... の i 番目(0 始まり)の項
+    fn luby(mut i: u32) -> usize {
+        // i を含む部分列 (長さ 2^k - 1) を探し，その中での位置に縮める
+        let mut size = 1;
+        let mut k = 0;
+        while size < i + 1 {
+            size = 2 * size + 1;
+            k += 1;
+        }
+        while size - 1 != i {
+            size = (size - 1) / 2;
+            k -= 1;
+            i %= size;
+        }
+        return 1 << k;
+    }
+}
+
+impl RestartPolicy for LubyRestart {
+    fn add_conflict(&mut self, _plbd: u32, _engine: &impl EngineTrait) {
+        self.number_of_conflicts_since_restart += 1;
+    }
+
+    fn should_restart(&self) -> bool {
+        return self.number_of_conflicts_since_restart >= self.unit * Self::luby(self.summary.number_of_restarts);
+    }
+
+    fn restart(&mut self) {
+        self.number_of_conflicts_since_restart = 0;
+        self.summary.number_of_restarts += 1;
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return self.summary.clone();
+    }
+}
+
+/// 再始動の間隔を factor 倍ずつ伸ばす
+#[derive(Clone)]
+pub struct GeometricRestart {
+    interval: f64,
+    factor: f64,
+    number_of_conflicts_since_restart: usize,
+    summary: RestartPolicySummary,
+}
+
+impl GeometricRestart {
+    pub fn new(initial_interval: f64, factor: f64) -> Self {
+        Self {
+            interval: initial_interval,
+            factor: factor,
+            number_of_conflicts_since_restart: 0,
+            summary: RestartPolicySummary::default(),
+        }
+    }
+}
+
+impl RestartPolicy for GeometricRestart {
+    fn add_conflict(&mut self, _plbd: u32, _engine: &impl EngineTrait) {
+        self.number_of_conflicts_since_restart += 1;
+    }
+
+    fn should_restart(&self) -> bool {
+        return self.number_of_conflicts_since_restart as f64 >= self.interval;
+    }
+
+    fn restart(&mut self) {
+        self.interval *= self.factor;
+        self.number_of_conflicts_since_restart = 0;
+        self.summary.number_of_restarts += 1;
+    }
+
+    fn summary(&self) -> RestartPolicySummary {
+        return self.summary.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, CoreEngine, CoreEngineExplainKey, EngineTrait, Literal, Reason};
+
+    use super::{GeometricRestart, GlucoseEmaRestart, LubyRestart, RestartPolicy};
+
+    type Engine = CoreEngine<CoreEngineExplainKey>;
+
+    /// number_of_assigneds 個の変数を決定したエンジン
+    fn make_engine(number_of_variables: u32, number_of_assigneds: u32) -> Engine {
+        let mut engine = Engine::default();
+        for _ in 0..number_of_variables {
+            engine.add_variable(Boolean::FALSE);
+        }
+        for index in 0..number_of_assigneds {
+            let _ = engine.assign(Literal::new(index, Boolean::TRUE), Reason::Decision);
+        }
+        return engine;
+    }
+
+    /// 再始動するまでに矛盾した回数を，再始動 number_of_restarts 回分並べる
+    fn intervals(policy: &mut impl RestartPolicy, number_of_restarts: usize) -> Vec<usize> {
+        let engine = make_engine(1, 0);
+        let mut intervals = Vec::new();
+        for _ in 0..number_of_restarts {
+            let mut interval = 0;
+            while !policy.should_restart() {
+                policy.add_conflict(1, &engine);
+                interval += 1;
+            }
+            policy.restart();
+            intervals.push(interval);
+        }
+        return intervals;
+    }
+
+    /// Luby 数列が 1, 1, 2, 1, 1, 2, 4, ... になることを確かめる
+    #[test]
+    fn test_luby_sequence() {
+        let sequence = Vec::from_iter((0..15).map(LubyRestart::luby));
+        assert_eq!(sequence, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    /// unit * (Luby 数列の項) 回の矛盾ごとに再始動することを確かめる
+    #[test]
+    fn test_luby_restart_intervals() {
+        let mut policy = LubyRestart::new(3);
+        assert_eq!(intervals(&mut policy, 7), [3, 3, 6, 3, 3, 6, 12]);
+        assert_eq!(policy.summary().number_of_restarts, 7);
+    }
+
+    /// 再始動の間隔が factor 倍ずつ伸びることを確かめる
+    #[test]
+    fn test_geometric_restart_intervals() {
+        let mut policy = GeometricRestart::new(2.0, 1.5);
+        assert_eq!(intervals(&mut policy, 5), [2, 3, 5, 7, 11]);
+        assert_eq!(policy.summary().number_of_restarts, 5);
+    }
+
+    /// 割り当ての列が平均より十分長い矛盾で再始動を見送り，最小の間隔を空けるまで再び見送らないことを確かめる
+    #[test]
+    fn test_glucose_ema_restart_counts_blocked_restarts() {
+        let mut policy = GlucoseEmaRestart::new(50.0, 5000.0, 5000.0, 1.25, 1.4);
+        let short_trail = make_engine(10, 1);
+        let long_trail = make_engine(10, 10);
+        for _ in 0..policy.min_conflicts_for_blocking {
+            policy.add_conflict(1, &short_trail);
+        }
+        assert_eq!(policy.summary().number_of_blocked_restarts, 0);
+        policy.add_conflict(1, &long_trail);
+        assert_eq!(policy.summary().number_of_blocked_restarts, 1);
+        for _ in 1..policy.min_interval {
+            policy.add_conflict(1, &long_trail);
+        }
+        assert_eq!(policy.summary().number_of_blocked_restarts, 1);
+        policy.add_conflict(1, &long_trail);
+        assert_eq!(policy.summary().number_of_blocked_restarts, 2);
+        assert_eq!(policy.summary().number_of_restarts, 0);
+    }
+}
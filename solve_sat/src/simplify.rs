@@ -12,7 +12,7 @@ pub struct Simplify {
 
 impl Simplify {
     #[inline(never)]
-    pub fn simplify(&mut self, clause: &mut Array<u32, Literal>, engine: &SATEngine) {
+    pub fn simplify(&mut self, clause: &mut Array<u32, Literal>, engine: &mut SATEngine) {
         if clause.len() <= 2 {
             return;
         }
@@ -46,7 +46,7 @@ impl Simplify {
         }
     }
 
-    fn is_redundant(&mut self, index: u32, engine: &SATEngine) -> bool {
+    fn is_redundant(&mut self, index: u32, engine: &mut SATEngine) -> bool {
         if let Some(is_redundant) = self.variable_index_to_redundancy.get(index) {
             // 当該変数がキャッシュに含まれていればキャッシュの内容を返却
             return *is_redundant;
@@ -70,13 +70,15 @@ impl Simplify {
             } else if reason.is_decision() {
                 // 当該変数が決定変数ならば false
                 is_redundant = false;
-            } else if let Reason::Propagation { explain_key } = reason {
-                // 当該変数の割当を説明する節を取得
-                let reason_constraint: Constraint<_> = engine.explain(explain_key.into()).into();
+            } else if let Some(Reason::Propagation { explain_key }) = engine.get_explained_reason(index) {
                 // 現在のスタックサイズを取得
                 let n = self.literal_stack.len();
-                // 当該変数以外の変数(当該変数への割当の原因になっている変数)をスタックに積む
-                self.literal_stack.extend(reason_constraint.iter().filter(|&l| l.index() != index));
+                {
+                    // 当該変数の割当を説明する節を取得
+                    let reason_constraint: Constraint<_> = engine.explain_propagation(explain_key.into()).into();
+                    // 当該変数以外の変数(当該変数への割当の原因になっている変数)をスタックに積む
+                    self.literal_stack.extend(reason_constraint.iter().filter(|&l| l.index() != index));
+                }
                 // NOTE: ループを 2 回に分けて，定数数時間でできる判定を先に行ったほうが良い
                 // 割当の原因になっている全変数について再帰して判定
                 for k in n..self.literal_stack.len() {
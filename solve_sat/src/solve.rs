@@ -1,136 +1,671 @@
 use std::time;
 
 use crate::{
+    acyclicity_theory::AcyclicityTheory,
     analyze::{Analyze, AnalyzeResult},
     binary_clause_theory::BinaryClauseTheory,
+    blocked_clause_elimination::BlockedClauseElimination,
     calculate_lbd::CalculatePLBD,
-    clause_theory::ClauseTheory,
+    cardinality_theory::CardinalityTheory,
+    clause_theory::{ClauseTheory, ClauseView},
     cnf::CNF,
-    engine::{ExplainKey, make_constraint},
-    plbd_watcher2::PLBDWatcher2,
-    pricer2::Pricer,
+    decision_heuristic::{DecisionHeuristic, SearchDecisionHeuristic},
+    difference_logic_theory::DifferenceLogicTheory,
+    engine::{EngineConstraint, ExplainKey, SATEngine, SATEngineSummary, TheoryKind, make_constraint, new_sat_engine},
+    external_propagator::{ExternalPropagator, ExternalPropagatorTheory},
+    integer_theory::IntegerTheory,
+    learning_rate_branching::LearningRateBranching,
+    mode_switching::{ModeSwitching, SearchMode},
+    model::{print_integer_model, print_integer_variable_model, print_model},
+    options::{DecisionHeuristicKind, PropagationOptions, RestartPolicyKind, SearchOptions},
+    phase::{PhaseSource, Phases},
+    preprocess::{
+        Preprocess, PreprocessResult, add_acyclicity_edge_at_root, add_cardinality_constraint_at_root,
+        add_clause_at_root, add_difference_atom_at_root, add_integer_model_at_root,
+        add_pseudo_boolean_constraint_at_root, add_xor_constraint_at_root,
+    },
+    pricer, pricer2,
+    probe::{Probe, ProbeResult},
+    pseudo_boolean_analyze::{PseudoBooleanAnalyze, PseudoBooleanAnalyzeResult},
+    pseudo_boolean_theory::{PseudoBooleanExplainKey, PseudoBooleanTheory},
+    restart_policy::{
+        AverageRatioRestart, GeometricRestart, GlucoseEmaRestart, LogNormalRestart, LubyRestart, RestartPolicy,
+        SearchRestartPolicy,
+    },
+    symmetry::SymmetryDetection,
+    xor_theory::XorTheory,
 };
 use cdcl_engine::{
-    Boolean, CoreEngine, EngineAddConstraintTrait, EngineTrait, Literal, OuterEngine, PropagationResult, Reason,
+    Boolean, DerivedClauses, EngineAddConstraintTrait, EngineGetTheoryTrait, EngineTrait, Literal, PropagationResult,
+    Reason,
 };
+use utility::Array;
 
-pub fn solve(cnf: &CNF) -> bool {
+/// 探索の結果
+pub enum SolveResult {
+    /// 元の論理式の変数への割り当て
+    Satisfiable {
+        model: Array<u32, Boolean>,
+    },
+    Unsatisfiable,
+    /// 時間切れ
+    Indefinite,
+}
+
+/// 充足不能であることを矛盾の数と経過時間とともに出力する
+fn report_unsatisfiable(number_of_conflicts: usize, start_time: &time::Instant) -> SolveResult {
+    println!("UNSATISFIABLE,{},{}", number_of_conflicts, start_time.elapsed().as_secs_f64());
+    return SolveResult::Unsatisfiable;
+}
+
+/// 結果を出力して返す．external_propagator があれば探索に接続する
+///
+/// 伝播器は全ての変数を観測するので，変数の置換・除去・ blocked clause の除去・対称性の除去は行わない．
+/// symmetry_breaking_effort は対称性の検出に費やす手間の上限で，0 であれば対称性を検出しない．
+pub fn solve(
+    cnf: &CNF,
+    is_xor_detection_enabled: bool,
+    symmetry_breaking_effort: usize,
+    propagation_options: &PropagationOptions,
+    search_options: &SearchOptions,
+    external_propagator: Option<Box<dyn ExternalPropagator>>,
+) -> SolveResult {
+    // 探索の前に根で行う探査の手間の上限と，再始動ごとに積み増す探査の手間
+    const INITIAL_PROBE_EFFORT: usize = 1000000;
+    const PROBE_EFFORT_PER_RESTART: usize = 10000;
+    // blocked clause の除去の手間の上限．探索中は BLOCKED_CLAUSE_ELIMINATION_INTERVAL 回の再始動ごとに行う
+    const INITIAL_BLOCKED_CLAUSE_ELIMINATION_EFFORT: usize = 10000000;
+    const BLOCKED_CLAUSE_ELIMINATION_EFFORT: usize = 1000000;
+    const BLOCKED_CLAUSE_ELIMINATION_INTERVAL: u32 = 16;
     let start_time = time::Instant::now();
 
-    let mut engine = OuterEngine::new(
+    let mut engine = new_sat_engine(
+        ExternalPropagatorTheory::default(),
+        IntegerTheory::default(),
+        DifferenceLogicTheory::default(),
+        AcyclicityTheory::default(),
+        XorTheory::default(),
+        PseudoBooleanTheory::new(10000),
+        CardinalityTheory::default(),
         ClauseTheory::new(1e4),
-        OuterEngine::new(BinaryClauseTheory::default(), CoreEngine::<ExplainKey>::default()),
+        BinaryClauseTheory::new(100000),
     );
-    let mut pricer = Pricer::new(1e2);
+    for &kind in propagation_options.deferred_theories.iter() {
+        kind.propagation_policy_mut(&mut engine).is_deferred = true;
+    }
+    if propagation_options.is_statistics_printing_enabled {
+        for &(_, kind) in TheoryKind::NAMES.iter() {
+            kind.propagation_policy_mut(&mut engine).is_timed = true;
+        }
+    }
+    let is_external_propagator_connected = external_propagator.is_some();
+    if let Some(external_propagator) = external_propagator {
+        engine.get_theory_mut::<ExternalPropagatorTheory>().connect_propagator(external_propagator);
+    }
+    let mut mode_switching = ModeSwitching::new(
+        (
+            new_decision_heuristic(search_options.decision_heuristic, SearchMode::Focused),
+            new_restart_policy(search_options.restart_policy),
+        ),
+        (
+            new_decision_heuristic(search_options.decision_heuristic, SearchMode::Stable),
+            new_stable_restart_policy(),
+        ),
+        search_options.is_mode_switching_enabled,
+        1e3,
+        2.0,
+    );
+    let mut phases = Phases::new(search_options.is_rephasing_enabled, 1000);
     let mut analyze = Analyze::default();
+    analyze.set_reason_side_enabled(mode_switching.is_reason_side_rate_used());
+    let mut pseudo_boolean_analyze = PseudoBooleanAnalyze::default();
     let calculate_lbd = CalculatePLBD::default();
-    let mut plbd_watcher = PLBDWatcher2::new(1e5);
+    let mut probe = Probe::default();
+    let mut blocked_clause_elimination = BlockedClauseElimination::new(true);
+
+    let mut preprocess = Preprocess::new(10000000, is_xor_detection_enabled);
+    if is_external_propagator_connected {
+        preprocess.freeze_every_variable();
+    }
+    let preprocess_result = preprocess.preprocess(cnf);
+    preprocess.print_summary();
+    let (cnf, mut reconstruction_stack) = match preprocess_result {
+        PreprocessResult::Unsatisfiable => {
+            return report_unsatisfiable(0, &start_time);
+        }
+        PreprocessResult::Simplified { cnf, reconstruction_stack } => (*cnf, reconstruction_stack),
+    };
 
-    let number_of_variables = cnf.clauses.iter().map(|c| c.iter().map(|l| l.index()).max().unwrap()).max().unwrap() + 1;
+    let number_of_variables = cnf.number_of_variables;
     for _ in 0..number_of_variables {
         engine.add_variable(Boolean::FALSE);
-        pricer.add_variable(0.0, false);
+        mode_switching.add_variable(0.0, false);
+        phases.add_variable(Boolean::FALSE);
     }
 
     for clause in cnf.clauses.iter() {
-        let constraint = make_constraint(clause.iter().cloned());
-        let result = engine.add_constraint(constraint.into(), false);
-        if result.is_conflict() {
-            println!("UNSATISFIABLE,{},{}", 1, start_time.elapsed().as_secs_f64());
-            return false;
+        if !add_clause_at_root(&mut engine, &clause[..]) {
+            return report_unsatisfiable(1, &start_time);
+        }
+    }
+    for constraint in cnf.cardinality_constraints.iter() {
+        if !add_cardinality_constraint_at_root(&mut engine, constraint) {
+            return report_unsatisfiable(1, &start_time);
+        }
+    }
+    for constraint in cnf.pseudo_boolean_constraints.iter() {
+        if !add_pseudo_boolean_constraint_at_root(&mut engine, constraint) {
+            return report_unsatisfiable(1, &start_time);
+        }
+    }
+    for constraint in cnf.xor_constraints.iter() {
+        if !add_xor_constraint_at_root(&mut engine, constraint) {
+            return report_unsatisfiable(1, &start_time);
+        }
+    }
+    for edge in cnf.acyclicity_edges.iter() {
+        if !add_acyclicity_edge_at_root(&mut engine, edge) {
+            return report_unsatisfiable(1, &start_time);
+        }
+    }
+    for atom in cnf.difference_atoms.iter() {
+        if !add_difference_atom_at_root(&mut engine, atom) {
+            return report_unsatisfiable(1, &start_time);
         }
     }
 
+    // 整数変数のリテラルは補助変数として作る
+    if !add_integer_model_at_root(&mut engine, &cnf) {
+        return report_unsatisfiable(1, &start_time);
+    }
+    for _ in number_of_variables..engine.number_of_variables() {
+        mode_switching.add_variable(0.0, false);
+        phases.add_variable(Boolean::FALSE);
+    }
+
+    // 対称性の検出と lex-leader 制約の追加
+    let mut symmetry_detection = SymmetryDetection::new(symmetry_breaking_effort);
+    let mut number_of_all_variables = engine.number_of_variables();
+    let generators = if is_external_propagator_connected || symmetry_breaking_effort == 0 {
+        Array::default()
+    } else {
+        symmetry_detection.find_generators(&cnf)
+    };
+    for generator in generators.iter() {
+        let clauses = symmetry_detection.make_symmetry_breaking_clauses(&generator[..], &mut number_of_all_variables);
+        while engine.number_of_variables() < number_of_all_variables {
+            engine.add_variable(Boolean::FALSE);
+            mode_switching.add_variable(0.0, false);
+            phases.add_variable(Boolean::FALSE);
+        }
+        for clause in clauses.iter() {
+            if !add_clause_at_root(&mut engine, &clause[..]) {
+                return report_unsatisfiable(0, &start_time);
+            }
+        }
+    }
+    println!("c symmetry generators: {}", symmetry_detection.summary().number_of_generators);
+    println!("c symmetry breaking clauses: {}", symmetry_detection.summary().number_of_symmetry_breaking_clauses);
+
+    if let ProbeResult::Unsatisfiable = probe.probe(&mut engine, INITIAL_PROBE_EFFORT) {
+        return report_unsatisfiable(0, &start_time);
+    }
+    blocked_clause_elimination.eliminate(
+        &mut engine,
+        &mut reconstruction_stack,
+        INITIAL_BLOCKED_CLAUSE_ELIMINATION_EFFORT,
+    );
+    let mut derived_clauses = DerivedClauses::default();
+    let mut number_of_root_removed_constraints = 0;
+    let mut number_of_chronological_backtracks = 0;
+    let mut number_of_partial_restarts: u32 = 0;
+    let mut number_of_reused_decision_levels: u64 = 0;
+    let mut last_reused_decision_level = 0;
+    let mut probe_effort = 0;
+
+    // 理論ごとの列は SATEngineSummary が理論を並べた順に出力する
     eprintln!(
-        "RESTART\tCONFLICT\tDECISION_LEVEL\tPLBD\tFIXED\tBINARY_CLAUSE\tBINARY_LEARNT_CLAUSE\tCLAUSE\tLEARNT_CLAUSE",
+        "RESTART\tCONFLICT\tDECISION_LEVEL\tPLBD\t{}\tFAILED_LITERAL\tHYPER_BINARY_RESOLVENT\tBLOCKED\tCOVERED\tROOT_REMOVED\tBLOCKED_RESTART\tMODE\tREPHASE\tCHRONOLOGICAL_BACKTRACK\tREUSED_DECISION_LEVEL",
+        Vec::from_iter(SATEngineSummary::progress_headers()).join("\t"),
     );
 
-    let mut restart_count: usize = 0;
     let mut conflict_count: usize = 0;
     macro_rules! print_progress {
         ($decision_level: expr, $plbd:expr) => {
-            let summary = engine.summary();
+            let summary = SATEngineSummary::from(engine.summary());
             eprintln!(
-                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                restart_count,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                mode_switching.summary().number_of_restarts,
                 conflict_count,
                 $decision_level,
                 $plbd,
-                summary.1.1.number_of_fixed_variables,
-                summary.1.0.number_of_binary_clauses,
-                summary.1.0.number_of_learnt_binary_clauses,
-                summary.0.number_of_clauses,
-                summary.0.number_of_learnt_clauses,
+                Vec::from_iter(summary.progress_values().map(|value| value.to_string())).join("\t"),
+                probe.summary().number_of_failed_literals,
+                probe.summary().number_of_hyper_binary_resolvents,
+                blocked_clause_elimination.summary().number_of_blocked_clauses,
+                blocked_clause_elimination.summary().number_of_covered_clauses,
+                number_of_root_removed_constraints,
+                mode_switching.summary().number_of_blocked_restarts,
+                mode_switching.mode().name(),
+                phases.last_source().map_or("-", |source| source.name()),
+                number_of_chronological_backtracks,
+                last_reused_decision_level,
             );
         };
     }
 
-    let mut conflict_count_at_previous_restart = 0;
+    macro_rules! print_statistics {
+        () => {
+            if propagation_options.is_statistics_printing_enabled {
+                print_propagation_statistics(&engine);
+            }
+            if search_options.is_rephasing_enabled {
+                print_rephase_statistics(&phases);
+            }
+            if search_options.is_trail_reuse_enabled {
+                println!(
+                    "c trail reuse: {} partial restarts {} reused decision levels",
+                    number_of_partial_restarts, number_of_reused_decision_levels
+                );
+            }
+        };
+    }
+
+    // 理論が探索中に作った変数を決定ヒューリスティックと位相に加える
+    let mut number_of_search_variables = engine.number_of_variables();
+    macro_rules! add_new_variables {
+        () => {
+            while number_of_search_variables < engine.number_of_variables() {
+                mode_switching.add_variable(0.0, engine.is_assigned(number_of_search_variables));
+                phases.add_variable(Boolean::FALSE);
+                number_of_search_variables += 1;
+            }
+        };
+    }
+
     let mut propagation_result = PropagationResult::Noconflict;
-    loop {
+    'search: loop {
+        add_new_variables!();
         if start_time.elapsed() > time::Duration::from_secs(60) {
+            print_statistics!();
             println!("INDEFINITE,{},{}", conflict_count, start_time.elapsed().as_secs_f64());
-            return false;
+            return SolveResult::Indefinite;
         }
         if let PropagationResult::Conflict { explain_key } = propagation_result {
             conflict_count += 1;
             if engine.current_decision_level() == 0 {
-                println!("UNSATISFIABLE,{},{}", conflict_count, start_time.elapsed().as_secs_f64());
-                return false;
+                print_statistics!();
+                return report_unsatisfiable(conflict_count, &start_time);
             }
-            let analyze_result = analyze.analyze(explain_key, &engine);
-            match analyze_result {
-                AnalyzeResult::Unsatisfiable => {
-                    println!("UNSATISFIABLE,{},{}", conflict_count, start_time.elapsed().as_secs_f64());
-                    return false;
+            // 擬似ブール制約の矛盾は cutting planes で解析し，打ち切った場合は節を学習する
+            let mut is_learnt = false;
+            if let ExplainKey::PseudoBooleanTheory(PseudoBooleanExplainKey::Conflict { .. }) = explain_key {
+                match pseudo_boolean_analyze.analyze(explain_key, &mut engine) {
+                    PseudoBooleanAnalyzeResult::Unsatisfiable => {
+                        print_statistics!();
+                        return report_unsatisfiable(conflict_count, &start_time);
+                    }
+                    PseudoBooleanAnalyzeResult::Backjumpable {
+                        backjump_decision_level,
+                        learnt_constraint,
+                        related_variables,
+                    } => {
+                        let plbd = calculate_lbd.calculate(learnt_constraint.terms.iter().map(|&(_, l)| l), &engine);
+                        mode_switching.add_conflict(plbd, &engine);
+                        phases.add_conflict(&engine);
+                        mode_switching.increase_price(
+                            related_variables,
+                            std::iter::empty(),
+                            &engine,
+                            backjump_decision_level,
+                        );
+                        {
+                            let unassigned_literals = engine.backjump(backjump_decision_level);
+                            for unassigned_literal in unassigned_literals {
+                                mode_switching.set_to_unassigned(unassigned_literal.index());
+                                phases.set_to_unassigned(unassigned_literal);
+                            }
+                        };
+                        print_progress!(engine.current_decision_level(), plbd);
+                        propagation_result = if learnt_constraint.degree == 1 {
+                            // 係数は飽和により全て 1 なので節
+                            let learnt_clause = make_constraint(learnt_constraint.terms.iter().map(|&(_, l)| l));
+                            engine.add_constraint(learnt_clause.into(), true)
+                        } else {
+                            let learnt_constraint: EngineConstraint<ClauseView<std::iter::Empty<Literal>>> =
+                                learnt_constraint.into();
+                            engine.add_constraint(learnt_constraint, true)
+                        };
+                        is_learnt = true;
+                    }
+                    PseudoBooleanAnalyzeResult::Aborted => {}
                 }
-                AnalyzeResult::Backjumpable { backjump_decision_level, learnt_clause, related_variables } => {
-                    let plbd = calculate_lbd.calculate(learnt_clause.iter(), &engine);
-                    plbd_watcher.add(plbd);
-                    pricer.increase_price(related_variables, &engine, backjump_decision_level);
-                    {
-                        let unassigned_literals = engine.backjump(backjump_decision_level);
-                        for unassigned_literal in unassigned_literals {
-                            pricer.set_to_unassigned(unassigned_literal.index());
-                        }
-                    };
-                    print_progress!(engine.current_decision_level(), plbd);
-                    propagation_result = engine.add_constraint(learnt_clause.into(), true);
-                    if propagation_result.is_no_conflict() && engine.current_decision_level() != 0 {
-                        if conflict_count >= 100
-                            && conflict_count
-                                >= conflict_count_at_previous_restart
-                                    + f64::ceil(1e4 * (1.0 - plbd_watcher.cfd(plbd as f64))) as usize
+            }
+            if !is_learnt {
+                match analyze.analyze(explain_key, &mut engine) {
+                    AnalyzeResult::Unsatisfiable => {
+                        print_statistics!();
+                        return report_unsatisfiable(conflict_count, &start_time);
+                    }
+                    AnalyzeResult::Backjumpable {
+                        backjump_decision_level,
+                        conflict_decision_level,
+                        learnt_clause,
+                        related_variables,
+                        reason_side_variables,
+                    } => {
+                        // 大きく戻るときは，矛盾した決定レベルより前の割り当てを残して学習節を低い決定レベルで伝播させる
+                        // (単位節は決定レベル 0 で加える)
+                        let backjump_decision_level = if let Some(threshold) =
+                            search_options.chronological_backtracking_threshold
+                            && backjump_decision_level > 0
+                            && conflict_decision_level - backjump_decision_level > threshold
                         {
-                            let unassigned_literals = engine.backjump(0);
+                            number_of_chronological_backtracks += 1;
+                            conflict_decision_level - 1
+                        } else {
+                            backjump_decision_level
+                        };
+                        let plbd = calculate_lbd.calculate(learnt_clause.iter(), &engine);
+                        mode_switching.add_conflict(plbd, &engine);
+                        phases.add_conflict(&engine);
+                        mode_switching.increase_price(
+                            related_variables,
+                            reason_side_variables,
+                            &engine,
+                            backjump_decision_level,
+                        );
+                        {
+                            let unassigned_literals = engine.backjump(backjump_decision_level);
                             for unassigned_literal in unassigned_literals {
-                                pricer.set_to_unassigned(unassigned_literal.index());
+                                mode_switching.set_to_unassigned(unassigned_literal.index());
+                                phases.set_to_unassigned(unassigned_literal);
                             }
-                            restart_count += 1;
-                            conflict_count_at_previous_restart = conflict_count;
-                            engine.reduce_constraints();
-                        }
+                        };
+                        print_progress!(engine.current_decision_level(), plbd);
+                        propagation_result = engine.add_constraint(learnt_clause.into(), true);
                     }
                 }
             }
+            if propagation_result.is_no_conflict()
+                && engine.current_decision_level() != 0
+                && mode_switching.should_restart()
+            {
+                add_new_variables!();
+                mode_switching.restart();
+                phases.restart();
+                let restart_count = mode_switching.summary().number_of_restarts;
+                // rephase ・ blocked clause の除去・決定レベル 0 での簡略化を行う再始動では全ての割り当てを取り消す
+                let reused_decision_level = if search_options.is_trail_reuse_enabled
+                    && !phases.should_rephase()
+                    && !restart_count.is_multiple_of(4)
+                {
+                    calculate_reusable_decision_level(&engine, &mut mode_switching)
+                } else {
+                    0
+                };
+                let unassigned_literals = engine.backjump(reused_decision_level);
+                for unassigned_literal in unassigned_literals {
+                    mode_switching.set_to_unassigned(unassigned_literal.index());
+                    phases.set_to_unassigned(unassigned_literal);
+                }
+                last_reused_decision_level = reused_decision_level;
+                // 学習した制約は割り当ての理由であっても説明できるまま削除されるので，決定レベルによらず整理する
+                engine.reduce_constraints();
+                // 探査は決定レベル 0 でしか行えないので，割り当てを残した再始動の分は次に全て取り消したときにまとめて行う
+                probe_effort += PROBE_EFFORT_PER_RESTART;
+                if reused_decision_level > 0 {
+                    number_of_partial_restarts += 1;
+                    number_of_reused_decision_levels += reused_decision_level as u64;
+                    continue 'search;
+                }
+                if phases.should_rephase() {
+                    phases.rephase(&cnf.clauses, &engine);
+                }
+                if let ProbeResult::Unsatisfiable = probe.probe(&mut engine, probe_effort) {
+                    print_statistics!();
+                    return report_unsatisfiable(conflict_count, &start_time);
+                }
+                probe_effort = 0;
+                if restart_count.is_multiple_of(BLOCKED_CLAUSE_ELIMINATION_INTERVAL) {
+                    blocked_clause_elimination.eliminate(
+                        &mut engine,
+                        &mut reconstruction_stack,
+                        BLOCKED_CLAUSE_ELIMINATION_EFFORT,
+                    );
+                }
+                if restart_count.is_multiple_of(4) {
+                    let Some(number_of_removed_constraints) =
+                        simplify_at_root(&mut engine, &mut derived_clauses, 1000000)
+                    else {
+                        print_statistics!();
+                        return report_unsatisfiable(conflict_count, &start_time);
+                    };
+                    number_of_root_removed_constraints += number_of_removed_constraints;
+                }
+            }
+        } else if let Some(decision_literal) = engine.suggest_decision() {
+            // 理論の提案があれば Pricer より優先する
+            propagation_result = engine.assign(decision_literal, Reason::Decision);
         } else {
             let decision_index = {
                 let mut index;
                 loop {
-                    index = pricer.peek();
+                    index = mode_switching.peek();
                     if index.is_none() {
+                        // 理論が解を拒否した場合や未割り当ての変数を作った場合は，それらとともに探索を続ける
+                        propagation_result = engine.final_check();
+                        if propagation_result.is_conflict()
+                            || engine.number_of_assigneds() < engine.number_of_variables()
+                        {
+                            continue 'search;
+                        }
+                        print_statistics!();
                         println!("SATISFIABLE,{},{}", conflict_count, start_time.elapsed().as_secs_f64());
-                        return false;
+                        let mut model =
+                            Array::from_iter((0..engine.number_of_variables()).map(|index| engine.get_value(index)));
+                        reconstruction_stack.extend_model(&mut model);
+                        // 補助変数を除く
+                        model.truncate(number_of_variables);
+                        print_model(&model);
+                        print_integer_model(engine.get_theory());
+                        print_integer_variable_model(engine.get_theory(), &engine);
+                        return SolveResult::Satisfiable { model: model };
                     }
-                    pricer.set_to_assigned(index.unwrap());
+                    mode_switching.set_to_assigned(index.unwrap());
                     if !engine.is_assigned(index.unwrap()) {
                         break;
                     }
                 }
                 index.unwrap()
             };
-            let decision_value = engine.get_value(decision_index);
+            let decision_value = engine
+                .suggest_phase(decision_index)
+                .unwrap_or_else(|| phases.get(decision_index, mode_switching.mode()));
             propagation_result = engine.assign(Literal::new(decision_index, decision_value), Reason::Decision);
         }
     }
 }
+
+/// 再始動で取り消さずに残せる決定レベル
+///
+/// 決定した変数の優先度が未割り当ての変数の優先度の最大値以上である決定レベルまでは，
+/// 全て取り消しても同じ変数を同じ順に決定し直すことになるので残す．
+fn calculate_reusable_decision_level(
+    engine: &impl EngineTrait,
+    decision_heuristic: &mut impl DecisionHeuristic,
+) -> u32 {
+    let next_index = loop {
+        let Some(index) = decision_heuristic.peek() else {
+            return engine.current_decision_level();
+        };
+        if !engine.is_assigned(index) {
+            break index;
+        }
+        decision_heuristic.set_to_assigned(index);
+    };
+    let next_activity = decision_heuristic.activity(next_index);
+    for decision_level in 1..(engine.current_decision_level() + 1) {
+        let decision_index = engine.get_decision_literal(decision_level).index();
+        if decision_heuristic.activity(decision_index) < next_activity {
+            return decision_level - 1;
+        }
+    }
+    return engine.current_decision_level();
+}
+
+/// stable モードでは優先度をゆっくり減衰させる
+fn new_decision_heuristic(kind: DecisionHeuristicKind, mode: SearchMode) -> SearchDecisionHeuristic {
+    return match (kind, mode) {
+        (DecisionHeuristicKind::Vsids, SearchMode::Focused) => SearchDecisionHeuristic::Vsids(pricer::Pricer::new(2e1)),
+        (DecisionHeuristicKind::Vsids, SearchMode::Stable) => SearchDecisionHeuristic::Vsids(pricer::Pricer::new(1e2)),
+        (DecisionHeuristicKind::Adam, SearchMode::Focused) => SearchDecisionHeuristic::Adam(pricer2::Pricer::new(1e2)),
+        (DecisionHeuristicKind::Adam, SearchMode::Stable) => SearchDecisionHeuristic::Adam(pricer2::Pricer::new(1e3)),
+        (DecisionHeuristicKind::LearningRateBranching, SearchMode::Focused) => {
+            SearchDecisionHeuristic::LearningRateBranching(LearningRateBranching::new(0.4, 0.06, 1e-6))
+        }
+        (DecisionHeuristicKind::LearningRateBranching, SearchMode::Stable) => {
+            SearchDecisionHeuristic::LearningRateBranching(LearningRateBranching::new(0.2, 0.02, 1e-6))
+        }
+    };
+}
+
+fn new_restart_policy(kind: RestartPolicyKind) -> SearchRestartPolicy {
+    return match kind {
+        RestartPolicyKind::LogNormal => SearchRestartPolicy::LogNormal(LogNormalRestart::new(1e5, 1e4, 100)),
+        RestartPolicyKind::AverageRatio => SearchRestartPolicy::AverageRatio(AverageRatioRestart::new(1e4, 50.0, 1.25)),
+        RestartPolicyKind::GlucoseEma => {
+            SearchRestartPolicy::GlucoseEma(GlucoseEmaRestart::new(32.0, 1e5, 5000.0, 1.25, 1.4))
+        }
+        RestartPolicyKind::Luby => SearchRestartPolicy::Luby(LubyRestart::new(100)),
+        RestartPolicyKind::Geometric => SearchRestartPolicy::Geometric(GeometricRestart::new(100.0, 1.5)),
+    };
+}
+
+/// stable モードでは長い間隔の Luby 数列で再始動する
+fn new_stable_restart_policy() -> SearchRestartPolicy {
+    return SearchRestartPolicy::Luby(LubyRestart::new(1024));
+}
+
+/// 決定レベル 0 で理論に制約を整理させ，導いた節を内側の層に加えて，削除した制約の数を返す．矛盾すれば None を返す
+fn simplify_at_root(engine: &mut SATEngine, derived_clauses: &mut DerivedClauses, effort: usize) -> Option<u32> {
+    debug_assert!(engine.current_decision_level() == 0);
+    derived_clauses.clear();
+    let number_of_removed_constraints = engine.simplify_at_root(effort, derived_clauses);
+    for &literal in derived_clauses.units.iter() {
+        if engine.is_false(literal) {
+            return None;
+        }
+        if engine.add_constraint(make_constraint([literal].into_iter()).into(), false).is_conflict() {
+            return None;
+        }
+    }
+    for &binary_clause in derived_clauses.binary_clauses.iter() {
+        if binary_clause.iter().any(|&l| engine.is_true(l)) {
+            continue;
+        }
+        if binary_clause.iter().all(|&l| engine.is_false(l)) {
+            return None;
+        }
+        if engine.add_constraint(make_constraint(binary_clause.into_iter()).into(), false).is_conflict() {
+            return None;
+        }
+    }
+    return Some(number_of_removed_constraints);
+}
+
+/// 理論を加えた層の伝播の方針
+/// 1 行に 1 つずつ "c propagation <理論>: <処理したリテラルの数> literals <秒数> s" の形式で出力する
+fn print_propagation_statistics(engine: &SATEngine) {
+    for &(name, kind) in TheoryKind::NAMES.iter() {
+        let statistics = kind.propagation_statistics(engine);
+        println!(
+            "c propagation {}: {} literals {:.3} s",
+            name,
+            statistics.number_of_processed_literals,
+            statistics.propagation_time.as_secs_f64()
+        );
+    }
+}
+
+/// 1 行に 1 つずつ "c rephase <取り出し元>: <再設定の回数> rephases <最長の矛盾のない割り当ての列を更新した回数> improvements" の形式で出力する
+fn print_rephase_statistics(phases: &Phases) {
+    for source in PhaseSource::ALL {
+        let statistics = phases.statistics(source);
+        println!(
+            "c rephase {}: {} rephases {} improvements",
+            source.name(),
+            statistics.number_of_rephases,
+            statistics.number_of_improvements
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, Literal};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+    use utility::Array;
+
+    use super::{SolveResult, solve};
+    use crate::{
+        cnf::CNF,
+        options::{PropagationOptions, SearchOptions},
+    };
+
+    fn make_random_cnf(rng: &mut SmallRng, number_of_variables: u32, number_of_clauses: u32) -> CNF {
+        let mut cnf = CNF { number_of_variables: number_of_variables, ..CNF::default() };
+        for _ in 0..number_of_clauses {
+            let mut clause = Array::<u32, Literal>::default();
+            while clause.len() < 3 {
+                let index = rng.gen_range(0..number_of_variables);
+                if clause.iter().all(|l| l.index() != index) {
+                    clause.push(Literal::new(index, Boolean::from(rng.gen_bool(0.5))));
+                }
+            }
+            cnf.clauses.push(clause);
+        }
+        return cnf;
+    }
+
+    fn is_satisfied(cnf: &CNF, model: &Array<u32, Boolean>) -> bool {
+        return cnf.clauses.iter().all(|clause| clause.iter().any(|l| model[l.index()] == l.value()));
+    }
+
+    /// search_options による探索が，既定の探索と同じ結果になることを確かめる
+    fn assert_agrees_with_default_search(search_options: &SearchOptions) {
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut number_of_satisfiables = 0;
+        let mut number_of_unsatisfiables = 0;
+        for _ in 0..40 {
+            let cnf = make_random_cnf(&mut rng, 100, 426);
+            let expected = solve(&cnf, false, 0, &PropagationOptions::default(), &SearchOptions::default(), None);
+            let actual = solve(&cnf, false, 0, &PropagationOptions::default(), search_options, None);
+            match (expected, actual) {
+                (SolveResult::Satisfiable { model: expected_model }, SolveResult::Satisfiable { model }) => {
+                    assert!(is_satisfied(&cnf, &expected_model));
+                    assert!(is_satisfied(&cnf, &model));
+                    number_of_satisfiables += 1;
+                }
+                (SolveResult::Unsatisfiable, SolveResult::Unsatisfiable) => number_of_unsatisfiables += 1,
+                _ => panic!("the search options changed the result"),
+            }
+        }
+        assert!(number_of_satisfiables > 0 && number_of_unsatisfiables > 0);
+    }
+
+    /// 決定レベルの差が 1 を超えれば chronological backtracking する探索が，しない探索と同じ結果になることを確かめる
+    #[test]
+    fn test_chronological_backtracking_agrees_with_non_chronological_backtracking() {
+        assert_agrees_with_default_search(&SearchOptions {
+            chronological_backtracking_threshold: Some(1),
+            ..SearchOptions::default()
+        });
+    }
+
+    /// 再始動で割り当てを残す探索が，全て取り消す探索と同じ結果になることを確かめる
+    #[test]
+    fn test_trail_reuse_agrees_with_full_restarts() {
+        assert_agrees_with_default_search(&SearchOptions { is_trail_reuse_enabled: true, ..SearchOptions::default() });
+    }
+}
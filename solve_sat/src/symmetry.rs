@@ -0,0 +1,395 @@
+use cdcl_engine::{Boolean, Literal};
+use utility::Array;
+
+use crate::cnf::CNF;
+
+#[derive(Default, Clone)]
+pub struct SymmetrySummary {
+    pub number_of_generators: u32,
+    pub number_of_symmetry_breaking_clauses: u32,
+}
+
+/// 頂点の順序付き分割
+#[derive(Clone)]
+struct Partition {
+    elements: Array<u32, u32>,
+    /// 頂点 -> 属するセルの先頭の位置
+    cell_of: Array<u32, u32>,
+    /// セルの先頭の位置 -> セルの末尾の位置(先頭でない位置の値は使わない)
+    cell_ends: Array<u32, u32>,
+    number_of_cells: u32,
+}
+
+impl Partition {
+    fn is_discrete(&self) -> bool {
+        return self.number_of_cells == self.elements.len();
+    }
+
+    fn first_non_singleton_cell(&self) -> Option<u32> {
+        let mut start = 0;
+        while start < self.elements.len() {
+            if self.cell_ends[start] - start > 1 {
+                return Some(start);
+            }
+            start = self.cell_ends[start];
+        }
+        return None;
+    }
+
+    /// vertex だけからなるセルを，vertex の属するセルの先頭に切り出す
+    fn individualize(&mut self, vertex: u32) {
+        let start = self.cell_of[vertex];
+        let end = self.cell_ends[start];
+        debug_assert!(end - start > 1);
+        let position = start + self.elements[start..end].iter().position(|&v| v == vertex).unwrap() as u32;
+        self.elements.swap(start, position);
+        self.cell_ends[start] = start + 1;
+        self.cell_ends[start + 1] = end;
+        for position in start + 1..end {
+            self.cell_of[self.elements[position]] = start + 1;
+        }
+        self.number_of_cells += 1;
+    }
+
+    fn has_same_cells(&self, other: &Partition) -> bool {
+        let mut start = 0;
+        while start < self.elements.len() {
+            if self.cell_ends[start] != other.cell_ends[start] || other.cell_of[other.elements[start]] != start {
+                return false;
+            }
+            start = self.cell_ends[start];
+        }
+        return true;
+    }
+}
+
+/// 節・基数制約とリテラルを頂点とする色付きグラフの自己同型群の生成元を求め，lex-leader 制約を作る
+///
+/// 自己同型は色の細分化と個別化による探索(最初の葉との比較)で求め，見つかった置換は辺を保存するかを確かめてから採用する．
+/// 探索は effort で打ち切るので，求まる生成元は群の一部であってもよい．
+pub struct SymmetryDetection {
+    effort: usize,
+    ticks: usize,
+    /// 頂点 -> 隣接する頂点(整列済み)
+    /// リテラル x の頂点は x.bits()，i 番目の節の頂点は 2 * (変数の数) + i，
+    /// j 番目の基数制約の頂点は 2 * (変数の数) + (節の数) + j
+    adjacency: Array<u32, Array<u32, u32>>,
+    /// 頂点 -> 隣接する頂点の属するセル(整列済み)
+    signatures: Array<u32, Array<u32, u32>>,
+    /// 最初の葉への経路上の分割と，そこで個別化した頂点
+    first_path: Array<u32, (Partition, u32)>,
+    first_leaf: Array<u32, u32>,
+    /// 生成元から求めた軌道(union-find)
+    orbit_parents: Array<u32, u32>,
+    summary: SymmetrySummary,
+}
+
+impl SymmetryDetection {
+    /// lex-leader 制約に用いる変数の数の上限
+    const MAX_SYMMETRY_BREAKING_LENGTH: u32 = 100;
+
+    pub fn new(effort: usize) -> Self {
+        Self {
+            effort: effort,
+            ticks: 0,
+            adjacency: Array::default(),
+            signatures: Array::default(),
+            first_path: Array::default(),
+            first_leaf: Array::default(),
+            orbit_parents: Array::default(),
+            summary: SymmetrySummary::default(),
+        }
+    }
+
+    pub fn summary(&self) -> &SymmetrySummary {
+        return &self.summary;
+    }
+
+    /// 生成元をリテラルの置換(リテラルの bits -> 像)として返す
+    ///
+    /// 節と基数制約以外の理論の制約(擬似ブール制約・ XOR 制約・非巡回性の辺・差分論理の原子・整数変数)を含む場合は，
+    /// 係数・偶奇・グラフ・整数変数をグラフで表さないので，対称性を検出しない．
+    pub fn find_generators(&mut self, cnf: &CNF) -> Array<u32, Array<u32, Literal>> {
+        if cnf.has_non_cardinality_theory_constraints() {
+            return Array::default();
+        }
+        let number_of_literals = 2 * cnf.number_of_variables;
+        let first_cardinality_vertex = number_of_literals + cnf.clauses.len();
+        let number_of_vertices = first_cardinality_vertex + cnf.cardinality_constraints.len();
+        self.ticks = 0;
+        self.adjacency.clear();
+        self.adjacency.resize_with(number_of_vertices, Array::default);
+        for index in 0..cnf.number_of_variables {
+            let literal = Literal::new(index, Boolean::TRUE);
+            self.adjacency[literal.bits()].push((!literal).bits());
+            self.adjacency[(!literal).bits()].push(literal.bits());
+        }
+        for (clause_index, clause) in cnf.clauses.iter().enumerate() {
+            let clause_vertex = number_of_literals + clause_index as u32;
+            for literal in clause.iter() {
+                self.adjacency[clause_vertex].push(literal.bits());
+                self.adjacency[literal.bits()].push(clause_vertex);
+            }
+        }
+        for (constraint_index, constraint) in cnf.cardinality_constraints.iter().enumerate() {
+            let constraint_vertex = first_cardinality_vertex + constraint_index as u32;
+            for literal in constraint.literals.iter() {
+                self.adjacency[constraint_vertex].push(literal.bits());
+                self.adjacency[literal.bits()].push(constraint_vertex);
+            }
+        }
+        for neighbors in self.adjacency.iter_mut() {
+            neighbors.sort_unstable_by_key(|&v| v);
+        }
+        self.signatures.clear();
+        self.signatures.resize_with(number_of_vertices, Array::default);
+        self.orbit_parents = Array::from_iter(0..number_of_vertices);
+
+        // リテラル・節・ bound ごとの基数制約を別の色とした初期分割
+        let colors = Array::<u32, u32>::from_iter((0..number_of_vertices).map(|v| {
+            if v < number_of_literals {
+                0
+            } else if v < first_cardinality_vertex {
+                1
+            } else {
+                2 + cnf.cardinality_constraints[v - first_cardinality_vertex].bound
+            }
+        }));
+        let mut partition = Partition {
+            elements: Array::from_iter(0..number_of_vertices),
+            cell_of: Array::default(),
+            cell_ends: Array::default(),
+            number_of_cells: 0,
+        };
+        partition.elements.sort_unstable_by_key(|&v| (colors[v], v));
+        partition.cell_of.resize(number_of_vertices, 0);
+        partition.cell_ends.resize(number_of_vertices + 1, 0);
+        let mut cell_start = 0;
+        for position in 1..=number_of_vertices {
+            if position == number_of_vertices
+                || colors[partition.elements[position]] != colors[partition.elements[position - 1]]
+            {
+                partition.cell_ends[cell_start] = position;
+                partition.number_of_cells += 1;
+                for p in cell_start..position {
+                    partition.cell_of[partition.elements[p]] = cell_start;
+                }
+                cell_start = position;
+            }
+        }
+
+        let mut generators = Array::default();
+        if !self.refine(&mut partition) {
+            return generators;
+        }
+
+        // 最初の葉への経路
+        self.first_path.clear();
+        while let Some(target_cell) = partition.first_non_singleton_cell() {
+            let vertex = partition.elements[target_cell];
+            self.first_path.push((partition.clone(), vertex));
+            partition.individualize(vertex);
+            self.ticks += number_of_vertices as usize;
+            if !self.refine(&mut partition) {
+                return generators;
+            }
+        }
+        self.first_leaf = partition.elements;
+
+        // 経路の深い位置から順に，個別化する頂点を取り替えて最初の葉と同型な葉を探す
+        'loop_for_depths: for depth in (0..self.first_path.len()).rev() {
+            let (node, vertex) = self.first_path[depth].clone();
+            let target_cell = node.first_non_singleton_cell().unwrap();
+            for position in target_cell..node.cell_ends[target_cell] {
+                if self.ticks >= self.effort {
+                    break 'loop_for_depths;
+                }
+                let other_vertex = node.elements[position];
+                if other_vertex == vertex || self.find_orbit(other_vertex) == self.find_orbit(vertex) {
+                    continue;
+                }
+                let mut child = node.clone();
+                child.individualize(other_vertex);
+                if !self.refine(&mut child) {
+                    break 'loop_for_depths;
+                }
+                if let Some(permutation) = self.search(child, depth + 1) {
+                    for v in 0..number_of_vertices {
+                        self.union_orbits(v, permutation[v]);
+                    }
+                    generators
+                        .push(Array::from_iter((0..number_of_literals).map(|bits| {
+                            Literal::new(permutation[bits] / 2, Boolean::from(permutation[bits] % 2 == 1))
+                        })));
+                    self.summary.number_of_generators += 1;
+                }
+            }
+        }
+        return generators;
+    }
+
+    /// 生成元 generator について x ≤_lex generator(x) を表す節を作る
+    ///
+    /// 変数は添字の順に並べ，補助変数 e_j (j 番目までが等しい) は number_of_variables から順に割り当てる．
+    pub fn make_symmetry_breaking_clauses(
+        &mut self,
+        generator: &[Literal],
+        number_of_variables: &mut u32,
+    ) -> Array<u32, Array<u32, Literal>> {
+        let support = Array::<u32, Literal>::from_iter(
+            (0..generator.len() as u32 / 2)
+                .map(|index| Literal::new(index, Boolean::TRUE))
+                .filter(|&literal| generator[literal.bits() as usize] != literal)
+                .take(Self::MAX_SYMMETRY_BREAKING_LENGTH as usize),
+        );
+        let mut clauses = Array::<u32, Array<u32, Literal>>::default();
+        let mut equal: Option<Literal> = None;
+        for k in 0..support.len() {
+            let literal = support[k];
+            let image = generator[literal.bits() as usize];
+            if image == !literal {
+                // e_{j-1} → ¬literal であり，等しくなり得ないので以降は不要
+                clauses.push(Array::from_iter(equal.map(|e| !e).into_iter().chain([!literal])));
+                break;
+            }
+            // e_{j-1} → (literal ≤ image)
+            clauses.push(Array::from_iter(equal.map(|e| !e).into_iter().chain([!literal, image])));
+            if k + 1 == support.len() {
+                break;
+            }
+            // e_{j-1} ∧ (literal = image) → e_j
+            let next_equal = Literal::new(*number_of_variables, Boolean::TRUE);
+            *number_of_variables += 1;
+            clauses.push(Array::from_iter(equal.map(|e| !e).into_iter().chain([!literal, next_equal])));
+            clauses.push(Array::from_iter(equal.map(|e| !e).into_iter().chain([image, next_equal])));
+            equal = Some(next_equal);
+        }
+        self.summary.number_of_symmetry_breaking_clauses += clauses.len();
+        return clauses;
+    }
+
+    /// 最初の経路と同じ形の分割を辿り，最初の葉と同型な葉が見つかれば頂点の置換を返す
+    fn search(&mut self, partition: Partition, depth: u32) -> Option<Array<u32, u32>> {
+        if self.ticks >= self.effort {
+            return None;
+        }
+        if partition.is_discrete() {
+            let mut permutation = Array::default();
+            permutation.resize(partition.elements.len(), 0);
+            for position in 0..partition.elements.len() {
+                permutation[self.first_leaf[position]] = partition.elements[position];
+            }
+            return if self.is_automorphism(&permutation) { Some(permutation) } else { None };
+        }
+        if depth >= self.first_path.len() || !partition.has_same_cells(&self.first_path[depth].0) {
+            return None;
+        }
+        let target_cell = partition.first_non_singleton_cell().unwrap();
+        for position in target_cell..partition.cell_ends[target_cell] {
+            let mut child = partition.clone();
+            child.individualize(partition.elements[position]);
+            self.ticks += partition.elements.len() as usize;
+            if !self.refine(&mut child) {
+                return None;
+            }
+            if let Some(permutation) = self.search(child, depth + 1) {
+                return Some(permutation);
+            }
+        }
+        return None;
+    }
+
+    fn is_automorphism(&mut self, permutation: &Array<u32, u32>) -> bool {
+        for vertex in 0..self.adjacency.len() {
+            let image_neighbors = &self.adjacency[permutation[vertex]];
+            self.ticks += self.adjacency[vertex].len() as usize;
+            if image_neighbors.len() != self.adjacency[vertex].len()
+                || self.adjacency[vertex]
+                    .iter()
+                    .any(|&v| image_neighbors.as_slice().binary_search(&permutation[v]).is_err())
+            {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /// 各頂点の隣接する頂点の属するセルの多重集合でセルを分割することを，変化がなくなるまで繰り返す
+    /// effort を使い切れば false を返す
+    fn refine(&mut self, partition: &mut Partition) -> bool {
+        loop {
+            if self.ticks >= self.effort {
+                return false;
+            }
+            let mut start = 0;
+            while start < partition.elements.len() {
+                let end = partition.cell_ends[start];
+                if end - start > 1 {
+                    for position in start..end {
+                        let vertex = partition.elements[position];
+                        let signature = &mut self.signatures[vertex];
+                        signature.clear();
+                        signature.extend(self.adjacency[vertex].iter().map(|&v| partition.cell_of[v]));
+                        signature.sort_unstable_by_key(|&cell| cell);
+                        self.ticks += signature.len() as usize;
+                    }
+                }
+                start = end;
+            }
+            let mut is_split = false;
+            let mut start = 0;
+            while start < partition.elements.len() {
+                let end = partition.cell_ends[start];
+                if end - start > 1 {
+                    let signatures = &self.signatures;
+                    partition.elements[start..end]
+                        .sort_unstable_by(|&v, &w| signatures[v].as_slice().cmp(signatures[w].as_slice()));
+                    let mut cell_start = start;
+                    for position in start + 1..=end {
+                        if position == end
+                            || signatures[partition.elements[position]].as_slice()
+                                != signatures[partition.elements[position - 1]].as_slice()
+                        {
+                            if cell_start != start || position != end {
+                                is_split = true;
+                                partition.cell_ends[cell_start] = position;
+                                if cell_start != start {
+                                    partition.number_of_cells += 1;
+                                    for p in cell_start..position {
+                                        partition.cell_of[partition.elements[p]] = cell_start;
+                                    }
+                                }
+                            }
+                            cell_start = position;
+                        }
+                    }
+                }
+                start = end;
+            }
+            if !is_split {
+                return true;
+            }
+        }
+    }
+
+    fn find_orbit(&mut self, vertex: u32) -> u32 {
+        let mut root = vertex;
+        while self.orbit_parents[root] != root {
+            root = self.orbit_parents[root];
+        }
+        let mut v = vertex;
+        while self.orbit_parents[v] != root {
+            let parent = self.orbit_parents[v];
+            self.orbit_parents[v] = root;
+            v = parent;
+        }
+        return root;
+    }
+
+    fn union_orbits(&mut self, v: u32, w: u32) {
+        let (v, w) = (self.find_orbit(v), self.find_orbit(w));
+        if v != w {
+            self.orbit_parents[u32::max(v, w)] = u32::min(v, w);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! 理論の単体テストに共通する道具
+//!
+//! 理論を CoreEngine の外側に置いたエンジンを作り，解を総当たりで求めて，伝播・矛盾の説明が解から導かれることと，
+//! バックジャンプで理論の状態が戻ることを確かめる．
+
+use cdcl_engine::{
+    Boolean, CoreEngine, CoreEngineExplainKey, Either, EngineAddConstraintTrait, EngineTrait, Literal, OuterEngine,
+    PropagationResult, Reason, TheoryTrait,
+};
+use rand::{Rng, rngs::SmallRng};
+
+use crate::clause_theory::ClauseTrait;
+
+/// 試験する理論の説明のキーに付ける印
+pub trait TestedExplainKey: Copy {}
+
+#[derive(Clone, Copy)]
+pub enum TestExplainKey<ExplainKeyT> {
+    Theory(ExplainKeyT),
+    /// 単位節の伝播．試験する理論の説明ではないので区別するだけである
+    CoreEngine,
+}
+
+impl<ExplainKeyT: TestedExplainKey> From<ExplainKeyT> for TestExplainKey<ExplainKeyT> {
+    fn from(explain_key: ExplainKeyT) -> Self {
+        return TestExplainKey::Theory(explain_key);
+    }
+}
+
+impl<ExplainKeyT> From<CoreEngineExplainKey> for TestExplainKey<ExplainKeyT> {
+    fn from(_explain_key: CoreEngineExplainKey) -> Self {
+        return TestExplainKey::CoreEngine;
+    }
+}
+
+pub type TestEngine<TheoryT> = OuterEngine<TheoryT, CoreEngine<TestExplainKey<<TheoryT as TheoryTrait>::ExplainKey>>>;
+
+/// 総当たりで確かめる理論
+pub trait TestedTheory: TheoryTrait<ExplainKey: TestedExplainKey> + Sized
+where
+    for<'a> Self::ExplanationConstraint<'a>: ClauseTrait,
+{
+    /// 制約を充足する値の組の表現
+    type Solution: PartialEq;
+
+    /// 解 solution で literal が真であるか
+    fn holds(&self, literal: Literal, solution: &Self::Solution) -> bool;
+
+    /// 全ての変数に割り当てたエンジンの割り当てが表す値の組
+    fn solution(&self, engine: &impl EngineTrait) -> Self::Solution;
+
+    /// バックジャンプで決定した時点の値に戻るべき状態(説明のリテラルの数など)
+    fn checkpoint(&self) -> u32;
+
+    /// 伝播を終えた後に，状態がエンジンの割り当てと整合することを確かめる
+    fn assert_consistent(&self, engine: &impl EngineTrait);
+}
+
+/// number_of_variables 個の変数を持つエンジン
+pub fn make_engine<TheoryT: TestedTheory>(theory: TheoryT, number_of_variables: u32) -> TestEngine<TheoryT>
+where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    let mut engine = OuterEngine::new(theory, CoreEngine::default());
+    for _ in 0..number_of_variables {
+        engine.add_variable(Boolean::FALSE);
+    }
+    return engine;
+}
+
+pub fn add_constraint<TheoryT, ConstraintT>(
+    engine: &mut TestEngine<TheoryT>,
+    constraint: ConstraintT,
+) -> PropagationResult<TestExplainKey<TheoryT::ExplainKey>>
+where
+    TheoryT: TestedTheory,
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+    TestEngine<TheoryT>: EngineAddConstraintTrait<
+            Either<ConstraintT, Literal>,
+            CompositeExplainKey = TestExplainKey<TheoryT::ExplainKey>,
+        >,
+{
+    let constraint: Either<ConstraintT, Literal> = Either::Left(constraint);
+    return engine.add_constraint(constraint, false);
+}
+
+pub fn literal(index: u32, is_positive: bool) -> Literal {
+    return Literal::new(index, Boolean::from(is_positive));
+}
+
+/// 変数の値を bits で表した割り当てでリテラルが真であるか
+pub fn holds_in_bits(literal: Literal, bits: u32) -> bool {
+    return ((bits >> literal.index()) & 1 == 1) == (literal.value() == Boolean::TRUE);
+}
+
+/// 全ての変数に割り当てたエンジンの割り当てを bits で表す
+pub fn assignment_bits(engine: &impl EngineTrait) -> u32 {
+    return (0..engine.number_of_variables())
+        .filter(|&index| engine.is_true(literal(index, true)))
+        .fold(0, |bits, index| bits | (1 << index));
+}
+
+/// number_of_variables 個の変数の割り当てのうち is_satisfied を満たすものを bits で表して返す
+pub fn enumerate_bits(number_of_variables: u32, is_satisfied: impl Fn(u32) -> bool) -> Vec<u32> {
+    return Vec::from_iter((0..1 << number_of_variables).filter(|&bits| is_satisfied(bits)));
+}
+
+/// 節が全ての解で充足されるか
+pub fn is_implied<TheoryT: TestedTheory>(theory: &TheoryT, clause: &[Literal], solutions: &[TheoryT::Solution]) -> bool
+where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    return solutions.iter().all(|solution| clause.iter().any(|&literal| theory.holds(literal, solution)));
+}
+
+pub fn sorted(mut clause: Vec<Literal>) -> Vec<Literal> {
+    clause.sort_unstable_by_key(|literal| (literal.index(), literal.value() == Boolean::TRUE));
+    return clause;
+}
+
+/// 理論が literal を伝播した理由の節．説明がまだなければ作って割り当て理由を置き換える
+pub fn reason_clause<TheoryT: TestedTheory>(engine: &mut TestEngine<TheoryT>, literal: Literal) -> Option<Vec<Literal>>
+where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    let explain_key = match engine.get_reason(literal.index())? {
+        Reason::LazyPropagation { explain_key: TestExplainKey::Theory(explain_key) } => {
+            let explain_key = engine.explain_lazily(literal, Either::Left(explain_key));
+            engine.set_reason(literal.index(), Reason::Propagation { explain_key: explain_key });
+            explain_key
+        }
+        Reason::Propagation { explain_key } => explain_key,
+        _ => return None,
+    };
+    let TestExplainKey::Theory(explain_key) = explain_key else {
+        return None;
+    };
+    return Some(Vec::from_iter(engine.theory().explain_propagation(explain_key).iter_literals()));
+}
+
+/// 矛盾の説明の節
+pub fn conflict_clause<TheoryT: TestedTheory>(
+    engine: &TestEngine<TheoryT>,
+    explain_key: TestExplainKey<TheoryT::ExplainKey>,
+) -> Vec<Literal>
+where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    let TestExplainKey::Theory(explain_key) = explain_key else {
+        unreachable!();
+    };
+    return Vec::from_iter(engine.theory().explain_conflict(explain_key).iter_literals());
+}
+
+/// 伝播した全てのリテラルについて，説明の節が解から導かれ，他のリテラルが先に偽になっていることを確かめる
+pub fn assert_explanations_are_valid<TheoryT: TestedTheory>(
+    engine: &mut TestEngine<TheoryT>,
+    solutions: &[TheoryT::Solution],
+) where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    for assignment_order in 0..engine.number_of_assigneds() {
+        let literal = engine.get_assignment(assignment_order);
+        let Some(clause) = reason_clause(engine, literal) else {
+            continue;
+        };
+        assert!(clause.contains(&literal));
+        for &other in clause.iter().filter(|&&other| other != literal) {
+            assert!(engine.is_false(other));
+            assert!(engine.get_assignment_order(other.index()) < assignment_order);
+        }
+        assert!(is_implied(engine.theory(), &clause, solutions));
+    }
+}
+
+/// 矛盾の説明の節が全て偽で，解から導かれることを確かめる
+pub fn assert_conflict_is_valid<TheoryT: TestedTheory>(
+    engine: &TestEngine<TheoryT>,
+    explain_key: TestExplainKey<TheoryT::ExplainKey>,
+    solutions: &[TheoryT::Solution],
+) where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    let clause = conflict_clause(engine, explain_key);
+    assert!(clause.iter().all(|&literal| engine.is_false(literal)));
+    assert!(is_implied(engine.theory(), &clause, solutions));
+}
+
+/// 制約を加えた結果 result から，無作為な決定と矛盾時の無作為なバックジャンプを繰り返す
+///
+/// 伝播・矛盾の説明が解から導かれること，バックジャンプで割り当ての数と理論の状態が決定した時点に戻ること，
+/// 全ての変数に割り当てれば解になることを確かめる．
+pub fn check_random_search<TheoryT: TestedTheory>(
+    engine: &mut TestEngine<TheoryT>,
+    mut result: PropagationResult<TestExplainKey<TheoryT::ExplainKey>>,
+    solutions: &[TheoryT::Solution],
+    rng: &mut SmallRng,
+) where
+    for<'a> TheoryT::ExplanationConstraint<'a>: ClauseTrait,
+{
+    // 決定ごとに (割り当ての数, 理論の状態) を記録する
+    let mut checkpoints = Vec::new();
+    for _ in 0..100 {
+        if let PropagationResult::Conflict { explain_key } = result {
+            assert_conflict_is_valid(engine, explain_key, solutions);
+            if engine.current_decision_level() == 0 {
+                assert!(solutions.is_empty());
+                return;
+            }
+            let backjump_level = rng.gen_range(0..engine.current_decision_level());
+            let _ = engine.backjump(backjump_level).count();
+            let (number_of_assigneds, checkpoint) = checkpoints[backjump_level as usize];
+            checkpoints.truncate(backjump_level as usize);
+            assert_eq!(engine.number_of_assigneds(), number_of_assigneds);
+            assert_eq!(engine.theory().checkpoint(), checkpoint);
+            engine.theory().assert_consistent(engine);
+            result = PropagationResult::Noconflict;
+            continue;
+        }
+        assert_explanations_are_valid(engine, solutions);
+        engine.theory().assert_consistent(engine);
+        let unassigned = Vec::from_iter((0..engine.number_of_variables()).filter(|&index| !engine.is_assigned(index)));
+        if unassigned.is_empty() {
+            // 理論が変数を作ったときは割り当てを続ける
+            result = engine.final_check();
+            if result.is_conflict() || engine.number_of_assigneds() < engine.number_of_variables() {
+                continue;
+            }
+            assert!(solutions.contains(&engine.theory().solution(engine)));
+            return;
+        }
+        checkpoints.push((engine.number_of_assigneds(), engine.theory().checkpoint()));
+        let index = unassigned[rng.gen_range(0..unassigned.len())];
+        result = engine.assign(literal(index, rng.gen_bool(0.5)), Reason::Decision);
+    }
+}
@@ -0,0 +1,155 @@
+use cdcl_engine::{Boolean, EngineTrait, Literal, LiteralArray};
+use rand::{Rng, rngs::SmallRng};
+use utility::Array;
+
+/// 節だけを対象とする WalkSAT 風の局所探索
+///
+/// 決定レベル 0 で割り当てられた変数は固定し，与えられた位相から始めて，充足されない節の数が最も少なかった割り当てを位相に書き戻す．
+/// 充足されない節を無作為に選び，その節のリテラルのうち反転しても新たに充足されなくなる節(break)が最も少ないものを反転する．
+/// break が 0 のリテラルがなければ，確率 noise で節の中から無作為に選ぶ．
+#[derive(Default)]
+pub struct Walk {
+    /// 決定レベル 0 で充足されていない節から偽のリテラルを除いたもの
+    clauses: Array<u32, Array<u32, Literal>>,
+    /// リテラル -> 含む節
+    occurrences: LiteralArray<Array<u32, u32>>,
+    number_of_true_literals: Array<u32, u32>,
+    unsatisfied_clauses: Array<u32, u32>,
+    /// 節 -> unsatisfied_clauses での位置(充足されていれば NULL)
+    positions: Array<u32, u32>,
+    values: Array<u32, Boolean>,
+    candidates: Array<u32, Literal>,
+}
+
+impl Walk {
+    const NULL: u32 = u32::MAX;
+
+    /// 決定レベル 0 で呼び出し，最良の割り当てで充足されない節の数を返す
+    pub fn walk(
+        &mut self,
+        clauses: &Array<u32, Array<u32, Literal>>,
+        engine: &impl EngineTrait,
+        phases: &mut Array<u32, Boolean>,
+        noise: f64,
+        mut effort: usize,
+        rng: &mut SmallRng,
+    ) -> u32 {
+        debug_assert!(engine.current_decision_level() == 0);
+        self.initialize(clauses, engine, phases);
+        let mut min_number_of_unsatisfied_clauses = self.unsatisfied_clauses.len();
+        phases.clear();
+        phases.extend(self.values.iter().copied());
+        while !self.unsatisfied_clauses.is_empty() && effort > 0 {
+            let clause_id = self.unsatisfied_clauses[rng.gen_range(0..self.unsatisfied_clauses.len())];
+            // break が最小のリテラルを集める
+            self.candidates.clear();
+            let mut min_break_count = u32::MAX;
+            for &literal in self.clauses[clause_id].iter() {
+                let occurrences = &self.occurrences[!literal];
+                effort = effort.saturating_sub(occurrences.len() as usize);
+                let break_count =
+                    occurrences.iter().filter(|&&clause_id| self.number_of_true_literals[clause_id] == 1).count()
+                        as u32;
+                if break_count < min_break_count {
+                    min_break_count = break_count;
+                    self.candidates.clear();
+                }
+                if break_count == min_break_count {
+                    self.candidates.push(literal);
+                }
+            }
+            let literal = if min_break_count > 0 && rng.gen_bool(noise) {
+                let clause = &self.clauses[clause_id];
+                clause[rng.gen_range(0..clause.len())]
+            } else {
+                self.candidates[rng.gen_range(0..self.candidates.len())]
+            };
+            effort = effort.saturating_sub(self.flip(literal) as usize);
+            if self.unsatisfied_clauses.len() < min_number_of_unsatisfied_clauses {
+                min_number_of_unsatisfied_clauses = self.unsatisfied_clauses.len();
+                phases.clear();
+                phases.extend(self.values.iter().copied());
+            }
+        }
+        return min_number_of_unsatisfied_clauses;
+    }
+
+    fn initialize(
+        &mut self,
+        clauses: &Array<u32, Array<u32, Literal>>,
+        engine: &impl EngineTrait,
+        phases: &Array<u32, Boolean>,
+    ) {
+        let number_of_variables = engine.number_of_variables();
+        self.values.clear();
+        self.values.extend(
+            (0..number_of_variables)
+                .map(|index| if engine.is_assigned(index) { engine.get_value(index) } else { phases[index] }),
+        );
+        self.occurrences = LiteralArray::default();
+        self.occurrences.resize_with(number_of_variables, || [Array::default(), Array::default()]);
+        self.clauses.clear();
+        for clause in clauses.iter() {
+            if clause.iter().any(|&literal| engine.is_true(literal)) {
+                continue;
+            }
+            let clause =
+                Array::from_iter(clause.iter().copied().filter(|&literal| !engine.is_assigned(literal.index())));
+            if clause.is_empty() {
+                continue;
+            }
+            let clause_id = self.clauses.len();
+            for &literal in clause.iter() {
+                self.occurrences[literal].push(clause_id);
+            }
+            self.clauses.push(clause);
+        }
+        self.number_of_true_literals.clear();
+        self.unsatisfied_clauses.clear();
+        self.positions.clear();
+        for clause_id in 0..self.clauses.len() {
+            let number_of_true_literals =
+                self.clauses[clause_id].iter().filter(|&&literal| self.is_true(literal)).count() as u32;
+            self.number_of_true_literals.push(number_of_true_literals);
+            if number_of_true_literals == 0 {
+                self.positions.push(self.unsatisfied_clauses.len());
+                self.unsatisfied_clauses.push(clause_id);
+            } else {
+                self.positions.push(Self::NULL);
+            }
+        }
+    }
+
+    fn is_true(&self, literal: Literal) -> bool {
+        return self.values[literal.index()] == literal.value();
+    }
+
+    /// literal を真にし，走査した出現の数を返す
+    fn flip(&mut self, literal: Literal) -> u32 {
+        debug_assert!(!self.is_true(literal));
+        self.values[literal.index()] = literal.value();
+        for i in 0..self.occurrences[literal].len() {
+            let clause_id = self.occurrences[literal][i];
+            self.number_of_true_literals[clause_id] += 1;
+            if self.number_of_true_literals[clause_id] == 1 {
+                // 充足されない節から除く
+                let position = self.positions[clause_id];
+                let last_clause_id = *self.unsatisfied_clauses.last().unwrap();
+                self.unsatisfied_clauses.swap_remove(position);
+                if last_clause_id != clause_id {
+                    self.positions[last_clause_id] = position;
+                }
+                self.positions[clause_id] = Self::NULL;
+            }
+        }
+        for i in 0..self.occurrences[!literal].len() {
+            let clause_id = self.occurrences[!literal][i];
+            self.number_of_true_literals[clause_id] -= 1;
+            if self.number_of_true_literals[clause_id] == 0 {
+                self.positions[clause_id] = self.unsatisfied_clauses.len();
+                self.unsatisfied_clauses.push(clause_id);
+            }
+        }
+        return self.occurrences[literal].len() + self.occurrences[!literal].len();
+    }
+}
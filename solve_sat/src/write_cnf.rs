@@ -0,0 +1,104 @@
+use cdcl_engine::{Boolean, Literal};
+
+use crate::{cnf::CNF, integer_theory::IntegerConstraint};
+
+pub fn format_literal(literal: Literal) -> i64 {
+    let variable = literal.index() as i64 + 1;
+    return if literal.value() == Boolean::TRUE { variable } else { -variable };
+}
+
+pub fn write_cnf(writer: &mut impl std::io::Write, cnf: &CNF) -> std::io::Result<()> {
+    if cnf.cardinality_constraints.is_empty() && cnf.pseudo_boolean_constraints.is_empty() {
+        let number_of_constraints = cnf.clauses.len()
+            + cnf.xor_constraints.len()
+            + cnf.acyclicity_edges.len()
+            + cnf.difference_atoms.len()
+            + cnf.integer_variables.len()
+            + cnf.order_literals.len()
+            + cnf.integer_constraints.len();
+        writeln!(writer, "p cnf {} {}", cnf.number_of_variables, number_of_constraints)?;
+    } else {
+        let number_of_constraints = cnf.clauses.len()
+            + cnf.cardinality_constraints.len()
+            + cnf.pseudo_boolean_constraints.len()
+            + cnf.xor_constraints.len()
+            + cnf.acyclicity_edges.len()
+            + cnf.difference_atoms.len()
+            + cnf.integer_variables.len()
+            + cnf.order_literals.len()
+            + cnf.integer_constraints.len();
+        writeln!(writer, "p knf {} {}", cnf.number_of_variables, number_of_constraints)?;
+    }
+    for clause in cnf.clauses.iter() {
+        for &literal in clause.iter() {
+            write!(writer, "{} ", format_literal(literal))?;
+        }
+        writeln!(writer, "0")?;
+    }
+    for constraint in cnf.cardinality_constraints.iter() {
+        write!(writer, "k {} ", constraint.bound)?;
+        for &literal in constraint.literals.iter() {
+            write!(writer, "{} ", format_literal(literal))?;
+        }
+        writeln!(writer, "0")?;
+    }
+    for constraint in cnf.pseudo_boolean_constraints.iter() {
+        write!(writer, "b {} ", constraint.degree)?;
+        for &(coefficient, literal) in constraint.terms.iter() {
+            write!(writer, "{} {} ", coefficient, format_literal(literal))?;
+        }
+        writeln!(writer, "0")?;
+    }
+    for constraint in cnf.xor_constraints.iter() {
+        // 先頭の変数の符号で parity を表す
+        assert!(!constraint.variables.is_empty());
+        write!(writer, "x")?;
+        for (k, &index) in constraint.variables.iter().enumerate() {
+            let literal = Literal::new(index, Boolean::from(k != 0 || constraint.parity));
+            write!(writer, "{} ", format_literal(literal))?;
+        }
+        writeln!(writer, "0")?;
+    }
+    for edge in cnf.acyclicity_edges.iter() {
+        writeln!(writer, "e {} {} {} 0", edge.from, edge.to, format_literal(edge.literal))?;
+    }
+    for atom in cnf.difference_atoms.iter() {
+        writeln!(writer, "d {} {} {} {} 0", atom.x, atom.y, atom.bound, format_literal(atom.literal))?;
+    }
+    for (variable, &(lower, upper)) in cnf.integer_variables.iter().enumerate() {
+        writeln!(writer, "z {} {} {} 0", variable, lower, upper)?;
+    }
+    for order_literal in cnf.order_literals.iter() {
+        writeln!(
+            writer,
+            "o {} {} {} 0",
+            format_literal(order_literal.literal),
+            order_literal.variable,
+            order_literal.value
+        )?;
+    }
+    for constraint in cnf.integer_constraints.iter() {
+        match constraint {
+            IntegerConstraint::Linear { terms, bound } => {
+                write!(writer, "l {} ", bound)?;
+                for &(coefficient, variable) in terms.iter() {
+                    write!(writer, "{} {} ", coefficient, variable)?;
+                }
+            }
+            IntegerConstraint::AllDifferent { variables } => {
+                write!(writer, "a ")?;
+                for &variable in variables.iter() {
+                    write!(writer, "{} ", variable)?;
+                }
+            }
+            IntegerConstraint::Element { index, result, values } => {
+                write!(writer, "m {} {} ", index, result)?;
+                for &value in values.iter() {
+                    write!(writer, "{} ", value)?;
+                }
+            }
+        }
+        writeln!(writer, "0")?;
+    }
+    return Ok(());
+}
@@ -0,0 +1,596 @@
+use cdcl_engine::{
+    Boolean, EngineTrait, Literal, ProgressColumnsTrait, PropagationResult, Reason, TheoryAddConstraintTrait,
+    TheoryTrait, UndoLog,
+};
+use either::Either;
+use utility::Array;
+
+use crate::clause_theory::{ClauseTrait, ClauseView};
+
+/// XOR 制約 (variables の排他的論理和) = parity
+///
+/// 同じ変数は高々一度しか現れない．
+#[derive(Clone)]
+pub struct XorConstraint {
+    pub variables: Array<u32, u32>,
+    pub parity: bool,
+}
+
+impl XorConstraint {
+    /// literals の排他的論理和が真であるという制約
+    ///
+    /// 否定リテラルは変数に置き換えて parity を反転し，同じ変数の組は打ち消し合うので取り除く．
+    pub fn new(literals: impl Iterator<Item = Literal>) -> Self {
+        let mut variables = Array::<u32, u32>::default();
+        let mut parity = true;
+        for literal in literals {
+            if literal.value() == Boolean::FALSE {
+                parity = !parity;
+            }
+            if let Some(k) = variables.iter().position(|&index| index == literal.index()) {
+                variables.swap_remove(k as u32);
+            } else {
+                variables.push(literal.index());
+            }
+        }
+        return Self { variables: variables, parity: parity };
+    }
+
+    /// 同値な 2^(len - 1) 個の節(変数がなく parity が真であれば空節)を返す
+    pub fn clauses(&self) -> Array<u32, Array<u32, Literal>> {
+        let mut clauses = Array::default();
+        for assignment in 0..1u64 << self.variables.len() {
+            if (assignment.count_ones() % 2 == 1) == self.parity {
+                continue;
+            }
+            // 制約を満たさない割り当て assignment を禁止する
+            clauses.push(Array::from_iter(
+                self.variables
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &index)| Literal::new(index, Boolean::from((assignment >> k) & 1 == 0))),
+            ));
+        }
+        return clauses;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum XorExplainKey {
+    /// literal と explanation_literals[start..end] (偽のリテラル)からなる節
+    Propagation { literal: Literal, start: u32, end: u32 },
+    /// conflicting_literals からなる節
+    Conflict,
+}
+
+#[derive(Default, Clone)]
+pub struct XorTheorySummary {
+    pub number_of_xor_constraints: u32,
+    pub number_of_xor_propagations: u32,
+    pub number_of_gauss_jordan_pivots: u32,
+}
+
+impl ProgressColumnsTrait for XorTheorySummary {
+    const HEADERS: &'static [&'static str] = &["XOR_PROPAGATION", "GAUSS_JORDAN_PIVOT"];
+
+    fn values(&self) -> impl Iterator<Item = u32> {
+        return [self.number_of_xor_propagations, self.number_of_gauss_jordan_pivots].into_iter();
+    }
+}
+
+/// GF(2) 上の行列の行
+#[derive(Clone)]
+struct Row {
+    /// 列 -> 係数 (64 列ずつ詰める)
+    bits: Array<u32, u64>,
+    parity: bool,
+    /// 基底変数の列(この行にだけ現れる)
+    basic_column: u32,
+    /// 監視している基底変数以外の列
+    watched_column: u32,
+}
+
+impl Row {
+    fn contains(&self, column: u32) -> bool {
+        return column != XorTheory::NULL && (self.bits[column / 64] >> (column % 64)) & 1 == 1;
+    }
+
+    fn columns(&self) -> impl Iterator<Item = u32> + '_ {
+        return self.bits.iter().enumerate().flat_map(|(k, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let column = 64 * k as u32 + word.trailing_zeros();
+                word &= word - 1;
+                return Some(column);
+            })
+        });
+    }
+}
+
+/// XOR 制約の理論
+///
+/// XOR 制約に現れる変数を列とする GF(2) 上の行列を既約行階段形に保ち(Gauss-Jordan 消去)，各行で基底変数と
+/// それ以外の一つの列を監視する．監視している列が割り当てられると，割り当てられていない列に監視を移し，
+/// 基底変数が割り当てられた場合はその列で掃き出しなおして基底変数を割り当てられていない変数にする．
+/// 割り当てられていない列が一つだけになった行はその列を伝播し，なくなった行は偶奇を調べて矛盾を検出する．
+/// 行の基本変形は制約の同値変形なので，バックジャンプしても行列は元に戻さない．
+/// 伝播・矛盾の説明は，その時点の行に現れる偽のリテラルからなる節として伝播時に記録する．
+#[derive(Default, Clone)]
+pub struct XorTheory {
+    rows: Array<u32, Row>,
+    /// 変数 -> 列 (XOR 制約に現れなければ NULL)
+    column_of_variable: Array<u32, u32>,
+    /// 列 -> 変数
+    variable_of_column: Array<u32, u32>,
+    /// 列 -> その列を監視している行の番号(監視をやめた行や重複を含みうるので，列が割り当てられたときに取り除く)
+    watches: Array<u32, Array<u32, u32>>,
+    /// 基底変数・監視する列を選びなおす行
+    queued_rows: Array<u32, u32>,
+    is_queued: Array<u32, bool>,
+    /// 割り当てられていない列が一つ以下になった行
+    unit_rows: Array<u32, u32>,
+    /// 行 -> watches から重複を取り除くときの印
+    stamps: Array<u32, u32>,
+    stamp: u32,
+    pivot_bits: Array<u32, u64>,
+    explanation_literals: Array<u32, Literal>,
+    conflicting_literals: Array<u32, Literal>,
+    summary: XorTheorySummary,
+}
+
+impl XorTheory {
+    const NULL: u32 = u32::MAX;
+
+    fn add_column(&mut self, index: u32) -> u32 {
+        let column = self.variable_of_column.len();
+        self.column_of_variable[index] = column;
+        self.variable_of_column.push(index);
+        self.watches.push(Array::default());
+        let number_of_words = column / 64 + 1;
+        for row in self.rows.iter_mut() {
+            row.bits.resize(number_of_words, 0);
+        }
+        return column;
+    }
+
+    fn is_assigned_column(&self, column: u32, engine: &impl EngineTrait) -> bool {
+        return engine.is_assigned(self.variable_of_column[column]);
+    }
+
+    fn enqueue(&mut self, row_id: u32) {
+        if !self.is_queued[row_id] {
+            self.is_queued[row_id] = true;
+            self.queued_rows.push(row_id);
+        }
+    }
+
+    /// row_id 行の column 列を基底変数にし，他の行からその列を消去する
+    fn pivot(&mut self, row_id: u32, column: u32) {
+        self.summary.number_of_gauss_jordan_pivots += 1;
+        self.pivot_bits.clear();
+        self.pivot_bits.extend(self.rows[row_id].bits.iter().cloned());
+        let parity = self.rows[row_id].parity;
+        for other_row_id in 0..self.rows.len() {
+            if other_row_id == row_id || !self.rows[other_row_id].contains(column) {
+                continue;
+            }
+            let other_row = &mut self.rows[other_row_id];
+            for (word, &pivot_word) in other_row.bits.iter_mut().zip(self.pivot_bits.iter()) {
+                *word ^= pivot_word;
+            }
+            other_row.parity ^= parity;
+            self.enqueue(other_row_id);
+        }
+        let row = &mut self.rows[row_id];
+        if row.watched_column == column {
+            row.watched_column = Self::NULL;
+        }
+        row.basic_column = column;
+        self.watches[column].push(row_id);
+    }
+
+    /// 基底変数と監視する列を選びなおし，割り当てられていない列が一つ以下であれば unit_rows に積む
+    fn update_row(&mut self, row_id: u32, engine: &impl EngineTrait) {
+        let row = &self.rows[row_id];
+        let pivot_column = if self.is_assigned_column(row.basic_column, engine) {
+            row.columns()
+                .find(|&column| column != row.watched_column && !self.is_assigned_column(column, engine))
+        } else {
+            None
+        };
+        if let Some(column) = pivot_column {
+            self.pivot(row_id, column);
+        }
+        let row = &self.rows[row_id];
+        let basic_column = row.basic_column;
+        let watched_column = row.watched_column;
+        let is_watched_column_valid = watched_column != basic_column
+            && row.contains(watched_column)
+            && !self.is_assigned_column(watched_column, engine);
+        if !is_watched_column_valid {
+            // 割り当てられていない列がなければ，バックジャンプで最初に割り当てが取り消される列を監視する
+            let column = row
+                .columns()
+                .filter(|&column| column != basic_column)
+                .max_by_key(|&column| match self.is_assigned_column(column, engine) {
+                    true => engine.get_assignment_order(self.variable_of_column[column]),
+                    false => u32::MAX,
+                })
+                .unwrap_or(Self::NULL);
+            self.rows[row_id].watched_column = column;
+            if column != Self::NULL && column != watched_column {
+                self.watches[column].push(row_id);
+            }
+        }
+        let row = &self.rows[row_id];
+        if self.is_assigned_column(row.basic_column, engine)
+            || row.watched_column == Self::NULL
+            || self.is_assigned_column(row.watched_column, engine)
+        {
+            self.unit_rows.push(row_id);
+        }
+    }
+
+    /// 基底変数・監視する列を選びなおす必要のある行を全て処理する
+    fn update_queued_rows(&mut self, engine: &impl EngineTrait) {
+        while let Some(row_id) = self.queued_rows.pop() {
+            self.is_queued[row_id] = false;
+            self.update_row(row_id, engine);
+        }
+    }
+
+    /// column 列を監視していない行と重複を watches から取り除く
+    fn clean_watches(&mut self, column: u32) {
+        self.stamp += 1;
+        let watches = &mut self.watches[column];
+        let mut k = 0;
+        while k < watches.len() {
+            let row_id = watches[k];
+            let row = &self.rows[row_id];
+            if (row.basic_column != column && row.watched_column != column) || self.stamps[row_id] == self.stamp {
+                watches.swap_remove(k);
+            } else {
+                self.stamps[row_id] = self.stamp;
+                k += 1;
+            }
+        }
+    }
+
+    /// 割り当てられていない列が一つ以下の行について，矛盾の検出と伝播を行う
+    fn propagate_unit_rows<EngineT>(
+        &mut self,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<u32>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<XorExplainKey>,
+    {
+        while let Some(row_id) = self.unit_rows.pop() {
+            let row = &self.rows[row_id];
+            let mut parity = row.parity;
+            let mut unassigned_variable = None;
+            let mut is_unit = true;
+            for column in row.columns() {
+                let index = self.variable_of_column[column];
+                if !engine.is_assigned(index) {
+                    if unassigned_variable.is_some() {
+                        is_unit = false;
+                        break;
+                    }
+                    unassigned_variable = Some(index);
+                } else if engine.get_value(index) == Boolean::TRUE {
+                    parity = !parity;
+                }
+            }
+            if !is_unit {
+                continue;
+            }
+            let false_literals = row
+                .columns()
+                .map(|column| self.variable_of_column[column])
+                .filter(|&index| engine.is_assigned(index))
+                .map(|index| Literal::new(index, !engine.get_value(index)));
+            let Some(index) = unassigned_variable else {
+                if parity {
+                    self.conflicting_literals.clear();
+                    self.conflicting_literals.extend(false_literals);
+                    self.unit_rows.clear();
+                    return PropagationResult::Conflict { explain_key: XorExplainKey::Conflict.into() };
+                }
+                continue;
+            };
+            let start = self.explanation_literals.len();
+            self.explanation_literals.extend(false_literals);
+            let end = self.explanation_literals.len();
+            undo_log.push(start);
+            self.summary.number_of_xor_propagations += 1;
+            let literal = Literal::new(index, Boolean::from(parity));
+            let explain_key = XorExplainKey::Propagation { literal: literal, start: start, end: end };
+            let result = engine.assign(literal, Reason::Propagation { explain_key: explain_key.into() });
+            if result.is_conflict() {
+                self.unit_rows.clear();
+                return result;
+            }
+        }
+        return PropagationResult::Noconflict;
+    }
+}
+
+impl TheoryTrait for XorTheory {
+    type ExplainKey = XorExplainKey;
+    type ExplanationConstraint<'a> = impl ClauseTrait + 'a;
+    type Summary = XorTheorySummary;
+    /// 伝播の説明を explanation_literals に積んだ区間の始まり
+    type UndoEntry = u32;
+
+    fn add_variable(&mut self) {
+        self.column_of_variable.push(Self::NULL);
+    }
+
+    fn assign<EngineT>(
+        &mut self,
+        assigned_literal: Literal,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT: EngineTrait,
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        debug_assert!(engine.is_true(assigned_literal));
+        let column = self.column_of_variable[assigned_literal.index()];
+        if column == Self::NULL {
+            return PropagationResult::Noconflict;
+        }
+        // 行列の変形を全て終えてから伝播するので，矛盾で途中で戻っても監視は保たれる
+        let watches = std::mem::take(&mut self.watches[column]);
+        for &row_id in watches.iter() {
+            let row = &self.rows[row_id];
+            if row.basic_column == column || row.watched_column == column {
+                self.enqueue(row_id);
+            }
+        }
+        self.watches[column] = watches;
+        self.update_queued_rows(engine);
+        self.clean_watches(column);
+        return self.propagate_unit_rows(engine, undo_log);
+    }
+
+    fn explain_propagation(&self, explain_key: XorExplainKey) -> Self::ExplanationConstraint<'_> {
+        return match explain_key {
+            XorExplainKey::Propagation { literal, start, end } => ClauseView::new(Either::Left(
+                [literal].into_iter().chain(self.explanation_literals[start..end].iter().cloned()),
+            )),
+            XorExplainKey::Conflict => ClauseView::new(Either::Right(self.conflicting_literals.iter().cloned())),
+        };
+    }
+
+    fn undo(&mut self, start: u32) {
+        // 行列も監視も割り当てに依らずに正しいので，取り消された割り当ての説明だけを捨てる
+        self.explanation_literals.truncate(start);
+    }
+
+    /// 変数が XOR 制約に現れるか否か
+    fn contains_variable(&self, index: u32) -> bool {
+        return self.column_of_variable[index] != Self::NULL;
+    }
+
+    fn reduce_constraints(&mut self) {
+        // 学習しないのでなにもしない
+    }
+
+    fn summary(&self) -> Self::Summary {
+        return self.summary.clone();
+    }
+}
+
+impl TheoryAddConstraintTrait<XorConstraint> for XorTheory {
+    fn add_constraint<EngineT: EngineTrait>(
+        &mut self,
+        constraint: XorConstraint,
+        _is_learnt: bool,
+        engine: &mut EngineT,
+        undo_log: &mut UndoLog<Self::UndoEntry>,
+    ) -> PropagationResult<EngineT::CompositeExplainKey>
+    where
+        EngineT::CompositeExplainKey: From<Self::ExplainKey>,
+    {
+        for &index in constraint.variables.iter() {
+            if self.column_of_variable[index] == Self::NULL {
+                self.add_column(index);
+            }
+        }
+        let mut row = Row {
+            bits: Array::default(),
+            parity: constraint.parity,
+            basic_column: Self::NULL,
+            watched_column: Self::NULL,
+        };
+        row.bits.resize(self.variable_of_column.len().div_ceil(64), 0);
+        for &index in constraint.variables.iter() {
+            let column = self.column_of_variable[index];
+            row.bits[column / 64] |= 1 << (column % 64);
+        }
+        // 既存の行の基底変数を消去する
+        for &index in constraint.variables.iter() {
+            let column = self.column_of_variable[index];
+            if let Some(other_row) = self.rows.iter().find(|other_row| other_row.basic_column == column) {
+                for (word, &other_word) in row.bits.iter_mut().zip(other_row.bits.iter()) {
+                    *word ^= other_word;
+                }
+                row.parity ^= other_row.parity;
+            }
+        }
+        let Some(column) = row
+            .columns()
+            .find(|&column| !self.is_assigned_column(column, engine))
+            .or_else(|| row.columns().next())
+        else {
+            if row.parity {
+                // 0 = 1 が導かれたので矛盾
+                self.conflicting_literals.clear();
+                return PropagationResult::Conflict { explain_key: XorExplainKey::Conflict.into() };
+            }
+            return PropagationResult::Noconflict;
+        };
+        let row_id = self.rows.len();
+        self.rows.push(row);
+        self.is_queued.push(false);
+        self.stamps.push(0);
+        self.summary.number_of_xor_constraints += 1;
+        self.pivot(row_id, column);
+        self.enqueue(row_id);
+        self.update_queued_rows(engine);
+        return self.propagate_unit_rows(engine, undo_log);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cdcl_engine::{Boolean, EngineTrait, Literal, PropagationResult, Reason};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use super::{XorConstraint, XorExplainKey, XorTheory};
+    use crate::theory_test::{
+        TestEngine, TestExplainKey, TestedExplainKey, TestedTheory, assert_conflict_is_valid,
+        assert_explanations_are_valid, assignment_bits, check_random_search, conflict_clause, enumerate_bits,
+        holds_in_bits, literal, make_engine, reason_clause, sorted,
+    };
+
+    impl TestedExplainKey for XorExplainKey {}
+
+    impl TestedTheory for XorTheory {
+        type Solution = u32;
+
+        fn holds(&self, literal: Literal, bits: &u32) -> bool {
+            return holds_in_bits(literal, *bits);
+        }
+
+        fn solution(&self, engine: &impl EngineTrait) -> u32 {
+            return assignment_bits(engine);
+        }
+
+        fn checkpoint(&self) -> u32 {
+            return self.explanation_literals.len();
+        }
+
+        /// 行列が既約行階段形で，監視が割り当てと整合する
+        ///
+        /// 伝播を終えた後は，監視している列が割り当てられた行は全ての列が割り当てられて充足されている．
+        fn assert_consistent(&self, engine: &impl EngineTrait) {
+            for (row_id, row) in self.rows.iter().enumerate() {
+                let row_id = row_id as u32;
+                assert!(row.contains(row.basic_column));
+                assert!(self.rows.iter().filter(|other_row| other_row.contains(row.basic_column)).count() == 1);
+                assert!(self.watches[row.basic_column].contains(&row_id));
+                if row.watched_column != XorTheory::NULL {
+                    assert!(row.watched_column != row.basic_column && row.contains(row.watched_column));
+                    assert!(self.watches[row.watched_column].contains(&row_id));
+                }
+                let is_assigned = |column: u32| engine.is_assigned(self.variable_of_column[column]);
+                let number_of_unassigneds = row.columns().filter(|&column| !is_assigned(column)).count();
+                assert!(number_of_unassigneds != 1);
+                if is_assigned(row.basic_column)
+                    || row.watched_column == XorTheory::NULL
+                    || is_assigned(row.watched_column)
+                {
+                    assert_eq!(number_of_unassigneds, 0);
+                    let number_of_trues = row
+                        .columns()
+                        .filter(|&column| engine.get_value(self.variable_of_column[column]) == Boolean::TRUE)
+                        .count();
+                    assert_eq!(number_of_trues % 2 == 1, row.parity);
+                }
+            }
+        }
+    }
+
+    /// literals の排他的論理和が真であるという制約を加える
+    fn add_constraint(
+        engine: &mut TestEngine<XorTheory>,
+        literals: &[Literal],
+    ) -> PropagationResult<TestExplainKey<XorExplainKey>> {
+        return crate::theory_test::add_constraint(engine, XorConstraint::new(literals.iter().cloned()));
+    }
+
+    fn enumerate_solutions(number_of_variables: u32, constraints: &[Vec<Literal>]) -> Vec<u32> {
+        return enumerate_bits(number_of_variables, |bits| {
+            constraints
+                .iter()
+                .all(|literals| literals.iter().filter(|&&literal| holds_in_bits(literal, bits)).count() % 2 == 1)
+        });
+    }
+
+    #[test]
+    fn test_eliminated_row_propagates_implied_xor() {
+        // x0 ^ x1 ^ x2 = 1, x1 ^ x2 ^ x3 = 0 から x0 ^ x3 = 1 が導かれる
+        let constraints = vec![
+            vec![literal(0, true), literal(1, true), literal(2, true)],
+            vec![literal(1, true), literal(2, true), literal(3, false)],
+        ];
+        let solutions = enumerate_solutions(4, &constraints);
+        let mut engine = make_engine(XorTheory::default(), 4);
+        for literals in constraints.iter() {
+            assert!(add_constraint(&mut engine, literals).is_no_conflict());
+        }
+        assert_eq!(engine.number_of_assigneds(), 0);
+        assert!(engine.assign(literal(0, true), Reason::Decision).is_no_conflict());
+        assert!(engine.is_false(literal(3, true)));
+        assert!(!engine.is_assigned(1) && !engine.is_assigned(2));
+        let clause = sorted(reason_clause(&mut engine, literal(3, false)).unwrap());
+        assert!(clause == vec![literal(0, false), literal(3, false)]);
+        // x1 ^ x2 = 0 なので x1 = 0 から x2 = 0 が従う
+        assert!(engine.assign(literal(1, false), Reason::Decision).is_no_conflict());
+        assert!(engine.is_false(literal(2, true)));
+        assert_explanations_are_valid(&mut engine, &solutions);
+        engine.theory().assert_consistent(&engine);
+        let _ = engine.backjump(0).count();
+        assert_eq!(engine.number_of_assigneds(), 0);
+        assert_eq!(engine.theory().explanation_literals.len(), 0);
+        engine.theory().assert_consistent(&engine);
+    }
+
+    #[test]
+    fn test_inconsistent_xors_conflict_at_root() {
+        // x0 ^ x1 = 1 と x0 ^ x1 = 0 は同時に満たせない
+        let mut engine = make_engine(XorTheory::default(), 2);
+        assert!(add_constraint(&mut engine, &[literal(0, true), literal(1, true)]).is_no_conflict());
+        let PropagationResult::Conflict { explain_key } =
+            add_constraint(&mut engine, &[literal(0, true), literal(1, false)])
+        else {
+            panic!("expected a conflict");
+        };
+        assert_conflict_is_valid(&engine, explain_key, &[]);
+        assert!(conflict_clause(&engine, explain_key).is_empty());
+    }
+
+    fn random_constraint(rng: &mut SmallRng, number_of_variables: u32) -> Vec<Literal> {
+        return Vec::from_iter(
+            (0..rng.gen_range(1..5)).map(|_| literal(rng.gen_range(0..number_of_variables), rng.gen_bool(0.5))),
+        );
+    }
+
+    #[test]
+    fn test_random_propagations_explanations_and_rollback() {
+        let mut rng = SmallRng::seed_from_u64(23);
+        for _ in 0..500 {
+            let number_of_variables = rng.gen_range(2..9);
+            let constraints =
+                Vec::from_iter((0..rng.gen_range(1..6)).map(|_| random_constraint(&mut rng, number_of_variables)));
+            let solutions = enumerate_solutions(number_of_variables, &constraints);
+            let mut engine = make_engine(XorTheory::default(), number_of_variables);
+            let mut result = PropagationResult::Noconflict;
+            for literals in constraints.iter() {
+                result = add_constraint(&mut engine, literals);
+                if result.is_conflict() {
+                    break;
+                }
+            }
+            check_random_search(&mut engine, result, &solutions, &mut rng);
+        }
+    }
+}
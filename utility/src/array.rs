@@ -170,6 +170,11 @@ where
         self.vec.swap_remove(index.to_usize())
     }
 
+    #[inline(always)]
+    pub fn insert(&mut self, index: UIntT, value: ValueT) {
+        self.vec.insert(index.to_usize(), value);
+    }
+
     #[inline(always)]
     pub fn sort_unstable_by_key<F, K>(&mut self, f: F)
     where
@@ -255,6 +260,15 @@ where
     }
 }
 
+impl<SizeT, ValueT> std::ops::IndexMut<std::ops::Range<SizeT>> for Array<SizeT, ValueT>
+where
+    SizeT: UnsignedInt,
+{
+    fn index_mut(&mut self, index: std::ops::Range<SizeT>) -> &mut Self::Output {
+        &mut self.vec[index.start.to_usize()..index.end.to_usize()]
+    }
+}
+
 impl<SizeT, ValueT> std::ops::Index<std::ops::RangeInclusive<SizeT>> for Array<SizeT, ValueT>
 where
     SizeT: UnsignedInt,
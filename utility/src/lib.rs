@@ -11,6 +11,7 @@ mod priority_queue;
 mod set;
 
 pub use array::Array;
+pub use calculate_gcd::CalculateGCD;
 pub use heaped_map::HeapedMap;
 pub use index::UnsignedInt;
 pub use map::Map;